@@ -0,0 +1,323 @@
+//
+// Loads block definitions from json files, so new blocks can be added without recompiling
+//
+
+use crate::block::{Block, RenderLayer};
+use crate::block::registry::BlockRegistry;
+use crate::block::state::BlockProperty;
+use crate::block::shape::{BlockShape, BlockBox};
+use crate::services::asset_service::models::MODEL_FACES;
+use crate::services::settings_service::SettingsService;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+
+/// The blocks the game ships with, extra definitions are read from resources/blocks/
+const DEFAULT_BLOCKS: &str = include_str!("../../assets/blocks/blocks.json");
+
+/// Texture used for a face that has no texture defined, shows up as the missing texture in game
+const MISSING_TEXTURE: &str = "textures/block/missing";
+
+#[derive(Deserialize)]
+struct BlockDefinition {
+    /// Every block needs a fixed id. Ids handed out in load order would all move whenever a block with a fixed id was added.
+    #[serde(default)]
+    id: Option<u32>,
+    name: String,
+    #[serde(default)]
+    identifier: Option<String>,
+    #[serde(default)]
+    textures: FaceTextures,
+    #[serde(default)]
+    transparent: bool,
+    #[serde(default)]
+    properties: Vec<BlockProperty>,
+    #[serde(default)]
+    shape: Option<ShapeDefinition>,
+    #[serde(default)]
+    light_emission: u8,
+    /// Defaults to blocking all light for opaque cubes and none for anything else
+    #[serde(default)]
+    light_opacity: Option<u8>,
+    /// "opaque", "cutout" or "translucent", transparent blocks default to cutout
+    #[serde(default)]
+    render_layer: Option<String>
+}
+
+/// Either the name of a built in shape ("slab", "stairs", "fence", "cross") or a list of boxes
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ShapeDefinition {
+    Named(String),
+    Boxes { boxes: Vec<BoxDefinition> }
+}
+
+/// A box in sixteenths of a block, uvs are keyed by model face names (up, north, ...)
+#[derive(Deserialize)]
+struct BoxDefinition {
+    from: [f32; 3],
+    to: [f32; 3],
+    #[serde(default)]
+    uv: HashMap<String, [f32; 4]>
+}
+
+/// Texture names per face. The most specific entry wins, so "front" beats "side" which beats "all"
+#[derive(Deserialize, Default)]
+struct FaceTextures {
+    all: Option<String>,
+    side: Option<String>,
+    top: Option<String>,
+    bottom: Option<String>,
+    front: Option<String>,
+    back: Option<String>,
+    left: Option<String>,
+    right: Option<String>
+}
+
+impl BlockRegistry {
+    pub fn load(settings: &SettingsService) -> BlockRegistry {
+        let mut definitions = Vec::new();
+
+        read_definitions("default blocks", DEFAULT_BLOCKS, &mut definitions);
+
+        // Ids come from the definitions, file order only decides which duplicate wins
+        let path = format!("{}resources/blocks/", settings.path);
+        if let Ok(files) = fs::read_dir(&path) {
+            let mut files: Vec<String> = files
+                .filter_map(|file| file.ok())
+                .map(|file| file.path().to_string_lossy().to_string())
+                .filter(|file| file.ends_with(".json"))
+                .collect();
+            files.sort();
+
+            for file in files {
+                match fs::read_to_string(&file) {
+                    Ok(data) => read_definitions(&file, &data, &mut definitions),
+                    Err(e) => {
+                        log_error!(format!("Failed to read block definitions {}: {}", file, e));
+                    }
+                }
+            }
+        }
+
+        let blocks = validate_definitions(definitions);
+
+        log!("Loaded {} blocks", blocks.len());
+
+        BlockRegistry::new(blocks)
+    }
+}
+
+#[cfg(test)]
+impl BlockRegistry {
+    /// Only the blocks that come with the game, for tests that need real blocks
+    pub fn built_in() -> BlockRegistry {
+        let mut definitions = Vec::new();
+        read_definitions("default blocks", DEFAULT_BLOCKS, &mut definitions);
+
+        BlockRegistry::new(validate_definitions(definitions))
+    }
+}
+
+fn read_definitions(source: &str, data: &str, out: &mut Vec<BlockDefinition>) {
+    match serde_json::from_str::<Vec<BlockDefinition>>(data) {
+        Ok(mut definitions) => out.append(&mut definitions),
+        Err(e) => {
+            log_error!(format!("Invalid block definitions in {}: {}", source, e));
+        }
+    }
+}
+
+/// Drops invalid or duplicate definitions, including ones without an id
+fn validate_definitions(definitions: Vec<BlockDefinition>) -> Vec<Block> {
+    let mut used_ids = HashSet::new();
+    let mut used_identifiers = HashSet::new();
+    let mut valid = Vec::new();
+
+    for definition in definitions {
+        let name = definition.name.trim().to_string();
+        if name.is_empty() {
+            log_error!("Skipping block definition with no name");
+            continue;
+        }
+
+        let identifier = definition.identifier.clone()
+            .unwrap_or_else(|| format!("minecraft:{}", name.to_lowercase().replace(' ', "_")));
+
+        if !used_identifiers.insert(identifier.clone()) {
+            log_error!("Skipping duplicate block {}", identifier);
+            continue;
+        }
+
+        let id = match definition.id {
+            Some(id) => id,
+            None => {
+                log_error!("Skipping block {}, it has no id", identifier);
+                continue;
+            }
+        };
+
+        if id == 0 {
+            log_error!("Skipping block {}, id 0 is reserved for air", identifier);
+            continue;
+        }
+
+        if !used_ids.insert(id) {
+            log_error!(format!("Skipping block {}, id {} is already in use", identifier, id));
+            continue;
+        }
+
+        if let Some(property) = definition.properties.iter().find(|property| property.values.is_empty()) {
+            log_error!(format!("Skipping block {}, property {} has no values", identifier, property.name));
+            continue;
+        }
+
+        valid.push((definition, id, name, identifier));
+    }
+
+    valid.into_iter().map(|(definition, id, name, identifier)| {
+        let shape = block_shape(&identifier, definition.shape);
+
+        let render_layer = render_layer(&identifier, definition.render_layer.as_deref(), definition.transparent);
+
+        let light_opacity = definition.light_opacity.unwrap_or(match shape {
+            BlockShape::Cube if !definition.transparent => 15,
+            _ => 0
+        });
+
+        Block {
+            id,
+            raw_texture_names: face_textures(&identifier, &definition.textures),
+            name,
+            identifier,
+            texture_ids: [0; 6],
+            texture_atlas_lookups: [([0.0, 0.0], [1.0, 1.0]); 6],
+            transparent: definition.transparent,
+            shape,
+            light_emission: definition.light_emission.min(15),
+            light_opacity: light_opacity.min(15),
+            render_layer,
+            properties: definition.properties
+        }
+    }).collect()
+}
+
+/// Resolves the texture of each face, in the same order as BlockDirection
+fn face_textures(identifier: &str, textures: &FaceTextures) -> [String; 6] {
+    let side = textures.side.as_ref().or(textures.all.as_ref());
+
+    let faces = [
+        textures.top.as_ref().or(textures.all.as_ref()),
+        textures.front.as_ref().or(side),
+        textures.back.as_ref().or(side),
+        textures.left.as_ref().or(side),
+        textures.right.as_ref().or(side),
+        textures.bottom.as_ref().or(textures.all.as_ref())
+    ];
+
+    let mut out: [String; 6] = Default::default();
+    for (i, face) in faces.iter().enumerate() {
+        out[i] = match face {
+            Some(texture) => texture.to_string(),
+            None => {
+                log_error!(format!("Block {} has no texture for face {}", identifier, i));
+                MISSING_TEXTURE.to_string()
+            }
+        };
+    }
+
+    out
+}
+
+fn block_shape(identifier: &str, shape: Option<ShapeDefinition>) -> BlockShape {
+    match shape {
+        None => BlockShape::Cube,
+        Some(ShapeDefinition::Named(name)) => match name.as_str() {
+            "cube" => BlockShape::Cube,
+            "slab" => BlockShape::Slab,
+            "stairs" => BlockShape::Stairs,
+            "fence" => BlockShape::Fence,
+            "cross" => BlockShape::Cross,
+            _ => {
+                log_error!(format!("Block {} has unknown shape {}, using a cube", identifier, name));
+                BlockShape::Cube
+            }
+        },
+        Some(ShapeDefinition::Boxes { boxes }) => {
+            BlockShape::Boxes(boxes.into_iter().map(|definition| {
+                let mut block_box = BlockBox::new(definition.from, definition.to);
+
+                for (i, face_name) in MODEL_FACES.iter().enumerate() {
+                    block_box.uvs[i] = definition.uv.get(*face_name).cloned();
+                }
+
+                block_box
+            }).collect())
+        }
+    }
+}
+
+fn render_layer(identifier: &str, layer: Option<&str>, transparent: bool) -> RenderLayer {
+    match layer {
+        Some("opaque") => RenderLayer::Opaque,
+        Some("cutout") => RenderLayer::Cutout,
+        Some("translucent") => RenderLayer::Translucent,
+        Some(layer) => {
+            log_error!(format!("Block {} has unknown render layer {}", identifier, layer));
+            RenderLayer::Opaque
+        }
+        None if transparent => RenderLayer::Cutout,
+        None => RenderLayer::Opaque
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(data: &str) -> Vec<Block> {
+        let mut definitions = Vec::new();
+        read_definitions("test blocks", data, &mut definitions);
+        validate_definitions(definitions)
+    }
+
+    #[test]
+    fn blocks_without_an_id_are_skipped() {
+        let blocks = load(r#"[
+            { "id": 1, "name": "Stone", "textures": { "all": "stone" } },
+            { "name": "Dirt", "textures": { "all": "dirt" } }
+        ]"#);
+
+        assert_eq!(blocks.len(), 1);
+        assert_eq!(blocks[0].identifier, "minecraft:stone");
+    }
+
+    #[test]
+    fn ids_stay_the_same_when_blocks_are_added() {
+        let before = load(r#"[
+            { "id": 1, "name": "Stone", "textures": { "all": "stone" } },
+            { "id": 2, "name": "Dirt", "textures": { "all": "dirt" } }
+        ]"#);
+        let after = load(r#"[
+            { "id": 1, "name": "Stone", "textures": { "all": "stone" } },
+            { "id": 50, "name": "Glass", "textures": { "all": "glass" } },
+            { "id": 2, "name": "Dirt", "textures": { "all": "dirt" } }
+        ]"#);
+
+        for block in &before {
+            let same = after.iter().find(|other| other.identifier == block.identifier).unwrap();
+            assert_eq!(same.id, block.id);
+        }
+    }
+
+    #[test]
+    fn duplicate_and_reserved_ids_are_skipped() {
+        let blocks = load(r#"[
+            { "id": 0, "name": "Void", "textures": { "all": "void" } },
+            { "id": 3, "name": "Sand", "textures": { "all": "sand" } },
+            { "id": 3, "name": "Gravel", "textures": { "all": "gravel" } }
+        ]"#);
+
+        assert_eq!(blocks.iter().map(|block| block.identifier.as_str()).collect::<Vec<_>>(), vec!["minecraft:sand"]);
+    }
+}
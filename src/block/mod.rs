@@ -1,17 +1,46 @@
 use crate::services::asset_service::atlas::TextureAtlasIndex;
+use crate::block::state::BlockProperty;
+use crate::block::shape::BlockShape;
+use cgmath::Vector3;
 
-pub mod blocks;
+pub mod registry;
+pub mod loader;
+pub mod state;
+pub mod shape;
 
 #[derive(Clone, Debug)]
 pub struct Block {
     pub id: u32,
-    pub name: &'static str,
-    pub raw_texture_names: [&'static str; 6],
+    pub name: String,
+    /// Namespaced name, e.g. minecraft:grass_block
+    pub identifier: String,
+    pub raw_texture_names: [String; 6],
     pub texture_ids: [u32; 6],
     pub texture_atlas_lookups: [TextureAtlasIndex; 6],
     pub transparent: bool,
+    /// Properties that make up the block's states, e.g. the axis of a log
+    pub properties: Vec<BlockProperty>,
+    pub shape: BlockShape,
+    /// Block light given off, from 0 to 15
+    pub light_emission: u8,
+    /// Light lost passing through the block, 15 stops light completely
+    pub light_opacity: u8,
+    pub render_layer: RenderLayer,
 }
 
+/// Which pass a block's faces are drawn in
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum RenderLayer {
+    /// Drawn first, every pixel is solid
+    Opaque = 0,
+    /// Fully see through pixels are discarded, like the gaps between leaves
+    Cutout = 1,
+    /// Blended over everything else from back to front, like water and glass
+    Translucent = 2
+}
+
+pub const RENDER_LAYERS: [RenderLayer; 3] = [RenderLayer::Opaque, RenderLayer::Cutout, RenderLayer::Translucent];
+
 #[derive(Copy, Clone)]
 pub enum BlockDirection {
     Up = 0,
@@ -20,4 +49,34 @@ pub enum BlockDirection {
     Left = 3,
     Right = 4,
     Bottom = 5
-}
\ No newline at end of file
+}
+
+impl BlockDirection {
+    pub fn opposite(self) -> BlockDirection {
+        match self {
+            BlockDirection::Up => BlockDirection::Bottom,
+            BlockDirection::Bottom => BlockDirection::Up,
+            BlockDirection::Front => BlockDirection::Back,
+            BlockDirection::Back => BlockDirection::Front,
+            BlockDirection::Left => BlockDirection::Right,
+            BlockDirection::Right => BlockDirection::Left
+        }
+    }
+
+    /// The face an offset points out of, Front is -z and Left is -x
+    pub fn from_offset(offset: &Vector3<i32>) -> BlockDirection {
+        if offset.y > 0 {
+            BlockDirection::Up
+        } else if offset.y < 0 {
+            BlockDirection::Bottom
+        } else if offset.z < 0 {
+            BlockDirection::Front
+        } else if offset.z > 0 {
+            BlockDirection::Back
+        } else if offset.x < 0 {
+            BlockDirection::Left
+        } else {
+            BlockDirection::Right
+        }
+    }
+}
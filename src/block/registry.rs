@@ -0,0 +1,128 @@
+use crate::block::{Block, BlockDirection, RenderLayer};
+use crate::block::state::BlockState;
+use crate::block::shape::{BlockShape, coverage_within};
+use std::collections::HashMap;
+
+/// Holds every block definition once. Chunks only store block ids, anything that needs block properties looks them up here.
+pub struct BlockRegistry {
+    blocks: Vec<Block>,
+    ids: HashMap<u32, usize>,
+    names: HashMap<String, usize>
+}
+
+impl BlockRegistry {
+    pub fn new(blocks: Vec<Block>) -> BlockRegistry {
+        let mut registry = BlockRegistry {
+            blocks: Vec::new(),
+            ids: HashMap::new(),
+            names: HashMap::new()
+        };
+
+        for block in blocks {
+            registry.register(block);
+        }
+
+        registry
+    }
+
+    pub fn register(&mut self, block: Block) {
+        if block.id == 0 {
+            log_error!("Block {} can't use id 0, it is reserved for air", block.name);
+            return;
+        }
+
+        if self.ids.contains_key(&block.id) {
+            log_error!("Block id {} is registered more than once", block.id);
+            return;
+        }
+
+        let index = self.blocks.len();
+        self.ids.insert(block.id, index);
+        self.names.insert(normalize_name(&block.name), index);
+        self.names.insert(normalize_name(&block.identifier), index);
+        self.blocks.push(block);
+    }
+
+    /// Gets a block by its numeric id, air (0) has no block
+    pub fn get(&self, id: u32) -> Option<&Block> {
+        self.ids.get(&id).map(|index| &self.blocks[*index])
+    }
+
+    /// Gets a block by name, accepts both our names ("Dirt") and namespaced names ("minecraft:dirt")
+    pub fn get_by_name(&self, name: &str) -> Option<&Block> {
+        self.names.get(&normalize_name(name)).map(|index| &self.blocks[*index])
+    }
+
+    /// Gets the block a state belongs to
+    pub fn get_by_state(&self, state: BlockState) -> Option<&Block> {
+        self.get(state.block_id())
+    }
+
+    pub fn id_of(&self, name: &str) -> Option<u32> {
+        self.get_by_name(name).map(|block| block.id)
+    }
+
+    pub fn default_state(&self, name: &str) -> Option<BlockState> {
+        self.get_by_name(name).map(|block| block.default_state())
+    }
+
+    /// Returns true if the state is air or a block that can be seen through
+    pub fn is_transparent(&self, state: BlockState) -> bool {
+        self.get_by_state(state).is_none_or(|block| block.transparent)
+    }
+
+    /// Returns true if the state's face in this direction completely hides whatever is behind it, air never does
+    pub fn is_face_full(&self, state: BlockState, direction: BlockDirection) -> bool {
+        self.get_by_state(state).is_some_and(|block| block.is_face_full(state, direction))
+    }
+
+    /// Returns true if a face of state is hidden by the neighbouring state, face being the neighbour's face pointing back at it.
+    /// A partial face is hidden when the neighbour covers all of it, like a slab resting against a slab.
+    /// Touching translucent blocks of the same kind also hide each other, so the inside of a lake isn't drawn.
+    pub fn hides_face(&self, state: BlockState, neighbour: BlockState, face: BlockDirection) -> bool {
+        if self.is_face_full(neighbour, face) {
+            return true;
+        }
+
+        if let (Some(block), Some(neighbour_block)) = (self.get_by_state(state), self.get_by_state(neighbour)) {
+            let coverage = block.face_coverage(state, face.opposite());
+
+            if !neighbour_block.transparent && coverage.iter().any(|row| *row != 0) &&
+                coverage_within(&coverage, &neighbour_block.face_coverage(neighbour, face)) {
+                return true;
+            }
+        }
+
+        state.block_id() == neighbour.block_id() &&
+            self.get_by_state(state).is_some_and(|block| block.render_layer == RenderLayer::Translucent)
+    }
+
+    /// Returns true if the state is an opaque full cube, the only blocks that darken corners next to them
+    pub fn is_full_cube(&self, state: BlockState) -> bool {
+        self.get_by_state(state).is_some_and(|block| !block.transparent && matches!(block.shape, BlockShape::Cube))
+    }
+
+    /// How much light the state gives off, from 0 to 15
+    pub fn light_emission(&self, state: BlockState) -> u8 {
+        self.get_by_state(state).map_or(0, |block| block.light_emission)
+    }
+
+    /// How much light is lost passing through the state, 15 stops it completely
+    pub fn light_opacity(&self, state: BlockState) -> u8 {
+        self.get_by_state(state).map_or(0, |block| block.light_opacity)
+    }
+
+    pub fn blocks(&self) -> &Vec<Block> {
+        &self.blocks
+    }
+
+    pub fn blocks_mut(&mut self) -> &mut Vec<Block> {
+        &mut self.blocks
+    }
+}
+
+fn normalize_name(name: &str) -> String {
+    let name = name.trim().to_lowercase();
+
+    name.trim_start_matches("minecraft:").replace(' ', "_")
+}
@@ -0,0 +1,231 @@
+use crate::block::{Block, BlockDirection};
+use crate::block::state::BlockState;
+
+/// How a block is built out of quads
+#[derive(Clone, Debug)]
+pub enum BlockShape {
+    /// A full unit cube, almost every block
+    Cube,
+    /// Half a block, picked by the "type" property (bottom, top or double)
+    Slab,
+    /// A slab with a step on top, uses the "facing" and "half" properties
+    Stairs,
+    /// The centre post of a fence
+    Fence,
+    /// Two quads crossing diagonally, used by plants
+    Cross,
+    /// Any set of boxes
+    Boxes(Vec<BlockBox>)
+}
+
+/// An axis aligned box in sixteenths of a block, the same units resource pack models use
+#[derive(Clone, Debug)]
+pub struct BlockBox {
+    pub from: [f32; 3],
+    pub to: [f32; 3],
+    /// Texture coordinates per face as [u0, v0, u1, v1] in sixteenths, in BlockDirection order.
+    /// Faces without one use the part of the texture matching their position.
+    pub uvs: [Option<[f32; 4]>; 6]
+}
+
+impl BlockBox {
+    pub fn new(from: [f32; 3], to: [f32; 3]) -> BlockBox {
+        BlockBox {
+            from,
+            to,
+            uvs: [None; 6]
+        }
+    }
+
+    /// Returns true if the box's face lies on the edge of the block and covers all of it
+    pub fn covers_face(&self, direction: BlockDirection) -> bool {
+        let covers = |a: usize, b: usize| {
+            self.from[a] <= 0.0 && self.to[a] >= 16.0 && self.from[b] <= 0.0 && self.to[b] >= 16.0
+        };
+
+        match direction {
+            BlockDirection::Up => self.to[1] >= 16.0 && covers(0, 2),
+            BlockDirection::Bottom => self.from[1] <= 0.0 && covers(0, 2),
+            BlockDirection::Front => self.from[2] <= 0.0 && covers(0, 1),
+            BlockDirection::Back => self.to[2] >= 16.0 && covers(0, 1),
+            BlockDirection::Left => self.from[0] <= 0.0 && covers(1, 2),
+            BlockDirection::Right => self.to[0] >= 16.0 && covers(1, 2)
+        }
+    }
+
+    /// Returns true if the box's face lies on the edge of the block, so a neighbour might hide it
+    pub fn touches_edge(&self, direction: BlockDirection) -> bool {
+        match direction {
+            BlockDirection::Up => self.to[1] >= 16.0,
+            BlockDirection::Bottom => self.from[1] <= 0.0,
+            BlockDirection::Front => self.from[2] <= 0.0,
+            BlockDirection::Back => self.to[2] >= 16.0,
+            BlockDirection::Left => self.from[0] <= 0.0,
+            BlockDirection::Right => self.to[0] >= 16.0
+        }
+    }
+
+    /// Marks the sixteenths of the block's edge this box's face covers, opposite faces share the same grid
+    fn cover(&self, direction: BlockDirection, coverage: &mut FaceCoverage) {
+        if !self.touches_edge(direction) {
+            return;
+        }
+
+        let (a, b) = face_grid_axes(direction);
+        let covered = |axis: usize, i: usize| self.from[axis] <= i as f32 && self.to[axis] >= (i + 1) as f32;
+
+        for (row, cells) in coverage.iter_mut().enumerate() {
+            if covered(b, row) {
+                for column in 0..16 {
+                    if covered(a, column) {
+                        *cells |= 1 << column;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Which sixteenths of one side of a block are covered, a row of bits for each sixteenth along the side
+pub type FaceCoverage = [u16; 16];
+
+/// Returns true if every sixteenth covered in face is also covered in by
+pub fn coverage_within(face: &FaceCoverage, by: &FaceCoverage) -> bool {
+    face.iter().zip(by.iter()).all(|(face, by)| face & !by == 0)
+}
+
+/// The axes running across a side of the block, the same for both sides facing each other
+fn face_grid_axes(direction: BlockDirection) -> (usize, usize) {
+    match direction {
+        BlockDirection::Up | BlockDirection::Bottom => (0, 2),
+        BlockDirection::Front | BlockDirection::Back => (0, 1),
+        BlockDirection::Left | BlockDirection::Right => (1, 2)
+    }
+}
+
+impl Block {
+    /// The boxes making up a state, cross shaped blocks have none
+    pub fn boxes(&self, state: BlockState) -> Vec<BlockBox> {
+        match &self.shape {
+            BlockShape::Cube => vec![BlockBox::new([0.0, 0.0, 0.0], [16.0, 16.0, 16.0])],
+            BlockShape::Slab => match self.property_value(state, "type") {
+                Some("top") => vec![BlockBox::new([0.0, 8.0, 0.0], [16.0, 16.0, 16.0])],
+                Some("double") => vec![BlockBox::new([0.0, 0.0, 0.0], [16.0, 16.0, 16.0])],
+                _ => vec![BlockBox::new([0.0, 0.0, 0.0], [16.0, 8.0, 16.0])]
+            },
+            BlockShape::Stairs => {
+                let top = self.property_value(state, "half") == Some("top");
+                let (slab_y, step_y) = if top { (8.0, 0.0) } else { (0.0, 8.0) };
+
+                // The step is on the side the stairs face
+                let (step_from, step_to) = match self.property_value(state, "facing") {
+                    Some("east") => ([8.0, step_y, 0.0], [16.0, step_y + 8.0, 16.0]),
+                    Some("south") => ([0.0, step_y, 8.0], [16.0, step_y + 8.0, 16.0]),
+                    Some("west") => ([0.0, step_y, 0.0], [8.0, step_y + 8.0, 16.0]),
+                    _ => ([0.0, step_y, 0.0], [16.0, step_y + 8.0, 8.0])
+                };
+
+                vec![
+                    BlockBox::new([0.0, slab_y, 0.0], [16.0, slab_y + 8.0, 16.0]),
+                    BlockBox::new(step_from, step_to)
+                ]
+            }
+            BlockShape::Fence => vec![BlockBox::new([6.0, 0.0, 6.0], [10.0, 16.0, 10.0])],
+            BlockShape::Cross => Vec::new(),
+            BlockShape::Boxes(boxes) => boxes.clone()
+        }
+    }
+
+    /// Returns true if this face of the block completely hides whatever is behind it
+    pub fn is_face_full(&self, state: BlockState, direction: BlockDirection) -> bool {
+        if self.transparent {
+            return false;
+        }
+
+        match &self.shape {
+            BlockShape::Cube => true,
+            BlockShape::Cross => false,
+            _ => self.boxes(state).iter().any(|block_box| block_box.covers_face(direction))
+        }
+    }
+
+    /// The part of this side of the block its boxes cover, whether or not it can be seen through
+    pub fn face_coverage(&self, state: BlockState, direction: BlockDirection) -> FaceCoverage {
+        let mut coverage = [0; 16];
+
+        for block_box in self.boxes(state).iter() {
+            block_box.cover(direction, &mut coverage);
+        }
+
+        coverage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coverage(boxes: &[BlockBox], direction: BlockDirection) -> FaceCoverage {
+        let mut coverage = [0; 16];
+
+        for block_box in boxes {
+            block_box.cover(direction, &mut coverage);
+        }
+
+        coverage
+    }
+
+    fn bottom_slab() -> Vec<BlockBox> {
+        vec![BlockBox::new([0.0, 0.0, 0.0], [16.0, 8.0, 16.0])]
+    }
+
+    fn top_slab() -> Vec<BlockBox> {
+        vec![BlockBox::new([0.0, 8.0, 0.0], [16.0, 16.0, 16.0])]
+    }
+
+    fn cube() -> Vec<BlockBox> {
+        vec![BlockBox::new([0.0, 0.0, 0.0], [16.0, 16.0, 16.0])]
+    }
+
+    #[test]
+    fn matching_slabs_hide_each_other() {
+        let face = coverage(&bottom_slab(), BlockDirection::Right);
+        let neighbour = coverage(&bottom_slab(), BlockDirection::Left);
+
+        assert!(coverage_within(&face, &neighbour));
+    }
+
+    #[test]
+    fn slabs_at_different_heights_leave_faces_visible() {
+        let face = coverage(&bottom_slab(), BlockDirection::Right);
+        let neighbour = coverage(&top_slab(), BlockDirection::Left);
+
+        assert!(!coverage_within(&face, &neighbour));
+    }
+
+    #[test]
+    fn slab_does_not_hide_a_full_face() {
+        let face = coverage(&cube(), BlockDirection::Back);
+        let neighbour = coverage(&bottom_slab(), BlockDirection::Front);
+
+        assert!(!coverage_within(&face, &neighbour));
+        assert!(coverage_within(&neighbour, &face));
+    }
+
+    #[test]
+    fn stairs_hide_a_slab_against_their_back() {
+        let stairs = vec![
+            BlockBox::new([0.0, 0.0, 0.0], [16.0, 8.0, 16.0]),
+            BlockBox::new([0.0, 8.0, 0.0], [16.0, 16.0, 8.0])
+        ];
+        let face = coverage(&top_slab(), BlockDirection::Back);
+        let neighbour = coverage(&stairs, BlockDirection::Front);
+
+        assert!(coverage_within(&face, &neighbour));
+    }
+
+    #[test]
+    fn faces_away_from_the_edge_cover_nothing() {
+        assert_eq!(coverage(&bottom_slab(), BlockDirection::Up), [0; 16]);
+    }
+}
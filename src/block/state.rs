@@ -0,0 +1,175 @@
+use crate::block::Block;
+use crate::block::BlockDirection::{self, Up, Front, Back, Left, Right, Bottom};
+use crate::services::asset_service::atlas::TextureAtlasIndex;
+use serde::Deserialize;
+
+/// A block id packed together with the index of one of its states. Chunks and palettes store these instead of plain ids,
+/// so a rotated log costs no more space than a plain one.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BlockState(pub u32);
+
+const BLOCK_ID_BITS: u32 = 16;
+const BLOCK_ID_MASK: u32 = (1 << BLOCK_ID_BITS) - 1;
+
+impl BlockState {
+    pub const AIR: BlockState = BlockState(0);
+
+    pub fn new(block_id: u32, state: u32) -> BlockState {
+        BlockState((state << BLOCK_ID_BITS) | (block_id & BLOCK_ID_MASK))
+    }
+
+    pub fn block_id(self) -> u32 {
+        self.0 & BLOCK_ID_MASK
+    }
+
+    /// Index of this state within the block's legal states
+    pub fn state(self) -> u32 {
+        self.0 >> BLOCK_ID_BITS
+    }
+
+    pub fn is_air(self) -> bool {
+        self.block_id() == 0
+    }
+}
+
+/// A named property and the values it's allowed to take, the first value is the default
+#[derive(Clone, Debug, Deserialize)]
+pub struct BlockProperty {
+    pub name: String,
+    pub values: Vec<String>
+}
+
+/// What gets drawn on one face of a block
+#[derive(Copy, Clone, Debug)]
+pub struct BlockFace {
+    pub texture: TextureAtlasIndex,
+    /// Clockwise quarter turns of the texture
+    pub rotation: u8
+}
+
+impl Block {
+    pub fn default_state(&self) -> BlockState {
+        BlockState::new(self.id, 0)
+    }
+
+    /// Total number of legal states, every combination of property values
+    pub fn state_count(&self) -> u32 {
+        self.properties.iter().fold(1, |count, property| count * property.values.len() as u32)
+    }
+
+    pub fn property_value(&self, state: BlockState, name: &str) -> Option<&str> {
+        let mut index = state.state();
+
+        for property in &self.properties {
+            let len = property.values.len() as u32;
+
+            if property.name == name {
+                return property.values.get((index % len) as usize).map(|value| value.as_str());
+            }
+
+            index /= len;
+        }
+
+        None
+    }
+
+    /// Returns the state with one property changed, or the state unchanged if the property or value isn't legal
+    pub fn with_property(&self, state: BlockState, name: &str, value: &str) -> BlockState {
+        let mut stride = 1;
+
+        for property in &self.properties {
+            let len = property.values.len() as u32;
+
+            if property.name == name {
+                let new_value = match property.values.iter().position(|legal| legal == value) {
+                    Some(position) => position as u32,
+                    None => return state
+                };
+
+                let current = (state.state() / stride) % len;
+                let index = state.state() - current * stride + new_value * stride;
+
+                return BlockState::new(self.id, index);
+            }
+
+            stride *= len;
+        }
+
+        state
+    }
+
+    /// Picks the texture and rotation of each face for a state, in the same order as BlockDirection
+    pub fn faces(&self, state: BlockState) -> [BlockFace; 6] {
+        let mut faces = [BlockFace { texture: ([0.0, 0.0], [1.0, 1.0]), rotation: 0 }; 6];
+
+        if self.properties.is_empty() {
+            for (i, face) in faces.iter_mut().enumerate() {
+                face.texture = self.texture_atlas_lookups[i];
+            }
+            return faces;
+        }
+
+        let mapping = if let Some(axis) = self.property_value(state, "axis") {
+            axis_mapping(axis)
+        } else if let Some(facing) = self.property_value(state, "facing") {
+            facing_mapping(facing)
+        } else {
+            IDENTITY
+        };
+
+        for (i, face) in faces.iter_mut().enumerate() {
+            let (source, rotation) = mapping[i];
+            face.texture = self.texture_atlas_lookups[source as usize];
+            face.rotation = rotation;
+        }
+
+        faces
+    }
+}
+
+type FaceMapping = [(BlockDirection, u8); 6];
+
+// For each face, which of the block's own faces is drawn there and how far the texture turns.
+// Blocks are defined facing north (Front) with their axis pointing up.
+const IDENTITY: FaceMapping = [(Up, 0), (Front, 0), (Back, 0), (Left, 0), (Right, 0), (Bottom, 0)];
+
+// Logs lying along x or z
+const AXIS_X: FaceMapping = [(Left, 1), (Front, 1), (Back, 1), (Bottom, 0), (Up, 0), (Right, 1)];
+const AXIS_Z: FaceMapping = [(Back, 0), (Bottom, 0), (Up, 0), (Left, 1), (Right, 1), (Front, 0)];
+
+// Front pointing up or down
+const FACING_UP: FaceMapping = [(Front, 0), (Bottom, 0), (Up, 0), (Left, 1), (Right, 1), (Back, 0)];
+const FACING_DOWN: FaceMapping = [(Back, 0), (Up, 0), (Bottom, 0), (Left, 3), (Right, 3), (Front, 0)];
+
+fn axis_mapping(axis: &str) -> FaceMapping {
+    match axis {
+        "x" => AXIS_X,
+        "z" => AXIS_Z,
+        _ => IDENTITY
+    }
+}
+
+fn facing_mapping(facing: &str) -> FaceMapping {
+    // Horizontal faces clockwise from above
+    let ring = [Front, Right, Back, Left];
+
+    let turns = match facing {
+        "north" => 0,
+        "east" => 1,
+        "south" => 2,
+        "west" => 3,
+        "up" => return FACING_UP,
+        "down" => return FACING_DOWN,
+        _ => return IDENTITY
+    };
+
+    let mut mapping = IDENTITY;
+    mapping[Up as usize].1 = turns as u8;
+    mapping[Bottom as usize].1 = turns as u8;
+
+    for (position, face) in ring.iter().enumerate() {
+        mapping[*face as usize] = (ring[(position + 4 - turns) % 4], 0);
+    }
+
+    mapping
+}
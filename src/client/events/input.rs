@@ -1,4 +1,4 @@
-use winit::event::{MouseButton, WindowEvent};
+use winit::event::{ElementState, MouseButton, WindowEvent};
 use winit::window::Window;
 use crate::client::events::{GameChanges, GameChangesContext};
 
@@ -12,6 +12,7 @@ impl GameChanges {
             pause: false,
             jump: false,
             sneak: false,
+            save_template: false,
         }
     }
 
@@ -47,6 +48,10 @@ impl GameChanges {
         self.pause = true;
     }
 
+    fn save_template_pressed(&mut self) {
+        self.save_template = true;
+    }
+
     pub fn handle_event(&mut self, event: &WindowEvent, changes: &mut GameChangesContext, window: &Window) {
         match *event.clone() {
             WindowEvent::MouseInput { device_id: _, state: _, button, .. } => {
@@ -95,6 +100,11 @@ impl GameChanges {
                     if key == changes.mappings.sneak {
                         self.set_sneak();
                     }
+
+                    // Only on the press, otherwise releasing the key would mark the second corner straight away
+                    if key == changes.mappings.save_template && input.state == ElementState::Pressed {
+                        self.save_template_pressed();
+                    }
                 }
             }
 
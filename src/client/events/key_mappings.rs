@@ -8,6 +8,8 @@ pub struct KeyMapping {
     pub pause: VirtualKeyCode,
     pub jump: VirtualKeyCode,
     pub sneak: VirtualKeyCode,
+    /// Marks one corner of a structure template, then saves everything between it and the second corner
+    pub save_template: VirtualKeyCode,
 }
 
 impl KeyMapping {
@@ -20,6 +22,7 @@ impl KeyMapping {
             pause: VirtualKeyCode::Escape,
             jump: VirtualKeyCode::Space,
             sneak: VirtualKeyCode::LShift,
+            save_template: VirtualKeyCode::F6,
         }
     }
 }
\ No newline at end of file
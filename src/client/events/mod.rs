@@ -14,6 +14,7 @@ pub struct GameChanges {
     pub pause: bool,
     pub jump: bool,
     pub sneak: bool,
+    pub save_template: bool,
 }
 
 pub struct GameChangesContext {
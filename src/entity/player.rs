@@ -8,9 +8,9 @@ pub struct Player {
 }
 
 impl Player {
-    pub fn new() -> Player {
+    pub fn new(spawn: [f32; 3]) -> Player {
         Player {
-            pos: Vector3 {x: 10.0, y: 50.0, z: 10.0 },
+            pos: Vector3::from(spawn),
             rot: [0.0, 0.0],
             velocity: Vector3 {x: 0.0, y: 0.0, z: 0.0 },
         }
@@ -2,15 +2,30 @@ use crate::render::RenderState;
 use crate::client::events::GameChanges;
 use crate::entity::player::Player;
 use std::f32::consts::PI;
+use crate::services::chunk_service::streaming::COLUMNS_PER_FRAME;
+use crate::services::chunk_service::workers::UPLOADS_PER_FRAME;
+use crate::world::metadata::TICKS_PER_SECOND;
+use cgmath::Vector3;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub struct GameState {
-    player: Player
+    player: Player,
+    /// Time since the last whole world tick, in ticks
+    partial_tick: f64,
+    /// The first corner of a structure template waiting for its second
+    template_corner: Option<Vector3<i32>>
 }
 
 impl GameState {
-    pub fn new() -> GameState {
+    /// Starts the player at the spawn point of the open world
+    pub fn new(render: &mut RenderState) -> GameState {
+        let player = Player::new(render.services.as_ref().unwrap().world.spawn);
+        render.camera.move_first_person(&player.pos);
+
         GameState {
-            player: Player::new()
+            player,
+            partial_tick: 0.0,
+            template_corner: None
         }
     }
 
@@ -51,6 +66,35 @@ impl GameState {
             render.camera.move_first_person(&self.player.pos);
         }
 
+        let services = render.services.as_mut().unwrap();
+
+        if services.world.game_rules.daylight_cycle {
+            self.partial_tick += delta_time * TICKS_PER_SECOND;
+            services.world.time += self.partial_tick as u64;
+            self.partial_tick = self.partial_tick.fract();
+        }
+
+        // The first press marks a corner at the player, the second saves the box between the two corners
+        if events.save_template {
+            let pos = self.player.pos.map(|value| value.floor() as i32);
+
+            match self.template_corner.take() {
+                Some(corner) => {
+                    let time = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+                    services.save_template(&format!("template_{}", time), corner, pos);
+                }
+                None => {
+                    log!("Marked the first corner of a structure template at {:?}", pos);
+                    self.template_corner = Some(pos);
+                }
+            }
+        }
+
+        // Load and unload chunks around wherever the player has moved to, and upload whatever the workers have meshed
+        services.chunk.update_streaming(self.player.pos, &services.settings, &services.blocks, COLUMNS_PER_FRAME);
+        services.chunk.remesh_dirty_chunks(&services.blocks, &render.device);
+        services.chunk.upload_meshes(&render.device, UPLOADS_PER_FRAME);
+
         render.uniforms.update_view_proj(&render.camera);
 
         let uniform_buffer = render.device
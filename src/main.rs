@@ -1,7 +1,3 @@
-#![feature(get_mut_unchecked)]
-#![feature(fixed_size_array)]
-#![feature(clamp)]
-
 use winit::window::WindowBuilder;
 use winit::event_loop::EventLoop;
 use winit::event::{WindowEvent, Event};
@@ -24,6 +20,7 @@ pub mod client;
 pub mod game;
 pub mod entity;
 pub mod helpers;
+pub mod nbt;
 
 fn main() {
 
@@ -37,7 +34,7 @@ fn main() {
         .unwrap();
 
     let mut render_state = RenderState::new(&window);
-    let mut game_state = GameState::new();
+    let mut game_state = GameState::new(&mut render_state);
 
     let mut game_changes_context = GameChangesContext::new();
     game_changes_context.update_mouse_home(window.inner_size());
@@ -59,7 +56,13 @@ fn main() {
                 window_id,
             } if window_id == window.id() => {
                 match event {
-                    WindowEvent::CloseRequested => {*control_flow = ControlFlow::Exit; return;},
+                    WindowEvent::CloseRequested => {
+                        if let Some(services) = render_state.services.as_mut() {
+                            services.save_world();
+                        }
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    },
                     WindowEvent::Resized(physical_size) => {
                         render_state.resize(*physical_size);
                         game_changes_context.update_mouse_home(window.inner_size());
@@ -0,0 +1,101 @@
+//
+// NBT files are usually gzipped (level.dat, structures, player data), region files use zlib
+//
+
+use crate::nbt::Tag;
+use crate::nbt::read::read_nbt;
+use crate::nbt::write::write_nbt;
+use flate2::Compression;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use std::io::{self, Read, Write};
+
+const GZIP_MAGIC: u8 = 0x1f;
+const ZLIB_MAGIC: u8 = 0x78;
+
+pub fn read_gzip<R: Read>(reader: R) -> io::Result<(String, Tag)> {
+    read_nbt(&mut GzDecoder::new(reader))
+}
+
+pub fn read_zlib<R: Read>(reader: R) -> io::Result<(String, Tag)> {
+    read_nbt(&mut ZlibDecoder::new(reader))
+}
+
+/// Reads NBT that might be gzipped, zlib compressed or not compressed at all, going by its first byte
+pub fn read_compressed<R: Read>(mut reader: R) -> io::Result<(String, Tag)> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    match data.first() {
+        Some(&GZIP_MAGIC) => read_gzip(data.as_slice()),
+        Some(&ZLIB_MAGIC) => read_zlib(data.as_slice()),
+        _ => read_nbt(&mut data.as_slice())
+    }
+}
+
+pub fn write_gzip<W: Write>(writer: W, name: &str, tag: &Tag) -> io::Result<()> {
+    let mut encoder = GzEncoder::new(writer, Compression::default());
+    write_nbt(&mut encoder, name, tag)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+pub fn write_zlib<W: Write>(writer: W, name: &str, tag: &Tag) -> io::Result<()> {
+    let mut encoder = ZlibEncoder::new(writer, Compression::default());
+    write_nbt(&mut encoder, name, tag)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Tag {
+        let mut compound = crate::nbt::Compound::new();
+        compound.insert(String::from("DataVersion"), Tag::Int(2860));
+        compound.insert(String::from("palette"), Tag::List(vec![Tag::String(String::from("minecraft:stone")); 64]));
+
+        Tag::Compound(compound)
+    }
+
+    #[test]
+    fn gzip_round_trips() {
+        let mut data = Vec::new();
+        write_gzip(&mut data, "level", &sample()).unwrap();
+
+        assert_eq!(data[0], GZIP_MAGIC);
+        assert_eq!(read_gzip(data.as_slice()).unwrap(), (String::from("level"), sample()));
+    }
+
+    #[test]
+    fn zlib_round_trips() {
+        let mut data = Vec::new();
+        write_zlib(&mut data, "chunk", &sample()).unwrap();
+
+        assert_eq!(data[0], ZLIB_MAGIC);
+        assert_eq!(read_zlib(data.as_slice()).unwrap(), (String::from("chunk"), sample()));
+    }
+
+    #[test]
+    fn read_compressed_tells_the_framing_apart() {
+        let mut gzip = Vec::new();
+        let mut zlib = Vec::new();
+        let mut plain = Vec::new();
+        write_gzip(&mut gzip, "", &sample()).unwrap();
+        write_zlib(&mut zlib, "", &sample()).unwrap();
+        write_nbt(&mut plain, "", &sample()).unwrap();
+
+        for data in [gzip, zlib, plain].iter() {
+            assert_eq!(read_compressed(data.as_slice()).unwrap().1, sample());
+        }
+    }
+
+    #[test]
+    fn gzip_read_as_zlib_is_an_error() {
+        let mut data = Vec::new();
+        write_gzip(&mut data, "", &sample()).unwrap();
+
+        assert!(read_zlib(data.as_slice()).is_err());
+    }
+}
@@ -0,0 +1,362 @@
+//
+// Serde deserializer that reads any Deserialize type out of a Tag
+//
+
+use crate::nbt::{Tag, Compound, Error};
+use serde::de::{self, Deserialize, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::de::value::{MapDeserializer, SeqDeserializer};
+use serde::forward_to_deserialize_any;
+
+/// Reads a value back out of NBT. Arrays can be read into any sequence, and missing fields are None.
+pub fn from_tag<T: DeserializeOwned>(tag: Tag) -> Result<T, Error> {
+    T::deserialize(tag)
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Tag {
+    type Deserializer = Tag;
+
+    fn into_deserializer(self) -> Tag {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Tag {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Tag::Byte(value) => visitor.visit_i8(value),
+            Tag::Short(value) => visitor.visit_i16(value),
+            Tag::Int(value) => visitor.visit_i32(value),
+            Tag::Long(value) => visitor.visit_i64(value),
+            Tag::Float(value) => visitor.visit_f32(value),
+            Tag::Double(value) => visitor.visit_f64(value),
+            Tag::ByteArray(array) => visit_list(array.into_iter().map(Tag::Byte).collect(), visitor),
+            Tag::String(value) => visitor.visit_string(value),
+            Tag::List(list) => visit_list(list, visitor),
+            Tag::Compound(compound) => visit_compound(compound, visitor),
+            Tag::IntArray(array) => visit_list(array.into_iter().map(Tag::Int).collect(), visitor),
+            Tag::LongArray(array) => visit_list(array.into_iter().map(Tag::Long).collect(), visitor)
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Tag::Byte(value) => visitor.visit_bool(value != 0),
+            tag => tag.deserialize_any(visitor)
+        }
+    }
+
+    // Unsigned numbers were stored in the signed tag of the same size, read them back without range checks
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Tag::Byte(value) => visitor.visit_u8(value as u8),
+            tag => tag.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Tag::Short(value) => visitor.visit_u16(value as u16),
+            tag => tag.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Tag::Int(value) => visitor.visit_u32(value as u32),
+            tag => tag.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Tag::Long(value) => visitor.visit_u64(value as u64),
+            tag => tag.deserialize_any(visitor)
+        }
+    }
+
+    /// None is never written, so anything that is there is Some
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str, _variants: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Tag::String(variant) => visitor.visit_enum(<String as IntoDeserializer<'de, Error>>::into_deserializer(variant)),
+            Tag::Compound(compound) => {
+                if compound.len() != 1 {
+                    return Err(Error::new("enum variants with data must be a compound with one entry"));
+                }
+
+                let (variant, value) = compound.into_iter().next().unwrap();
+                visitor.visit_enum(EnumDeserializer { variant, value })
+            }
+            _ => Err(Error::new("expected a string or a compound for an enum"))
+        }
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u128 f32 f64 char str string bytes byte_buf seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+fn visit_list<'de, V: Visitor<'de>>(list: Vec<Tag>, visitor: V) -> Result<V::Value, Error> {
+    let mut seq = SeqDeserializer::new(list.into_iter());
+    let value = visitor.visit_seq(&mut seq)?;
+    seq.end()?;
+    Ok(value)
+}
+
+fn visit_compound<'de, V: Visitor<'de>>(compound: Compound, visitor: V) -> Result<V::Value, Error> {
+    let mut map = MapDeserializer::new(compound.into_iter());
+    let value = visitor.visit_map(&mut map)?;
+    map.end()?;
+    Ok(value)
+}
+
+/// An enum variant with data, stored as a compound with the variant name as its only key
+struct EnumDeserializer {
+    variant: String,
+    value: Tag
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = Error;
+    type Variant = Tag;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<(S::Value, Tag), Error> {
+        let variant = seed.deserialize(<String as IntoDeserializer<'de, Error>>::into_deserializer(self.variant))?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Tag {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+/// Lets a Tag be read as a field of a type being deserialized, keeping it as NBT
+impl<'de> Deserialize<'de> for Tag {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Tag, D::Error> {
+        deserializer.deserialize_any(TagVisitor)
+    }
+}
+
+struct TagVisitor;
+
+impl<'de> Visitor<'de> for TagVisitor {
+    type Value = Tag;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("any NBT value")
+    }
+
+    fn visit_bool<E>(self, value: bool) -> Result<Tag, E> {
+        Ok(Tag::Byte(value as i8))
+    }
+
+    fn visit_i8<E>(self, value: i8) -> Result<Tag, E> {
+        Ok(Tag::Byte(value))
+    }
+
+    fn visit_i16<E>(self, value: i16) -> Result<Tag, E> {
+        Ok(Tag::Short(value))
+    }
+
+    fn visit_i32<E>(self, value: i32) -> Result<Tag, E> {
+        Ok(Tag::Int(value))
+    }
+
+    fn visit_i64<E>(self, value: i64) -> Result<Tag, E> {
+        Ok(Tag::Long(value))
+    }
+
+    fn visit_u64<E>(self, value: u64) -> Result<Tag, E> {
+        Ok(Tag::Long(value as i64))
+    }
+
+    fn visit_f32<E>(self, value: f32) -> Result<Tag, E> {
+        Ok(Tag::Float(value))
+    }
+
+    fn visit_f64<E>(self, value: f64) -> Result<Tag, E> {
+        Ok(Tag::Double(value))
+    }
+
+    fn visit_str<E>(self, value: &str) -> Result<Tag, E> {
+        Ok(Tag::String(value.to_string()))
+    }
+
+    fn visit_string<E>(self, value: String) -> Result<Tag, E> {
+        Ok(Tag::String(value))
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Tag, A::Error> {
+        let mut list = Vec::new();
+
+        while let Some(element) = seq.next_element()? {
+            list.push(element);
+        }
+
+        Ok(Tag::List(list))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Tag, A::Error> {
+        let mut compound = Compound::new();
+
+        while let Some((name, value)) = map.next_entry()? {
+            compound.insert(name, value);
+        }
+
+        Ok(Tag::Compound(compound))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nbt::{Tag, ByteArray, IntArray, LongArray, from_tag, to_tag};
+    use crate::nbt::read::read_nbt;
+    use crate::nbt::write::write_nbt;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Block {
+        #[serde(rename = "Name")]
+        name: String,
+        #[serde(rename = "Properties", default, skip_serializing_if = "Option::is_none")]
+        properties: Option<BTreeMap<String, String>>
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Level {
+        version: i32,
+        seed: i64,
+        id: u16,
+        hardcore: bool,
+        height: i16,
+        light: i8,
+        spawn: Vec<f64>,
+        speed: f32,
+        name: String,
+        palette: Vec<Block>,
+        empty: Vec<Block>,
+        heights: IntArray,
+        states: LongArray,
+        biomes: ByteArray,
+        weather: Option<String>,
+        rules: BTreeMap<String, bool>
+    }
+
+    fn level() -> Level {
+        let mut properties = BTreeMap::new();
+        properties.insert(String::from("axis"), String::from("y"));
+
+        let mut rules = BTreeMap::new();
+        rules.insert(String::from("doDaylightCycle"), true);
+
+        Level {
+            version: 19133,
+            seed: -4_172_144_997_902_289_642,
+            id: 300,
+            hardcore: true,
+            height: -64,
+            light: 15,
+            spawn: vec![0.5, 70.0, -12.25],
+            speed: 0.1,
+            name: String::from("New \"World\""),
+            palette: vec![
+                Block { name: String::from("minecraft:air"), properties: None },
+                Block { name: String::from("minecraft:oak_log"), properties: Some(properties) }
+            ],
+            empty: Vec::new(),
+            heights: IntArray(vec![64, 65, 66]),
+            states: LongArray(vec![i64::MIN, 0, i64::MAX]),
+            biomes: ByteArray(vec![1, -1]),
+            weather: None,
+            rules
+        }
+    }
+
+    #[test]
+    fn struct_becomes_a_compound() {
+        let tag = to_tag(&level()).unwrap();
+
+        assert_eq!(tag.get("version"), Some(&Tag::Int(19133)));
+        assert_eq!(tag.get("hardcore"), Some(&Tag::Byte(1)));
+        assert_eq!(tag.get("id"), Some(&Tag::Short(300)));
+        assert_eq!(tag.get("heights"), Some(&Tag::IntArray(vec![64, 65, 66])));
+        assert_eq!(tag.get("states"), Some(&Tag::LongArray(vec![i64::MIN, 0, i64::MAX])));
+        assert_eq!(tag.get("biomes"), Some(&Tag::ByteArray(vec![1, -1])));
+        assert_eq!(tag.get("spawn"), Some(&Tag::List(vec![Tag::Double(0.5), Tag::Double(70.0), Tag::Double(-12.25)])));
+        assert_eq!(tag.get("empty"), Some(&Tag::List(Vec::new())));
+        assert_eq!(tag.get("rules").and_then(|rules| rules.get("doDaylightCycle")), Some(&Tag::Byte(1)));
+
+        // None is left out rather than written as anything
+        assert_eq!(tag.get("weather"), None);
+        assert_eq!(tag.get("palette").and_then(Tag::as_list).map(|palette| palette[0].get("Properties")), Some(None));
+    }
+
+    #[test]
+    fn struct_round_trips_through_a_tag() {
+        assert_eq!(from_tag::<Level>(to_tag(&level()).unwrap()).unwrap(), level());
+    }
+
+    #[test]
+    fn struct_round_trips_through_binary_nbt() {
+        let mut data = Vec::new();
+        write_nbt(&mut data, "Data", &to_tag(&level()).unwrap()).unwrap();
+        let (name, tag) = read_nbt(&mut data.as_slice()).unwrap();
+
+        assert_eq!(name, "Data");
+        assert_eq!(from_tag::<Level>(tag).unwrap(), level());
+    }
+
+    #[test]
+    fn arrays_can_be_read_into_plain_vecs() {
+        #[derive(Deserialize)]
+        struct Heights {
+            heights: Vec<i32>
+        }
+
+        let tag = to_tag(&level()).unwrap();
+
+        assert_eq!(from_tag::<Heights>(tag).unwrap().heights, vec![64, 65, 66]);
+    }
+
+    #[test]
+    fn missing_fields_are_an_error() {
+        let tag = to_tag(&Block { name: String::from("minecraft:stone"), properties: None }).unwrap();
+
+        assert!(from_tag::<Level>(tag).is_err());
+    }
+}
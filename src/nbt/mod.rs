@@ -0,0 +1,198 @@
+//
+// Minecraft's named binary tag format, used by Anvil worlds, structures and level data
+//
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fmt::{self, Display, Formatter};
+
+pub mod read;
+pub mod write;
+pub mod compression;
+pub mod snbt;
+pub mod ser;
+pub mod de;
+
+pub use ser::to_tag;
+pub use de::from_tag;
+
+pub type Compound = BTreeMap<String, Tag>;
+
+/// A single value and everything nested inside it. The tag ids match the binary format.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(Compound),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>)
+}
+
+pub const TAG_END: u8 = 0;
+pub const TAG_BYTE: u8 = 1;
+pub const TAG_SHORT: u8 = 2;
+pub const TAG_INT: u8 = 3;
+pub const TAG_LONG: u8 = 4;
+pub const TAG_FLOAT: u8 = 5;
+pub const TAG_DOUBLE: u8 = 6;
+pub const TAG_BYTE_ARRAY: u8 = 7;
+pub const TAG_STRING: u8 = 8;
+pub const TAG_LIST: u8 = 9;
+pub const TAG_COMPOUND: u8 = 10;
+pub const TAG_INT_ARRAY: u8 = 11;
+pub const TAG_LONG_ARRAY: u8 = 12;
+
+impl Tag {
+    pub fn id(&self) -> u8 {
+        match self {
+            Tag::Byte(_) => TAG_BYTE,
+            Tag::Short(_) => TAG_SHORT,
+            Tag::Int(_) => TAG_INT,
+            Tag::Long(_) => TAG_LONG,
+            Tag::Float(_) => TAG_FLOAT,
+            Tag::Double(_) => TAG_DOUBLE,
+            Tag::ByteArray(_) => TAG_BYTE_ARRAY,
+            Tag::String(_) => TAG_STRING,
+            Tag::List(_) => TAG_LIST,
+            Tag::Compound(_) => TAG_COMPOUND,
+            Tag::IntArray(_) => TAG_INT_ARRAY,
+            Tag::LongArray(_) => TAG_LONG_ARRAY
+        }
+    }
+
+    /// Gets a child of a compound by name, None if this isn't a compound or has no such child
+    pub fn get(&self, name: &str) -> Option<&Tag> {
+        self.as_compound().and_then(|compound| compound.get(name))
+    }
+
+    /// Any whole number tag widened to an i64, Minecraft isn't always consistent about which size it uses
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Tag::Byte(value) => Some(*value as i64),
+            Tag::Short(value) => Some(*value as i64),
+            Tag::Int(value) => Some(*value as i64),
+            Tag::Long(value) => Some(*value),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Tag::String(value) => Some(value.as_str()),
+            _ => None
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&Vec<Tag>> {
+        match self {
+            Tag::List(list) => Some(list),
+            _ => None
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<&Compound> {
+        match self {
+            Tag::Compound(compound) => Some(compound),
+            _ => None
+        }
+    }
+
+    pub fn as_long_array(&self) -> Option<&Vec<i64>> {
+        match self {
+            Tag::LongArray(array) => Some(array),
+            _ => None
+        }
+    }
+}
+
+/// Names the serializer looks for to write a sequence as an array tag instead of a list
+pub const BYTE_ARRAY_NAME: &str = "$nbt:byte_array";
+pub const INT_ARRAY_NAME: &str = "$nbt:int_array";
+pub const LONG_ARRAY_NAME: &str = "$nbt:long_array";
+
+/// Wrap a field in one of these to have it stored as an array tag, a plain Vec becomes a list
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ByteArray(pub Vec<i8>);
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IntArray(pub Vec<i32>);
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LongArray(pub Vec<i64>);
+
+impl Serialize for ByteArray {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(BYTE_ARRAY_NAME, &self.0)
+    }
+}
+
+impl Serialize for IntArray {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(INT_ARRAY_NAME, &self.0)
+    }
+}
+
+impl Serialize for LongArray {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(LONG_ARRAY_NAME, &self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for ByteArray {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<ByteArray, D::Error> {
+        Vec::deserialize(deserializer).map(ByteArray)
+    }
+}
+
+impl<'de> Deserialize<'de> for IntArray {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<IntArray, D::Error> {
+        Vec::deserialize(deserializer).map(IntArray)
+    }
+}
+
+impl<'de> Deserialize<'de> for LongArray {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<LongArray, D::Error> {
+        Vec::deserialize(deserializer).map(LongArray)
+    }
+}
+
+/// Something that couldn't be converted to or from NBT with serde
+#[derive(Debug)]
+pub struct Error {
+    message: String
+}
+
+impl Error {
+    pub fn new(message: &str) -> Error {
+        Error {
+            message: message.to_string()
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl serde::ser::Error for Error {
+    fn custom<T: Display>(message: T) -> Error {
+        Error::new(&message.to_string())
+    }
+}
+
+impl serde::de::Error for Error {
+    fn custom<T: Display>(message: T) -> Error {
+        Error::new(&message.to_string())
+    }
+}
@@ -0,0 +1,164 @@
+//
+// Reads uncompressed big endian NBT
+//
+
+use crate::nbt::*;
+use std::io::{self, Read};
+
+/// Compounds and lists nested deeper than this are treated as corrupt rather than risking the stack
+const MAX_DEPTH: usize = 512;
+
+/// Reads a named root tag, which is a compound in every file Minecraft writes
+pub fn read_nbt<R: Read>(reader: &mut R) -> io::Result<(String, Tag)> {
+    let id = read_u8(reader)?;
+
+    if id == TAG_END {
+        return Err(invalid("NBT data has no root tag"));
+    }
+
+    let name = read_string(reader)?;
+    let tag = read_payload(reader, id, 0)?;
+
+    Ok((name, tag))
+}
+
+fn read_payload<R: Read>(reader: &mut R, id: u8, depth: usize) -> io::Result<Tag> {
+    if depth > MAX_DEPTH {
+        return Err(invalid("NBT is nested too deeply"));
+    }
+
+    Ok(match id {
+        TAG_BYTE => Tag::Byte(read_u8(reader)? as i8),
+        TAG_SHORT => Tag::Short(read_bytes::<R, [u8; 2]>(reader).map(i16::from_be_bytes)?),
+        TAG_INT => Tag::Int(read_i32(reader)?),
+        TAG_LONG => Tag::Long(read_bytes::<R, [u8; 8]>(reader).map(i64::from_be_bytes)?),
+        TAG_FLOAT => Tag::Float(f32::from_bits(read_i32(reader)? as u32)),
+        TAG_DOUBLE => Tag::Double(f64::from_bits(read_bytes::<R, [u8; 8]>(reader).map(u64::from_be_bytes)?)),
+        TAG_BYTE_ARRAY => {
+            let mut bytes = vec![0; read_length(reader)?];
+            reader.read_exact(&mut bytes)?;
+            Tag::ByteArray(bytes.into_iter().map(|byte| byte as i8).collect())
+        }
+        TAG_STRING => Tag::String(read_string(reader)?),
+        TAG_LIST => {
+            let element_id = read_u8(reader)?;
+            let length = read_length(reader)?;
+            let mut list = Vec::with_capacity(length.min(1024));
+
+            for _ in 0..length {
+                list.push(read_payload(reader, element_id, depth + 1)?);
+            }
+
+            Tag::List(list)
+        }
+        TAG_COMPOUND => {
+            let mut compound = Compound::new();
+
+            loop {
+                let child_id = read_u8(reader)?;
+
+                if child_id == TAG_END {
+                    break;
+                }
+
+                let name = read_string(reader)?;
+                compound.insert(name, read_payload(reader, child_id, depth + 1)?);
+            }
+
+            Tag::Compound(compound)
+        }
+        TAG_INT_ARRAY => {
+            let length = read_length(reader)?;
+            let mut array = Vec::with_capacity(length.min(1024));
+
+            for _ in 0..length {
+                array.push(read_i32(reader)?);
+            }
+
+            Tag::IntArray(array)
+        }
+        TAG_LONG_ARRAY => {
+            let length = read_length(reader)?;
+            let mut array = Vec::with_capacity(length.min(1024));
+
+            for _ in 0..length {
+                array.push(read_bytes::<R, [u8; 8]>(reader).map(i64::from_be_bytes)?);
+            }
+
+            Tag::LongArray(array)
+        }
+        id => return Err(invalid(&format!("Unknown tag id {}", id)))
+    })
+}
+
+/// Strings are Java's modified UTF-8, which only differs from UTF-8 for nulls and characters outside the BMP
+fn read_string<R: Read>(reader: &mut R) -> io::Result<String> {
+    let length = read_bytes::<R, [u8; 2]>(reader).map(u16::from_be_bytes)? as usize;
+    let mut bytes = vec![0; length];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(decode_modified_utf8(&bytes))
+}
+
+/// NBT strings are Java's modified UTF-8. NUL is written as two bytes and characters outside the BMP as a surrogate pair
+/// of three bytes each. Anything that can't be decoded becomes the replacement character.
+fn decode_modified_utf8(bytes: &[u8]) -> String {
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let lead = bytes[i] as u32;
+        let continuation = |offset: usize| bytes.get(i + offset).map(|byte| *byte as u32).filter(|byte| byte & 0xC0 == 0x80);
+
+        let (unit, length) = if lead < 0x80 {
+            (Some(lead), 1)
+        } else if lead & 0xE0 == 0xC0 {
+            (continuation(1).map(|second| ((lead & 0x1F) << 6) | (second & 0x3F)), 2)
+        } else if lead & 0xF0 == 0xE0 {
+            (continuation(1).and_then(|second| continuation(2).map(|third| ((lead & 0x0F) << 12) | ((second & 0x3F) << 6) | (third & 0x3F))), 3)
+        } else {
+            (None, 1)
+        };
+
+        match unit {
+            Some(unit) => {
+                units.push(unit as u16);
+                i += length;
+            }
+            None => {
+                units.push(0xFFFD);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf16_lossy(&units)
+}
+
+fn read_length<R: Read>(reader: &mut R) -> io::Result<usize> {
+    let length = read_i32(reader)?;
+
+    if length < 0 {
+        return Err(invalid("Negative NBT length"));
+    }
+
+    Ok(length as usize)
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> io::Result<u8> {
+    read_bytes::<R, [u8; 1]>(reader).map(|bytes| bytes[0])
+}
+
+fn read_i32<R: Read>(reader: &mut R) -> io::Result<i32> {
+    read_bytes::<R, [u8; 4]>(reader).map(i32::from_be_bytes)
+}
+
+fn read_bytes<R: Read, B: AsMut<[u8]> + Default>(reader: &mut R) -> io::Result<B> {
+    let mut bytes = B::default();
+    reader.read_exact(bytes.as_mut())?;
+    Ok(bytes)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
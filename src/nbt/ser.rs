@@ -0,0 +1,372 @@
+//
+// Serde serializer that turns any Serialize type into a Tag
+//
+
+use crate::nbt::{Tag, Compound, Error, BYTE_ARRAY_NAME, INT_ARRAY_NAME, LONG_ARRAY_NAME};
+use serde::ser::{self, Serialize};
+
+/// Converts a value to NBT. Structs and maps become compounds and sequences become lists.
+/// Unsigned numbers are stored in the signed tag of the same size, and fields that are None are left out.
+pub fn to_tag<T: Serialize + ?Sized>(value: &T) -> Result<Tag, Error> {
+    value.serialize(Serializer)?.ok_or_else(|| Error::new("NBT has no way to store a value of None"))
+}
+
+/// Produces None for values that shouldn't be written at all
+struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Option<Tag>;
+    type Error = Error;
+    type SerializeSeq = ListSerializer;
+    type SerializeTuple = ListSerializer;
+    type SerializeTupleStruct = ListSerializer;
+    type SerializeTupleVariant = ListSerializer;
+    type SerializeMap = CompoundSerializer;
+    type SerializeStruct = CompoundSerializer;
+    type SerializeStructVariant = CompoundSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Option<Tag>, Error> {
+        Ok(Some(Tag::Byte(v as i8)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Option<Tag>, Error> {
+        Ok(Some(Tag::Byte(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Option<Tag>, Error> {
+        Ok(Some(Tag::Short(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Option<Tag>, Error> {
+        Ok(Some(Tag::Int(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Option<Tag>, Error> {
+        Ok(Some(Tag::Long(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Option<Tag>, Error> {
+        Ok(Some(Tag::Byte(v as i8)))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Option<Tag>, Error> {
+        Ok(Some(Tag::Short(v as i16)))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Option<Tag>, Error> {
+        Ok(Some(Tag::Int(v as i32)))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Option<Tag>, Error> {
+        Ok(Some(Tag::Long(v as i64)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Option<Tag>, Error> {
+        Ok(Some(Tag::Float(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Option<Tag>, Error> {
+        Ok(Some(Tag::Double(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Option<Tag>, Error> {
+        Ok(Some(Tag::String(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Option<Tag>, Error> {
+        Ok(Some(Tag::String(v.to_string())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Option<Tag>, Error> {
+        Ok(Some(Tag::ByteArray(v.iter().map(|byte| *byte as i8).collect())))
+    }
+
+    fn serialize_none(self) -> Result<Option<Tag>, Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Option<Tag>, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Option<Tag>, Error> {
+        Ok(Some(Tag::Compound(Compound::new())))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Option<Tag>, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32, variant: &'static str) -> Result<Option<Tag>, Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(self, name: &'static str, value: &T) -> Result<Option<Tag>, Error> {
+        let is_array = name == BYTE_ARRAY_NAME || name == INT_ARRAY_NAME || name == LONG_ARRAY_NAME;
+
+        // The array wrappers turn their list into the matching array tag
+        let list = match value.serialize(self)? {
+            Some(Tag::List(list)) if is_array => list,
+            tag => return Ok(tag)
+        };
+
+        let array = match name {
+            BYTE_ARRAY_NAME => list.iter().map(|element| match element {
+                Tag::Byte(value) => Ok(*value),
+                _ => Err(Error::new("byte arrays can only hold bytes"))
+            }).collect::<Result<_, _>>().map(Tag::ByteArray),
+            INT_ARRAY_NAME => list.iter().map(|element| match element {
+                Tag::Int(value) => Ok(*value),
+                _ => Err(Error::new("int arrays can only hold ints"))
+            }).collect::<Result<_, _>>().map(Tag::IntArray),
+            _ => list.iter().map(|element| match element {
+                Tag::Long(value) => Ok(*value),
+                _ => Err(Error::new("long arrays can only hold longs"))
+            }).collect::<Result<_, _>>().map(Tag::LongArray)
+        };
+
+        array.map(Some)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(self, _name: &'static str, _index: u32, variant: &'static str, value: &T) -> Result<Option<Tag>, Error> {
+        let mut compound = Compound::new();
+
+        if let Some(tag) = value.serialize(self)? {
+            compound.insert(variant.to_string(), tag);
+        }
+
+        Ok(Some(Tag::Compound(compound)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<ListSerializer, Error> {
+        Ok(ListSerializer {
+            list: Vec::with_capacity(len.unwrap_or(0)),
+            variant: None
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<ListSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<ListSerializer, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32, variant: &'static str, len: usize) -> Result<ListSerializer, Error> {
+        Ok(ListSerializer {
+            list: Vec::with_capacity(len),
+            variant: Some(variant)
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<CompoundSerializer, Error> {
+        Ok(CompoundSerializer {
+            compound: Compound::new(),
+            key: None,
+            variant: None
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<CompoundSerializer, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32, variant: &'static str, _len: usize) -> Result<CompoundSerializer, Error> {
+        Ok(CompoundSerializer {
+            compound: Compound::new(),
+            key: None,
+            variant: Some(variant)
+        })
+    }
+}
+
+/// Enum variants with data are written as a compound holding the data under the variant's name
+fn wrap_variant(variant: Option<&'static str>, tag: Tag) -> Tag {
+    match variant {
+        Some(variant) => {
+            let mut compound = Compound::new();
+            compound.insert(variant.to_string(), tag);
+            Tag::Compound(compound)
+        }
+        None => tag
+    }
+}
+
+struct ListSerializer {
+    list: Vec<Tag>,
+    variant: Option<&'static str>
+}
+
+impl ListSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let tag = value.serialize(Serializer)?.ok_or_else(|| Error::new("NBT lists can't hold None"))?;
+
+        if self.list.first().is_some_and(|first| first.id() != tag.id()) {
+            return Err(Error::new("NBT lists can only hold one type of tag"));
+        }
+
+        self.list.push(tag);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Tag>, Error> {
+        Ok(Some(wrap_variant(self.variant, Tag::List(self.list))))
+    }
+}
+
+impl ser::SerializeSeq for ListSerializer {
+    type Ok = Option<Tag>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Tag>, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for ListSerializer {
+    type Ok = Option<Tag>;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Tag>, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for ListSerializer {
+    type Ok = Option<Tag>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Tag>, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for ListSerializer {
+    type Ok = Option<Tag>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Tag>, Error> {
+        self.finish()
+    }
+}
+
+struct CompoundSerializer {
+    compound: Compound,
+    /// The key of a map entry waiting for its value
+    key: Option<String>,
+    variant: Option<&'static str>
+}
+
+impl CompoundSerializer {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), Error> {
+        if let Some(tag) = value.serialize(Serializer)? {
+            self.compound.insert(key, tag);
+        }
+
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Tag>, Error> {
+        Ok(Some(wrap_variant(self.variant, Tag::Compound(self.compound))))
+    }
+}
+
+impl ser::SerializeMap for CompoundSerializer {
+    type Ok = Option<Tag>;
+    type Error = Error;
+
+    /// Compound names are strings, number keys are written out as text
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        let key = match key.serialize(Serializer)? {
+            Some(Tag::String(key)) => Some(key),
+            Some(tag) => tag.as_i64().map(|number| number.to_string()),
+            None => None
+        };
+
+        self.key = Some(key.ok_or_else(|| Error::new("NBT compound names must be strings or numbers"))?);
+
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self.key.take().ok_or_else(|| Error::new("map value serialized before its key"))?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Option<Tag>, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for CompoundSerializer {
+    type Ok = Option<Tag>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Option<Tag>, Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for CompoundSerializer {
+    type Ok = Option<Tag>;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Option<Tag>, Error> {
+        self.finish()
+    }
+}
+
+/// Lets a Tag be a field of a type being serialized, arrays stay arrays
+impl Serialize for Tag {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeMap;
+
+        match self {
+            Tag::Byte(value) => serializer.serialize_i8(*value),
+            Tag::Short(value) => serializer.serialize_i16(*value),
+            Tag::Int(value) => serializer.serialize_i32(*value),
+            Tag::Long(value) => serializer.serialize_i64(*value),
+            Tag::Float(value) => serializer.serialize_f32(*value),
+            Tag::Double(value) => serializer.serialize_f64(*value),
+            Tag::ByteArray(array) => serializer.serialize_newtype_struct(BYTE_ARRAY_NAME, array),
+            Tag::String(value) => serializer.serialize_str(value),
+            Tag::List(list) => list.serialize(serializer),
+            Tag::Compound(compound) => {
+                let mut map = serializer.serialize_map(Some(compound.len()))?;
+
+                for (name, child) in compound {
+                    map.serialize_entry(name, child)?;
+                }
+
+                map.end()
+            }
+            Tag::IntArray(array) => serializer.serialize_newtype_struct(INT_ARRAY_NAME, array),
+            Tag::LongArray(array) => serializer.serialize_newtype_struct(LONG_ARRAY_NAME, array)
+        }
+    }
+}
@@ -0,0 +1,160 @@
+//
+// Stringified NBT, the text form Minecraft uses in commands. Handy for looking at NBT while debugging.
+//
+
+use crate::nbt::Tag;
+use std::fmt::{self, Display, Formatter, Write};
+
+/// Formats as SNBT on one line, or spread over several indented lines with {:#}
+impl Display for Tag {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let indent = if f.alternate() { Some(0) } else { None };
+        write_tag(f, self, indent)
+    }
+}
+
+fn write_tag(f: &mut Formatter, tag: &Tag, indent: Option<usize>) -> fmt::Result {
+    match tag {
+        Tag::Byte(value) => write!(f, "{}b", value),
+        Tag::Short(value) => write!(f, "{}s", value),
+        Tag::Int(value) => write!(f, "{}", value),
+        Tag::Long(value) => write!(f, "{}L", value),
+        Tag::Float(value) => write!(f, "{}f", value),
+        Tag::Double(value) => write!(f, "{}d", value),
+        Tag::ByteArray(array) => write_array(f, "B", array.iter().map(|value| format!("{}b", value))),
+        Tag::String(value) => write_quoted(f, value),
+        Tag::List(list) => {
+            // Lists of numbers and strings are short enough to keep on one line
+            let nested = list.iter().any(|element| matches!(element, Tag::List(_) | Tag::Compound(_)));
+
+            write_entries(f, '[', ']', list.iter().map(|element| (None, element)), if nested { indent } else { None })
+        }
+        Tag::Compound(compound) => write_entries(f, '{', '}', compound.iter().map(|(name, child)| (Some(name), child)), indent),
+        Tag::IntArray(array) => write_array(f, "I", array.iter().map(|value| value.to_string())),
+        Tag::LongArray(array) => write_array(f, "L", array.iter().map(|value| format!("{}L", value)))
+    }
+}
+
+/// Writes the children of a list or compound, each on its own line if indent is given
+fn write_entries<'a, I: Iterator<Item = (Option<&'a String>, &'a Tag)>>(f: &mut Formatter, open: char, close: char, entries: I, indent: Option<usize>) -> fmt::Result {
+    f.write_char(open)?;
+
+    let mut empty = true;
+
+    for (i, (name, child)) in entries.enumerate() {
+        empty = false;
+
+        if i > 0 {
+            f.write_char(',')?;
+            if indent.is_none() {
+                f.write_char(' ')?;
+            }
+        }
+
+        if let Some(indent) = indent {
+            write!(f, "\n{:width$}", "", width = (indent + 1) * 4)?;
+        }
+
+        if let Some(name) = name {
+            write_name(f, name)?;
+            f.write_str(": ")?;
+        }
+
+        write_tag(f, child, indent.map(|indent| indent + 1))?;
+    }
+
+    if let (Some(indent), false) = (indent, empty) {
+        write!(f, "\n{:width$}", "", width = indent * 4)?;
+    }
+
+    f.write_char(close)
+}
+
+fn write_array<I: Iterator<Item = String>>(f: &mut Formatter, prefix: &str, values: I) -> fmt::Result {
+    write!(f, "[{};", prefix)?;
+
+    for (i, value) in values.enumerate() {
+        f.write_str(if i == 0 { " " } else { ", " })?;
+        f.write_str(&value)?;
+    }
+
+    f.write_char(']')
+}
+
+/// Names only need quoting if they have characters that mean something in SNBT
+fn write_name(f: &mut Formatter, name: &str) -> fmt::Result {
+    let plain = !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.' || c == '+');
+
+    if plain {
+        f.write_str(name)
+    } else {
+        write_quoted(f, name)
+    }
+}
+
+fn write_quoted(f: &mut Formatter, value: &str) -> fmt::Result {
+    f.write_char('"')?;
+
+    for c in value.chars() {
+        if c == '"' || c == '\\' {
+            f.write_char('\\')?;
+        }
+
+        f.write_char(c)?;
+    }
+
+    f.write_char('"')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nbt::{Tag, Compound};
+
+    fn compound(children: Vec<(&str, Tag)>) -> Tag {
+        Tag::Compound(children.into_iter().map(|(name, child)| (name.to_string(), child)).collect::<Compound>())
+    }
+
+    #[test]
+    fn strings_escape_quotes_and_backslashes() {
+        let tag = Tag::String(String::from(r#"say "hi" C:\saves"#));
+
+        assert_eq!(tag.to_string(), r#""say \"hi\" C:\\saves""#);
+    }
+
+    #[test]
+    fn names_are_only_quoted_when_they_need_it() {
+        let tag = compound(vec![
+            ("plain_Name-1.0+", Tag::Int(1)),
+            ("has space", Tag::Int(2)),
+            ("", Tag::Int(3)),
+            ("quote\"d", Tag::Int(4))
+        ]);
+
+        assert_eq!(tag.to_string(), r#"{"": 3, "has space": 2, plain_Name-1.0+: 1, "quote\"d": 4}"#);
+    }
+
+    #[test]
+    fn numbers_and_arrays_have_their_suffixes() {
+        let tag = Tag::List(vec![
+            Tag::ByteArray(vec![1, -2]),
+            Tag::IntArray(vec![3, 4]),
+            Tag::LongArray(vec![5]),
+            Tag::IntArray(Vec::new())
+        ]);
+
+        assert_eq!(tag.to_string(), "[[B; 1b, -2b], [I; 3, 4], [L; 5L], [I;]]");
+        assert_eq!(compound(vec![("b", Tag::Byte(1)), ("d", Tag::Double(0.5)), ("f", Tag::Float(1.5)), ("l", Tag::Long(-7)), ("s", Tag::Short(2))]).to_string(),
+            "{b: 1b, d: 0.5d, f: 1.5f, l: -7L, s: 2s}");
+    }
+
+    #[test]
+    fn alternate_format_indents_nested_tags() {
+        let tag = compound(vec![
+            ("empty", compound(Vec::new())),
+            ("list", Tag::List(vec![compound(vec![("y", Tag::Int(1))])])),
+            ("numbers", Tag::List(vec![Tag::Int(1), Tag::Int(2)]))
+        ]);
+
+        assert_eq!(format!("{:#}", tag), "{\n    empty: {},\n    list: [\n        {\n            y: 1\n        }\n    ],\n    numbers: [1, 2]\n}");
+    }
+}
@@ -0,0 +1,237 @@
+//
+// Writes uncompressed big endian NBT
+//
+
+use crate::nbt::*;
+use std::io::{self, Write};
+
+/// Writes a named root tag
+pub fn write_nbt<W: Write>(writer: &mut W, name: &str, tag: &Tag) -> io::Result<()> {
+    writer.write_all(&[tag.id()])?;
+    write_string(writer, name)?;
+    write_payload(writer, tag)
+}
+
+fn write_payload<W: Write>(writer: &mut W, tag: &Tag) -> io::Result<()> {
+    match tag {
+        Tag::Byte(value) => writer.write_all(&[*value as u8]),
+        Tag::Short(value) => writer.write_all(&value.to_be_bytes()),
+        Tag::Int(value) => writer.write_all(&value.to_be_bytes()),
+        Tag::Long(value) => writer.write_all(&value.to_be_bytes()),
+        Tag::Float(value) => writer.write_all(&value.to_bits().to_be_bytes()),
+        Tag::Double(value) => writer.write_all(&value.to_bits().to_be_bytes()),
+        Tag::ByteArray(array) => {
+            write_length(writer, array.len())?;
+            writer.write_all(&array.iter().map(|byte| *byte as u8).collect::<Vec<u8>>())
+        }
+        Tag::String(value) => write_string(writer, value),
+        Tag::List(list) => {
+            // Empty lists have no element type, Minecraft writes them as lists of end tags
+            let element_id = list.first().map_or(TAG_END, Tag::id);
+
+            if list.iter().any(|element| element.id() != element_id) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "NBT lists can only hold one type of tag"));
+            }
+
+            writer.write_all(&[element_id])?;
+            write_length(writer, list.len())?;
+
+            for element in list {
+                write_payload(writer, element)?;
+            }
+
+            Ok(())
+        }
+        Tag::Compound(compound) => {
+            for (name, child) in compound {
+                writer.write_all(&[child.id()])?;
+                write_string(writer, name)?;
+                write_payload(writer, child)?;
+            }
+
+            writer.write_all(&[TAG_END])
+        }
+        Tag::IntArray(array) => {
+            write_length(writer, array.len())?;
+
+            for value in array {
+                writer.write_all(&value.to_be_bytes())?;
+            }
+
+            Ok(())
+        }
+        Tag::LongArray(array) => {
+            write_length(writer, array.len())?;
+
+            for value in array {
+                writer.write_all(&value.to_be_bytes())?;
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Written as plain UTF-8, which Java reads the same as its modified UTF-8 except for nulls and characters outside the BMP
+fn write_string<W: Write>(writer: &mut W, value: &str) -> io::Result<()> {
+    let bytes = encode_modified_utf8(value);
+
+    if bytes.len() > u16::MAX as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "NBT strings can't be longer than 65535 bytes"));
+    }
+
+    writer.write_all(&(bytes.len() as u16).to_be_bytes())?;
+    writer.write_all(&bytes)
+}
+
+/// Java's modified UTF-8, which is UTF-8 over UTF-16 code units with NUL written as C0 80 so it never contains a zero byte
+fn encode_modified_utf8(value: &str) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(value.len());
+
+    for unit in value.encode_utf16() {
+        match unit {
+            0x01..=0x7F => bytes.push(unit as u8),
+            0 | 0x80..=0x7FF => {
+                bytes.push(0xC0 | (unit >> 6) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                bytes.push(0xE0 | (unit >> 12) as u8);
+                bytes.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                bytes.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+
+    bytes
+}
+
+fn write_length<W: Write>(writer: &mut W, length: usize) -> io::Result<()> {
+    if length > i32::MAX as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "NBT lists and arrays can't be longer than an i32"));
+    }
+
+    writer.write_all(&(length as i32).to_be_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::read::read_nbt;
+
+    fn round_trip(tag: &Tag) -> (String, Tag) {
+        let mut data = Vec::new();
+        write_nbt(&mut data, "root", tag).unwrap();
+
+        read_nbt(&mut data.as_slice()).unwrap()
+    }
+
+    fn compound(children: Vec<(&str, Tag)>) -> Tag {
+        Tag::Compound(children.into_iter().map(|(name, child)| (name.to_string(), child)).collect())
+    }
+
+    #[test]
+    fn every_tag_type_round_trips() {
+        let tag = compound(vec![
+            ("byte", Tag::Byte(-8)),
+            ("short", Tag::Short(-1234)),
+            ("int", Tag::Int(i32::MIN)),
+            ("long", Tag::Long(i64::MAX)),
+            ("float", Tag::Float(0.15625)),
+            ("double", Tag::Double(-1.0e300)),
+            ("byte_array", Tag::ByteArray(vec![0, -1, 127, -128])),
+            ("string", Tag::String(String::from("grass_block é ⛏"))),
+            ("list", Tag::List(vec![Tag::Short(1), Tag::Short(2)])),
+            ("compound", compound(vec![("inner", Tag::Int(5))])),
+            ("int_array", Tag::IntArray(vec![i32::MIN, 0, i32::MAX])),
+            ("long_array", Tag::LongArray(vec![i64::MIN, -1, i64::MAX]))
+        ]);
+
+        assert_eq!(round_trip(&tag), (String::from("root"), tag));
+    }
+
+    #[test]
+    fn nested_compounds_and_lists_round_trip() {
+        let tag = compound(vec![
+            ("Level", compound(vec![
+                ("Sections", Tag::List(vec![
+                    compound(vec![("Y", Tag::Byte(0)), ("Palette", Tag::List(vec![compound(vec![("Name", Tag::String(String::from("minecraft:stone")))])]))]),
+                    compound(vec![("Y", Tag::Byte(1)), ("Palette", Tag::List(Vec::new()))])
+                ])),
+                ("Lists", Tag::List(vec![Tag::List(vec![Tag::Int(1)]), Tag::List(vec![Tag::String(String::from("a"))]), Tag::List(Vec::new())]))
+            ]))
+        ]);
+
+        assert_eq!(round_trip(&tag).1, tag);
+    }
+
+    #[test]
+    fn empty_lists_and_arrays_round_trip() {
+        let tag = compound(vec![
+            ("list", Tag::List(Vec::new())),
+            ("byte_array", Tag::ByteArray(Vec::new())),
+            ("int_array", Tag::IntArray(Vec::new())),
+            ("long_array", Tag::LongArray(Vec::new())),
+            ("compound", compound(Vec::new()))
+        ]);
+
+        assert_eq!(round_trip(&tag).1, tag);
+    }
+
+    #[test]
+    fn empty_list_is_written_as_a_list_of_end_tags() {
+        let mut data = Vec::new();
+        write_nbt(&mut data, "", &Tag::List(Vec::new())).unwrap();
+
+        assert_eq!(data, vec![TAG_LIST, 0, 0, TAG_END, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn writes_big_endian() {
+        let mut data = Vec::new();
+        write_nbt(&mut data, "a", &compound(vec![("b", Tag::Short(0x0102)), ("c", Tag::IntArray(vec![3]))])).unwrap();
+
+        assert_eq!(data, vec![
+            TAG_COMPOUND, 0, 1, b'a',
+            TAG_SHORT, 0, 1, b'b', 1, 2,
+            TAG_INT_ARRAY, 0, 1, b'c', 0, 0, 0, 1, 0, 0, 0, 3,
+            TAG_END
+        ]);
+    }
+
+    #[test]
+    fn strings_are_modified_utf8() {
+        let mut data = Vec::new();
+        write_nbt(&mut data, "\0", &Tag::String(String::from("a\u{1F600}"))).unwrap();
+
+        // NUL takes two bytes and the emoji is a surrogate pair of three bytes each
+        assert_eq!(data, vec![
+            TAG_STRING, 0, 2, 0xC0, 0x80,
+            0, 7, b'a', 0xED, 0xA0, 0xBD, 0xED, 0xB8, 0x80
+        ]);
+    }
+
+    #[test]
+    fn nul_and_characters_outside_the_bmp_round_trip() {
+        let text = String::from("nul \0 emoji \u{1F600} é ⛏ \u{10FFFF}");
+        let tag = compound(vec![(text.as_str(), Tag::String(text.clone()))]);
+
+        assert_eq!(round_trip(&tag), (String::from("root"), tag));
+    }
+
+    #[test]
+    fn lists_of_mixed_tags_are_rejected() {
+        let mut data = Vec::new();
+
+        assert!(write_nbt(&mut data, "", &Tag::List(vec![Tag::Int(1), Tag::Long(1)])).is_err());
+    }
+
+    #[test]
+    fn truncated_data_is_an_error() {
+        let mut data = Vec::new();
+        write_nbt(&mut data, "root", &compound(vec![("long", Tag::Long(1))])).unwrap();
+        data.truncate(data.len() - 3);
+
+        assert!(read_nbt(&mut data.as_slice()).is_err());
+    }
+}
@@ -1,8 +1,7 @@
 use winit::window::Window;
 use crate::render::camera::Camera;
 use crate::render::pass::uniforms::Uniforms;
-use wgpu::{Texture, TextureView, Sampler, AdapterInfo, RenderPipeline, Device, BindGroupLayout, SwapChainDescriptor};
-use crate::block::{blocks, Block};
+use wgpu::{Texture, TextureView, Sampler, AdapterInfo, RenderPipeline, Device, BindGroupLayout, SwapChainDescriptor, ShaderModule};
 use crate::render::shaders::load_shaders;
 use std::time::Instant;
 use systemstat::{System, Platform};
@@ -10,6 +9,7 @@ use crate::services::asset_service::depth_map::{create_depth_texture, DEPTH_FORM
 use crate::services::{Services, ServicesContext};
 use crate::render::loading::LoadingScreen;
 use crate::services::chunk_service::mesh::Vertex;
+use crate::block::RenderLayer;
 
 pub mod pass;
 pub mod camera;
@@ -25,7 +25,8 @@ pub struct RenderState {
     sc_desc: wgpu::SwapChainDescriptor,
     swap_chain: Option<wgpu::SwapChain>,
 
-    render_pipeline: wgpu::RenderPipeline,
+    /// One pipeline per render layer, in RenderLayer order
+    render_pipelines: [wgpu::RenderPipeline; 3],
 
     size: winit::dpi::PhysicalSize<u32>,
 
@@ -36,7 +37,6 @@ pub struct RenderState {
 
     depth_texture: (Texture, TextureView, Sampler),
 
-    blocks: Vec<Block>,
 
     fps: u32,
     fps_counter: Instant,
@@ -68,10 +68,8 @@ impl RenderState {
         let mut loading = LoadingScreen::new(&device, &size);
         loading.render(&mut swap_chain, &device, &mut queue, 10);
 
-        let mut blocks = blocks::get_blocks();
-
         // Start the intensive job of loading services
-        let mut services = Services::load_services(ServicesContext::new(&mut device, &mut queue, &mut blocks, &size));
+        let services = Services::load_services(ServicesContext::new(&mut device, &mut queue, &size));
 
         //Change to 50 %
         loading.render(&mut swap_chain, &device, &mut queue, 90);
@@ -87,8 +85,14 @@ impl RenderState {
 
         let depth_texture = create_depth_texture(&device, &sc_desc);
 
-        let render_pipeline = generate_render_pipeline(&sc_desc, &device,
-                                                       &[&services.asset.atlas_bind_group_layout.as_ref().unwrap(), &uniform_bind_group_layout, &services.chunk.bind_group_layout]);
+        let bind_group_layouts = [services.asset.atlas_bind_group_layout.as_ref().unwrap(), &uniform_bind_group_layout, &services.chunk.bind_group_layout];
+        // Every layer shares the same shaders, so they're only compiled once
+        let shaders = load_shaders(&device);
+        let render_pipelines = [
+            generate_render_pipeline(&sc_desc, &device, &bind_group_layouts, &shaders, RenderLayer::Opaque),
+            generate_render_pipeline(&sc_desc, &device, &bind_group_layouts, &shaders, RenderLayer::Cutout),
+            generate_render_pipeline(&sc_desc, &device, &bind_group_layouts, &shaders, RenderLayer::Translucent)
+        ];
 
         //Load font
 
@@ -101,13 +105,12 @@ impl RenderState {
             sc_desc,
             swap_chain: Some(swap_chain),
             size,
-            render_pipeline,
+            render_pipelines,
             camera,
             uniforms,
             uniform_buffer,
             uniform_bind_group,
             depth_texture,
-            blocks,
             fps: 0,
             fps_counter: Instant::now(),
             frames: 0,
@@ -131,9 +134,27 @@ impl RenderState {
     }
 }
 
-fn generate_render_pipeline(sc_desc: &SwapChainDescriptor, device: &Device, bind_group_layouts: &[&BindGroupLayout]) -> RenderPipeline{
+/// Builds the pipeline for one render layer, shaders being the modules returned by load_shaders
+fn generate_render_pipeline(sc_desc: &SwapChainDescriptor, device: &Device, bind_group_layouts: &[&BindGroupLayout], shaders: &(ShaderModule, ShaderModule, ShaderModule), layer: RenderLayer) -> RenderPipeline{
+
+    let (vs_module, fs_module, cutout_fs_module) = shaders;
+
+    let fs_module = if layer == RenderLayer::Cutout { cutout_fs_module } else { fs_module };
 
-    let (vs_module, fs_module) = load_shaders(device);
+    // Translucent faces blend over what's behind them, and don't hide the translucent faces drawn after them
+    let (color_blend, alpha_blend) = if layer == RenderLayer::Translucent {
+        (wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::SrcAlpha,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        }, wgpu::BlendDescriptor {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+            operation: wgpu::BlendOperation::Add,
+        })
+    } else {
+        (wgpu::BlendDescriptor::REPLACE, wgpu::BlendDescriptor::REPLACE)
+    };
 
     let render_pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
         bind_group_layouts
@@ -146,11 +167,11 @@ fn generate_render_pipeline(sc_desc: &SwapChainDescriptor, device: &Device, bind
         ],
         layout: &render_pipeline_layout,
         vertex_stage: wgpu::ProgrammableStageDescriptor {
-            module: &vs_module,
+            module: vs_module,
             entry_point: "main",
         },
         fragment_stage: Some(wgpu::ProgrammableStageDescriptor {
-            module: &fs_module,
+            module: fs_module,
             entry_point: "main",
         }),
         rasterization_state: Some(wgpu::RasterizationStateDescriptor {
@@ -163,15 +184,15 @@ fn generate_render_pipeline(sc_desc: &SwapChainDescriptor, device: &Device, bind
         color_states: &[
             wgpu::ColorStateDescriptor {
                 format: sc_desc.format,
-                color_blend: wgpu::BlendDescriptor::REPLACE,
-                alpha_blend: wgpu::BlendDescriptor::REPLACE,
+                color_blend,
+                alpha_blend,
                 write_mask: wgpu::ColorWrite::ALL,
             },
         ],
         primitive_topology: wgpu::PrimitiveTopology::TriangleList,
         depth_stencil_state: Some(wgpu::DepthStencilStateDescriptor {
             format: DEPTH_FORMAT,
-            depth_write_enabled: true,
+            depth_write_enabled: layer != RenderLayer::Translucent,
             depth_compare: wgpu::CompareFunction::Less,
             stencil_front: wgpu::StencilStateFaceDescriptor::IGNORE,
             stencil_back: wgpu::StencilStateFaceDescriptor::IGNORE,
@@ -1,6 +1,9 @@
 use crate::render::RenderState;
 use std::time::{Instant};
 use crate::services::ui_service::UIService;
+use crate::services::chunk_service::chunk::Chunk;
+use crate::block::{RenderLayer, RENDER_LAYERS};
+use cgmath::{Point3, MetricSpace};
 
 pub mod uniforms;
 
@@ -46,19 +49,43 @@ impl RenderState {
                     }),
                 });
 
-                render_pass.set_pipeline(&self.render_pipeline);
-                render_pass.set_bind_group(0, &services.asset.atlas_bind_group.as_ref().unwrap(), &[]);
-                render_pass.set_bind_group(1, &self.uniform_bind_group, &[]);
-
-                for chunk in &services.chunk.chunks {
-                    let indices_buffer = chunk.1.indices_buffer.as_ref().unwrap();
-                    let vertices_buffer = chunk.1.vertices_buffer.as_ref().unwrap();
-                    let model_bind_group = chunk.1.model_bind_group.as_ref().unwrap();
-
-                    render_pass.set_bind_group(2, model_bind_group, &[0]);
-                    render_pass.set_vertex_buffers(0, &[(vertices_buffer, 0)]);
-                    render_pass.set_index_buffer(indices_buffer, 0);
-                    render_pass.draw_indexed(0..chunk.1.indices_buffer_len, 0, 0..1);
+                // Translucent chunks are drawn furthest first so nearer water and glass blend over them
+                let mut translucent: Vec<&Chunk> = services.chunk.chunks.values()
+                    .filter(|chunk| chunk.meshes[RenderLayer::Translucent as usize].indices_buffer.is_some())
+                    .collect();
+
+                let eye = self.camera.eye;
+                let distance = |chunk: &Chunk| {
+                    let centre = Point3::new(chunk.position.x as f32 + 0.5, chunk.position.y as f32 + 0.5, chunk.position.z as f32 + 0.5) * 16.0;
+                    centre.distance2(eye)
+                };
+                translucent.sort_by(|a, b| distance(b).partial_cmp(&distance(a)).unwrap_or(std::cmp::Ordering::Equal));
+
+                for layer in RENDER_LAYERS.iter() {
+                    render_pass.set_pipeline(&self.render_pipelines[*layer as usize]);
+                    render_pass.set_bind_group(0, services.asset.atlas_bind_group.as_ref().unwrap(), &[]);
+                    render_pass.set_bind_group(1, &self.uniform_bind_group, &[]);
+
+                    let chunks: Vec<&Chunk> = if *layer == RenderLayer::Translucent {
+                        translucent.clone()
+                    } else {
+                        services.chunk.chunks.values().collect()
+                    };
+
+                    for chunk in chunks {
+                        let mesh = &chunk.meshes[*layer as usize];
+
+                        let (vertices_buffer, indices_buffer) = match (mesh.vertices_buffer.as_ref(), mesh.indices_buffer.as_ref()) {
+                            (Some(vertices_buffer), Some(indices_buffer)) => (vertices_buffer, indices_buffer),
+                            _ => continue
+                        };
+                        let model_bind_group = chunk.model_bind_group.as_ref().unwrap();
+
+                        render_pass.set_bind_group(2, model_bind_group, &[0]);
+                        render_pass.set_vertex_buffers(0, &[(vertices_buffer, 0)]);
+                        render_pass.set_index_buffer(indices_buffer, 0);
+                        render_pass.draw_indexed(0..mesh.indices_buffer_len, 0, 0..1);
+                    }
                 }
             }
 
@@ -1,17 +1,24 @@
 use wgpu::{Device, ShaderModule};
 
-pub fn load_shaders(device: &Device) -> (ShaderModule, ShaderModule){
+/// Loads the chunk shaders, returning the vertex shader, the fragment shader and the fragment shader for cutout blocks
+pub fn load_shaders(device: &Device) -> (ShaderModule, ShaderModule, ShaderModule){
     let vs_src = include_str!("./shader.vert");
     let fs_src = include_str!("./shader.frag");
 
+    // The cutout shader is the same one with alpha testing turned on
+    let cutout_fs_src = fs_src.replacen("#version 450", "#version 450\n#define CUTOUT", 1);
+
     let vs_spirv = glsl_to_spirv::compile(vs_src, glsl_to_spirv::ShaderType::Vertex).unwrap();
     let fs_spirv = glsl_to_spirv::compile(fs_src, glsl_to_spirv::ShaderType::Fragment).unwrap();
+    let cutout_fs_spirv = glsl_to_spirv::compile(&cutout_fs_src, glsl_to_spirv::ShaderType::Fragment).unwrap();
 
     let vs_data = wgpu::read_spirv(vs_spirv).unwrap();
     let fs_data = wgpu::read_spirv(fs_spirv).unwrap();
+    let cutout_fs_data = wgpu::read_spirv(cutout_fs_spirv).unwrap();
 
     let vs_module = device.create_shader_module(&vs_data);
     let fs_module = device.create_shader_module(&fs_data);
+    let cutout_fs_module = device.create_shader_module(&cutout_fs_data);
 
-    (vs_module, fs_module)
+    (vs_module, fs_module, cutout_fs_module)
 }
\ No newline at end of file
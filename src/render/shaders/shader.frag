@@ -3,6 +3,9 @@
 
 layout(location=0) in vec2 v_tex_coords;
 layout(location=1) in vec3 normal;
+layout(location=2) flat in vec4 v_atlas;
+layout(location=3) in float v_ao;
+layout(location=4) in vec2 v_light;
 
 layout(location=0) out vec4 f_color;
 
@@ -10,7 +13,30 @@ layout(set = 0, binding = 0) uniform texture2D t_diffuse;
 layout(set = 0, binding = 1) uniform sampler s_diffuse;
 
 void main() {
-    vec4 ambient = vec4(0.5, 0.5, 0.5, 1.0);
+    // Each side of a block gets a fixed shade so edges stand out, then occluded corners are darkened
+    float face_shade = normal.y > 0.5 ? 1.0 : (normal.y < -0.5 ? 0.5 : (abs(normal.x) > 0.5 ? 0.6 : 0.8));
+    // Each light level is 80% as bright as the one above it, with a little left over so unlit caves aren't pitch black
+    float level = max(v_light.x, v_light.y);
+    float brightness = max(pow(0.8, 15.0 * (1.0 - level)), 0.03);
 
-    f_color = texture(sampler2D(t_diffuse, s_diffuse), v_tex_coords) * ambient;
+    float light = face_shade * mix(0.35, 1.0, v_ao) * brightness;
+    vec4 ambient = vec4(light, light, light, 1.0);
+
+    // Texture coordinates are in tiles so merged faces repeat the texture, wrap them into this face's part of the atlas
+    vec2 tile = fract(v_tex_coords);
+    vec2 uv = vec2(mix(v_atlas.x, v_atlas.z, tile.x), mix(v_atlas.w, v_atlas.y, tile.y));
+
+    // Pick the mip level from the unwrapped coordinates, fract jumps at tile edges and would pick the smallest mip there
+    vec2 atlas_size = v_atlas.zw - v_atlas.xy;
+    vec2 dx = dFdx(v_tex_coords) * atlas_size;
+    vec2 dy = dFdy(v_tex_coords) * atlas_size;
+
+    f_color = textureGrad(sampler2D(t_diffuse, s_diffuse), uv, dx, dy) * ambient;
+
+#ifdef CUTOUT
+    // Cutout blocks are either solid or fully see through, no blending needed
+    if (f_color.a < 0.5) {
+        discard;
+    }
+#endif
 }
\ No newline at end of file
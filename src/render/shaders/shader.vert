@@ -3,9 +3,15 @@
 layout(location=0) in vec3 a_position;
 layout(location=1) in vec2 a_tex_coords;
 layout(location=2) in vec3 normals;
+layout(location=3) in vec4 a_atlas;
+layout(location=4) in float a_ao;
+layout(location=5) in vec2 a_light;
 
 layout(location=0) out vec2 v_tex_coords;
 layout(location=1) out vec3 normal;
+layout(location=2) flat out vec4 v_atlas;
+layout(location=3) out float v_ao;
+layout(location=4) out vec2 v_light;
 
 layout(set=1, binding=0) uniform Uniforms {
     mat4 u_view_proj;
@@ -18,6 +24,9 @@ layout(set=2, binding=0) uniform ModelBindings {
 void main() {
     normal = normals;
     v_tex_coords = a_tex_coords;
+    v_atlas = a_atlas;
+    v_ao = a_ao;
+    v_light = a_light;
 
     mat4 modelviewproj = u_view_proj * model;
     gl_Position = modelviewproj * vec4(a_position, 1.0);
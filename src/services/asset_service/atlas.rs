@@ -3,7 +3,7 @@ use std::time::SystemTime;
 use wgpu::{Device, Queue, Texture, Sampler};
 use crate::services::asset_service::{ResourcePack, AssetService};
 use std::collections::HashMap;
-use crate::block::Block;
+use crate::block::registry::BlockRegistry;
 use crate::services::settings_service::SettingsService;
 use std::fs::File;
 use std::io::Write;
@@ -293,15 +293,15 @@ fn sort_textures(textures: &mut HashMap<String, DynamicImage>) -> Vec<(String, D
     out
 }
 
-pub fn atlas_update_blocks(mapping: &HashMap<String, TextureAtlasIndex>, blocks: &mut Vec<Block>) {
-    for mut block in blocks.iter_mut() {
+pub fn atlas_update_blocks(mapping: &HashMap<String, TextureAtlasIndex>, blocks: &mut BlockRegistry) {
+    for block in blocks.blocks_mut().iter_mut() {
         for (i, tex) in block.raw_texture_names.iter().enumerate() {
-            match mapping.get(*tex) {
+            match mapping.get(tex) {
                 Some(map) => {
                     block.texture_atlas_lookups[i] = map.clone();
                 }
                 None => {
-                    log_error!("No mapping found for {}", tex);
+                    log_error!(format!("No texture {} found for block {}", tex, block.identifier));
                 }
             }
         }
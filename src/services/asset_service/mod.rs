@@ -7,12 +7,14 @@ use crate::services::settings_service::SettingsService;
 use image::DynamicImage;
 use wgpu::{Texture, Sampler, BindGroupLayout, BindGroup};
 use crate::services::asset_service::atlas::TextureAtlasIndex;
+use crate::services::asset_service::models::{ModelRegistry, RawBlockModel, BlockStateDefinition};
 use crate::services::{ServicesContext};
 
 pub mod depth_map;
 pub mod binding;
 pub mod atlas;
 pub mod packs;
+pub mod models;
 
 pub struct AssetService {
     resource_packs: Vec<String>,
@@ -22,14 +24,16 @@ pub struct AssetService {
     pub atlas_sampler: Option<Sampler>,
     pub atlas_bind_group_layout: Option<BindGroupLayout>,
     pub atlas_bind_group: Option<BindGroup>,
-
+    pub models: Option<ModelRegistry>
 }
 
 pub struct ResourcePack {
     name: String,
     author: String,
     version: String,
-    textures: HashMap<String, DynamicImage>
+    textures: HashMap<String, DynamicImage>,
+    models: HashMap<String, RawBlockModel>,
+    blockstates: HashMap<String, BlockStateDefinition>
 }
 
 impl AssetService {
@@ -46,6 +50,9 @@ impl AssetService {
             None
         };
 
+        let models = selected_pack.as_ref()
+            .map(|pack| ModelRegistry::new(&pack.models, &pack.blockstates));
+
         let (atlas, atlas_index, atlas_sampler) =
             AssetService::generate_texture_atlas(selected_pack.as_mut().unwrap(), context.device, context.queue, settings);
 
@@ -58,7 +65,8 @@ impl AssetService {
             atlas_index: Some(atlas_index),
             atlas_sampler: Some(atlas_sampler),
             atlas_bind_group_layout: Some(atlas_bind_group_layout),
-            atlas_bind_group: Some(atlas_bind_group)
+            atlas_bind_group: Some(atlas_bind_group),
+            models
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,477 @@
+//
+// Parses the blockstate and block model json files inside resource packs, so blocks can be textured by the pack's models
+//
+
+use serde::Deserialize;
+use std::collections::HashMap;
+use crate::block::registry::BlockRegistry;
+use crate::block::Block;
+use crate::block::state::BlockState;
+
+/// Stop following parents after this many, packs can contain parent loops
+const MAX_PARENT_DEPTH: usize = 32;
+
+/// Face names used by models, in the same order as BlockDirection
+pub const MODEL_FACES: [&str; 6] = ["up", "north", "south", "west", "east", "down"];
+
+/// A model as it appears in the pack, before parents and texture variables are resolved
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct RawBlockModel {
+    #[serde(default)]
+    pub parent: Option<String>,
+    #[serde(default)]
+    pub textures: HashMap<String, String>,
+    #[serde(default)]
+    pub elements: Option<Vec<ModelElement>>,
+    #[serde(default)]
+    pub ambientocclusion: Option<bool>
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ModelElement {
+    /// Corners of the box in sixteenths of a block
+    pub from: [f32; 3],
+    pub to: [f32; 3],
+    #[serde(default)]
+    pub rotation: Option<ElementRotation>,
+    #[serde(default)]
+    pub faces: HashMap<String, ModelFace>
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ElementRotation {
+    pub origin: [f32; 3],
+    pub axis: String,
+    pub angle: f32,
+    #[serde(default)]
+    pub rescale: bool
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ModelFace {
+    /// A texture variable like #side until the model is resolved, then a texture name like textures/block/stone
+    pub texture: String,
+    #[serde(default)]
+    pub uv: Option<[f32; 4]>,
+    #[serde(default)]
+    pub cullface: Option<String>,
+    #[serde(default)]
+    pub rotation: u32
+}
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct BlockStateDefinition {
+    #[serde(default)]
+    pub variants: HashMap<String, Variants>,
+    #[serde(default)]
+    pub multipart: Vec<MultipartCase>
+}
+
+/// A variant is either a single model or a weighted list of them
+#[derive(Deserialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum Variants {
+    Single(ModelVariant),
+    Weighted(Vec<ModelVariant>)
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ModelVariant {
+    pub model: String,
+    #[serde(default)]
+    pub x: u32,
+    #[serde(default)]
+    pub y: u32,
+    #[serde(default)]
+    pub uvlock: bool
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct MultipartCase {
+    pub apply: Variants,
+    #[serde(default)]
+    pub when: Option<serde_json::Value>
+}
+
+/// A model with its parents merged in and every face pointing at a real texture
+#[derive(Clone, Debug)]
+pub struct BlockModel {
+    pub textures: HashMap<String, String>,
+    pub elements: Vec<ModelElement>,
+    pub ambient_occlusion: bool
+}
+
+pub struct ModelRegistry {
+    models: HashMap<String, BlockModel>,
+    blockstates: HashMap<String, BlockStateDefinition>
+}
+
+impl Variants {
+    /// The model used when weights aren't taken into account
+    pub fn first(&self) -> Option<&ModelVariant> {
+        match self {
+            Variants::Single(variant) => Some(variant),
+            Variants::Weighted(variants) => variants.first()
+        }
+    }
+}
+
+impl ModelRegistry {
+    pub fn new(raw_models: &HashMap<String, RawBlockModel>, blockstates: &HashMap<String, BlockStateDefinition>) -> ModelRegistry {
+        let mut models = HashMap::new();
+
+        for name in raw_models.keys() {
+            models.insert(name.clone(), resolve_model(name, raw_models));
+        }
+
+        log!(format!("Loaded {} block models and {} blockstates", models.len(), blockstates.len()));
+
+        ModelRegistry {
+            models,
+            blockstates: blockstates.clone()
+        }
+    }
+
+    pub fn get_model(&self, name: &str) -> Option<&BlockModel> {
+        self.models.get(&model_key(name))
+    }
+
+    pub fn get_blockstate(&self, identifier: &str) -> Option<&BlockStateDefinition> {
+        self.blockstates.get(strip_namespace(identifier))
+    }
+
+    /// Finds the variant for a block's state. Properties the pack uses but our block doesn't have are ignored.
+    pub fn variant(&self, block: &Block, state: BlockState) -> Option<&ModelVariant> {
+        let definition = self.get_blockstate(&block.identifier)?;
+
+        let mut keys: Vec<&String> = definition.variants.keys().collect();
+        keys.sort();
+
+        for key in keys {
+            let matches = key.split(',')
+                .filter(|condition| !condition.is_empty())
+                .all(|condition| {
+                    let mut parts = condition.splitn(2, '=');
+                    let property = parts.next().unwrap_or("");
+                    let value = parts.next().unwrap_or("");
+
+                    block.property_value(state, property).is_none_or(|current| current == value)
+                });
+
+            if matches {
+                return definition.variants.get(key).and_then(|variants| variants.first());
+            }
+        }
+
+        // Multipart blocks (fences, walls) have no variants, use the first part that always applies
+        definition.multipart.iter()
+            .find(|case| case.when.is_none())
+            .or(definition.multipart.first())
+            .and_then(|case| case.apply.first())
+    }
+
+    /// Replaces the hardcoded face textures of every block with the ones from the pack's models.
+    /// Blocks only have one set of textures, so this uses the default state's variant and other
+    /// variants (a log's axis, a furnace's facing) share its textures instead of getting their own.
+    pub fn apply_to_blocks(&self, blocks: &mut BlockRegistry) {
+        for block in blocks.blocks_mut().iter_mut() {
+            let variant = match self.variant(block, block.default_state()) {
+                Some(variant) => variant.clone(),
+                None => {
+                    log!("No blockstate found for {}, using its json textures", block.identifier);
+                    continue;
+                }
+            };
+
+            let model = match self.get_model(&variant.model) {
+                Some(model) => model,
+                None => {
+                    log_error!(format!("Block {} uses missing model {}", block.identifier, variant.model));
+                    continue;
+                }
+            };
+
+            for (i, texture) in model_face_textures(model, variant.y).iter().enumerate() {
+                if let Some(texture) = texture {
+                    block.raw_texture_names[i] = texture.clone();
+                }
+            }
+        }
+    }
+}
+
+/// Picks a texture for each side of the block from the model's elements, turned by the variant's y rotation
+fn model_face_textures(model: &BlockModel, y_rotation: u32) -> [Option<String>; 6] {
+    let mut out: [Option<String>; 6] = Default::default();
+
+    // Faces of the largest element win, so overlays and small details don't replace the main texture
+    let mut elements: Vec<&ModelElement> = model.elements.iter().collect();
+    elements.sort_by(|a, b| element_volume(b).partial_cmp(&element_volume(a)).unwrap_or(std::cmp::Ordering::Equal));
+
+    for element in elements {
+        for (i, face_name) in MODEL_FACES.iter().enumerate() {
+            if out[i].is_some() {
+                continue;
+            }
+
+            if let Some(face) = element.faces.get(*face_name) {
+                out[i] = Some(face.texture.clone());
+            }
+        }
+    }
+
+    // Turning a model clockwise moves its north face to the east and so on
+    let turns = ((y_rotation / 90) % 4) as usize;
+    if turns != 0 {
+        let ring = [1, 4, 2, 3];
+        let unrotated = out.clone();
+
+        for (position, face) in ring.iter().enumerate() {
+            out[ring[(position + turns) % 4]] = unrotated[*face].clone();
+        }
+    }
+
+    out
+}
+
+fn element_volume(element: &ModelElement) -> f32 {
+    (element.to[0] - element.from[0]).abs() * (element.to[1] - element.from[1]).abs() * (element.to[2] - element.from[2]).abs()
+}
+
+fn resolve_model(name: &str, raw_models: &HashMap<String, RawBlockModel>) -> BlockModel {
+    // Walk up to the root model
+    let mut chain = Vec::new();
+    let mut current = Some(model_key(name));
+
+    while let Some(key) = current {
+        if chain.len() >= MAX_PARENT_DEPTH {
+            log_error!("Model {} has too many parents, is there a loop?", name);
+            break;
+        }
+
+        match raw_models.get(&key) {
+            Some(model) => {
+                current = model.parent.as_ref().map(|parent| model_key(parent));
+                chain.push(model);
+            }
+            None => break
+        }
+    }
+
+    // Children override their parents
+    let mut textures = HashMap::new();
+    let mut elements = None;
+    let mut ambient_occlusion = None;
+
+    for model in chain.iter().rev() {
+        for (variable, texture) in model.textures.iter() {
+            textures.insert(variable.clone(), texture.clone());
+        }
+    }
+
+    for model in chain.iter() {
+        if elements.is_none() {
+            elements = model.elements.clone();
+        }
+
+        if ambient_occlusion.is_none() {
+            ambient_occlusion = model.ambientocclusion;
+        }
+    }
+
+    let mut elements = elements.unwrap_or_default();
+
+    for element in elements.iter_mut() {
+        for face in element.faces.values_mut() {
+            face.texture = resolve_texture(&face.texture, &textures);
+        }
+    }
+
+    let textures: HashMap<String, String> = textures.keys()
+        .map(|variable| (variable.clone(), resolve_texture(&format!("#{}", variable), &textures)))
+        .collect();
+
+    BlockModel {
+        textures,
+        elements,
+        ambient_occlusion: ambient_occlusion.unwrap_or(true)
+    }
+}
+
+/// Follows texture variables until a real texture is found, and converts it to the name used by the atlas
+fn resolve_texture(texture: &str, textures: &HashMap<String, String>) -> String {
+    let mut texture = texture.to_string();
+
+    for _ in 0..MAX_PARENT_DEPTH {
+        if !texture.starts_with('#') {
+            break;
+        }
+
+        texture = match textures.get(&texture[1..]) {
+            Some(next) => next.clone(),
+            None => return String::from("textures/block/missing")
+        };
+    }
+
+    // Variables that point at each other never reach a texture
+    if texture.starts_with('#') {
+        return String::from("textures/block/missing");
+    }
+
+    format!("textures/{}", strip_namespace(&texture))
+}
+
+/// Model names can be written as minecraft:block/stone, block/stone or just stone
+fn model_key(name: &str) -> String {
+    let name = strip_namespace(name);
+
+    if name.contains('/') {
+        name.to_string()
+    } else {
+        format!("block/{}", name)
+    }
+}
+
+fn strip_namespace(name: &str) -> &str {
+    match name.find(':') {
+        Some(index) => &name[index + 1..],
+        None => name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn raw_models(models: &[(&str, &str)]) -> HashMap<String, RawBlockModel> {
+        models.iter()
+            .map(|(name, json)| (name.to_string(), serde_json::from_str(json).unwrap()))
+            .collect()
+    }
+
+    fn face_texture(model: &BlockModel, face: &str) -> String {
+        model.elements[0].faces[face].texture.clone()
+    }
+
+    const CUBE: &str = r##"{
+        "elements": [{
+            "from": [0, 0, 0],
+            "to": [16, 16, 16],
+            "faces": {
+                "up": { "texture": "#up" },
+                "down": { "texture": "#down" },
+                "north": { "texture": "#north" },
+                "south": { "texture": "#south" },
+                "west": { "texture": "#west" },
+                "east": { "texture": "#east" }
+            }
+        }]
+    }"##;
+
+    const CUBE_ALL: &str = r##"{
+        "parent": "block/cube",
+        "textures": {
+            "particle": "#all",
+            "up": "#all", "down": "#all", "north": "#all",
+            "south": "#all", "west": "#all", "east": "#all"
+        }
+    }"##;
+
+    #[test]
+    fn texture_variables_follow_the_parent_chain() {
+        let raw = raw_models(&[
+            ("block/cube", CUBE),
+            ("block/cube_all", CUBE_ALL),
+            ("block/stone", r#"{ "parent": "minecraft:block/cube_all", "textures": { "all": "minecraft:block/stone" } }"#)
+        ]);
+        let registry = ModelRegistry::new(&raw, &HashMap::new());
+
+        let stone = registry.get_model("minecraft:stone").unwrap();
+        assert_eq!(stone.elements.len(), 1);
+        for face in MODEL_FACES.iter() {
+            assert_eq!(face_texture(stone, face), "textures/block/stone");
+        }
+        assert_eq!(stone.textures["particle"], "textures/block/stone");
+        assert!(stone.ambient_occlusion);
+    }
+
+    #[test]
+    fn children_override_parent_textures_and_elements() {
+        let raw = raw_models(&[
+            ("block/cube", CUBE),
+            ("block/cube_all", CUBE_ALL),
+            ("block/grass", r#"{
+                "parent": "block/cube_all",
+                "ambientocclusion": false,
+                "textures": { "all": "block/dirt", "up": "block/grass_top" }
+            }"#),
+            ("block/slab", r##"{
+                "parent": "block/grass",
+                "elements": [{ "from": [0, 0, 0], "to": [16, 8, 16], "faces": { "up": { "texture": "#up" } } }]
+            }"##)
+        ]);
+        let registry = ModelRegistry::new(&raw, &HashMap::new());
+
+        let grass = registry.get_model("block/grass").unwrap();
+        assert_eq!(face_texture(grass, "up"), "textures/block/grass_top");
+        assert_eq!(face_texture(grass, "north"), "textures/block/dirt");
+        assert!(!grass.ambient_occlusion);
+
+        let slab = registry.get_model("slab").unwrap();
+        assert_eq!(slab.elements.len(), 1);
+        assert_eq!(slab.elements[0].to, [16.0, 8.0, 16.0]);
+        assert_eq!(face_texture(slab, "up"), "textures/block/grass_top");
+        assert!(!slab.ambient_occlusion);
+    }
+
+    #[test]
+    fn unresolved_variables_and_parent_loops_fall_back() {
+        let raw = raw_models(&[
+            ("block/cube", CUBE),
+            ("block/broken", r##"{ "parent": "block/cube", "textures": { "up": "#missing", "down": "#down" } }"##),
+            ("block/a", r#"{ "parent": "block/b", "textures": { "all": "block/a" } }"#),
+            ("block/b", r#"{ "parent": "block/a" }"#)
+        ]);
+        let registry = ModelRegistry::new(&raw, &HashMap::new());
+
+        let broken = registry.get_model("broken").unwrap();
+        assert_eq!(face_texture(broken, "up"), "textures/block/missing");
+        assert_eq!(face_texture(broken, "north"), "textures/block/missing");
+        assert_eq!(face_texture(broken, "down"), "textures/block/missing");
+
+        let looped = registry.get_model("a").unwrap();
+        assert!(looped.elements.is_empty());
+        assert_eq!(looped.textures["all"], "textures/block/a");
+    }
+
+    #[test]
+    fn model_faces_turn_with_the_variant() {
+        let raw = raw_models(&[
+            ("block/furnace", r#"{
+                "elements": [{
+                    "from": [0, 0, 0],
+                    "to": [16, 16, 16],
+                    "faces": {
+                        "up": { "texture": "block/top" },
+                        "down": { "texture": "block/top" },
+                        "north": { "texture": "block/front" },
+                        "south": { "texture": "block/side" },
+                        "west": { "texture": "block/side" },
+                        "east": { "texture": "block/side" }
+                    }
+                }]
+            }"#)
+        ]);
+        let registry = ModelRegistry::new(&raw, &HashMap::new());
+        let furnace = registry.get_model("furnace").unwrap();
+
+        let unturned = model_face_textures(furnace, 0);
+        assert_eq!(unturned[1].as_deref(), Some("textures/block/front"));
+
+        // A quarter turn moves the north face to the east
+        let turned = model_face_textures(furnace, 90);
+        assert_eq!(turned[4].as_deref(), Some("textures/block/front"));
+        assert_eq!(turned[1].as_deref(), Some("textures/block/side"));
+        assert_eq!(turned[0].as_deref(), Some("textures/block/top"));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::services::asset_service::{AssetService, ResourcePack};
+use crate::services::asset_service::models::{RawBlockModel, BlockStateDefinition};
 use std::fs;
 use zip::ZipArchive;
 use std::fs::File;
@@ -36,7 +37,7 @@ impl AssetService {
 
         let mut archive = zip::ZipArchive::new(zipfile).unwrap();
 
-        let textures = load_resources(&mut archive);
+        let (textures, models, blockstates) = load_resources(&mut archive);
 
         log!(format!("Took {} seconds to load texture pack {}", Instant::now().duration_since(start_time).as_secs_f32(), path));
 
@@ -44,18 +45,55 @@ impl AssetService {
             name: "".to_string(),
             author: "".to_string(),
             version: "".to_string(),
-            textures
+            textures,
+            models,
+            blockstates
         }
     }
 }
 
-fn load_resources(archive: &mut ZipArchive<File>) -> HashMap<String, DynamicImage> {
+fn load_resources(archive: &mut ZipArchive<File>) -> (HashMap<String, DynamicImage>, HashMap<String, RawBlockModel>, HashMap<String, BlockStateDefinition>) {
     let mut out = HashMap::new();
+    let mut models = HashMap::new();
+    let mut blockstates = HashMap::new();
 
     for i in 0..archive.len() {
         let mut item = archive.by_index(i).unwrap();
 
-        if item.is_file() && item.name().ends_with(".png") {
+        if item.is_file() && item.name().ends_with(".json") {
+            let name = format_file_name(item.name());
+
+            // Only block models and blockstates are used for now
+            if !name.starts_with("models/block/") && !name.starts_with("blockstates/") {
+                continue;
+            }
+
+            let mut data = String::new();
+            if let Err(e) = item.read_to_string(&mut data) {
+                log_error!(format!("Error reading resource {} - {}", item.name(), e));
+                continue;
+            }
+
+            if name.starts_with("models/") {
+                match serde_json::from_str::<RawBlockModel>(&data) {
+                    Ok(model) => {
+                        models.insert(name.trim_start_matches("models/").to_string(), model);
+                    }
+                    Err(e) => {
+                        log_error!(format!("Error parsing model {} - {}", item.name(), e));
+                    }
+                }
+            } else {
+                match serde_json::from_str::<BlockStateDefinition>(&data) {
+                    Ok(blockstate) => {
+                        blockstates.insert(name.trim_start_matches("blockstates/").to_string(), blockstate);
+                    }
+                    Err(e) => {
+                        log_error!(format!("Error parsing blockstate {} - {}", item.name(), e));
+                    }
+                }
+            }
+        } else if item.is_file() && item.name().ends_with(".png") {
             let mut data: Vec<u8> = Vec::new();
             if let Err(e) = item.read_to_end(&mut data) {
                 println!("Error reading resource {} - {}", item.name(), e);
@@ -73,7 +111,7 @@ fn load_resources(archive: &mut ZipArchive<File>) -> HashMap<String, DynamicImag
         }
     }
 
-    out
+    (out, models, blockstates)
 }
 
 fn format_file_name(name: &str) -> String {
@@ -92,8 +130,10 @@ fn format_file_name(name: &str) -> String {
         }
     }
 
-    // Remove .png
-    out.truncate(out.len() - 4);
+    // Remove the extension
+    if let Some(extension) = out.rfind('.') {
+        out.truncate(extension);
+    }
 
     out
 }
\ No newline at end of file
@@ -1,40 +1,48 @@
-use crate::block::Block;
 use wgpu::{Buffer, BindGroup};
 use crate::services::chunk_service::mesh::Vertex;
 use crate::services::settings_service::{CHUNK_SIZE};
 use crate::services::chunk_service::mesh::culling::ViewableDirection;
 use cgmath::Vector3;
+use crate::services::chunk_service::storage::ChunkStorage;
+use crate::services::chunk_service::light::ChunkLight;
 
 pub struct Chunk {
-    pub world: RawChunkData,
-    pub blocks: Vec<Block>,
-    pub vertices: Option<Vec<Vertex>>,
-    pub indices:  Option<Vec<u16>>,
-    pub vertices_buffer: Option<Buffer>,
-    pub indices_buffer: Option<Buffer>,
-    pub indices_buffer_len: u32,
+    pub world: ChunkStorage,
+    pub light: ChunkLight,
+    /// One mesh per render layer, in RenderLayer order
+    pub meshes: [ChunkMesh; 3],
     pub model_bind_group: Option<BindGroup>,
     //TODO: Investigate if caching this is even faster
     pub viewable_map: Option<[[[ViewableDirection; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]>,
     pub position: Vector3<i32>
 }
 
+/// The vertices and gpu buffers of one render layer of a chunk
+#[derive(Default)]
+pub struct ChunkMesh {
+    pub vertices: Option<Vec<Vertex>>,
+    pub indices:  Option<Vec<u16>>,
+    pub vertices_buffer: Option<Buffer>,
+    pub indices_buffer: Option<Buffer>,
+    pub indices_buffer_len: u32
+}
+
 impl Chunk {
-    pub fn new(data: ChunkData, position: Vector3<i32>) -> Chunk {
+    pub fn new(world: ChunkStorage, position: Vector3<i32>) -> Chunk {
         Chunk {
-            world: data.0,
-            blocks: data.1,
-            vertices: None,
-            indices: None,
-            vertices_buffer: None,
-            indices_buffer: None,
-            indices_buffer_len: 0,
+            world,
+            light: ChunkLight::new(),
+            meshes: [ChunkMesh::default(), ChunkMesh::default(), ChunkMesh::default()],
             model_bind_group: None,
             viewable_map: None,
             position
         }
     }
-}
 
-pub type RawChunkData = [[[u32; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
-pub type ChunkData = (RawChunkData, Vec<Block>);
\ No newline at end of file
+    /// Vertices in the chunk's meshes across every render layer
+    pub fn vertices_count(&self) -> usize {
+        self.meshes.iter()
+            .map(|mesh| mesh.vertices.as_ref().map_or(0, |vertices| vertices.len()))
+            .sum()
+    }
+}
\ No newline at end of file
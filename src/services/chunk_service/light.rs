@@ -0,0 +1,447 @@
+//
+// Sky and block light, spread with flood fills that carry on across chunk borders
+//
+
+use crate::services::chunk_service::{ChunkService, chunk_position};
+use crate::services::chunk_service::chunk::Chunk;
+use crate::services::chunk_service::storage::CHUNK_VOLUME;
+use crate::services::settings_service::CHUNK_SIZE;
+use crate::block::registry::BlockRegistry;
+use crate::block::state::BlockState;
+use cgmath::Vector3;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+pub const MAX_LIGHT: u8 = 15;
+
+const NEIGHBOURS: [Vector3<i32>; 6] = [
+    Vector3 { x: 1, y: 0, z: 0 }, Vector3 { x: -1, y: 0, z: 0 },
+    Vector3 { x: 0, y: 1, z: 0 }, Vector3 { x: 0, y: -1, z: 0 },
+    Vector3 { x: 0, y: 0, z: 1 }, Vector3 { x: 0, y: 0, z: -1 }
+];
+
+/// Light levels for every block in a chunk, skylight in the top four bits and block light in the bottom four
+#[derive(Clone)]
+pub struct ChunkLight {
+    data: Vec<u8>
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum LightChannel {
+    Sky,
+    Block
+}
+
+impl ChunkLight {
+    pub fn new() -> ChunkLight {
+        ChunkLight {
+            data: vec![0; CHUNK_VOLUME]
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize, channel: LightChannel) -> u8 {
+        let packed = self.data[index(x, y, z)];
+
+        match channel {
+            LightChannel::Sky => packed >> 4,
+            LightChannel::Block => packed & 0xF
+        }
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, channel: LightChannel, level: u8) {
+        let packed = &mut self.data[index(x, y, z)];
+        let level = level.min(MAX_LIGHT);
+
+        *packed = match channel {
+            LightChannel::Sky => (*packed & 0x0F) | (level << 4),
+            LightChannel::Block => (*packed & 0xF0) | level
+        };
+    }
+
+    /// Both channels packed into one byte, the same way they're stored
+    pub fn packed(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.data[index(x, y, z)]
+    }
+}
+
+impl Default for ChunkLight {
+    fn default() -> ChunkLight {
+        ChunkLight::new()
+    }
+}
+
+impl ChunkService {
+    /// Lights a chunk that has just been loaded, see WorldLight::light_chunk
+    pub fn light_chunk(&mut self, position: Vector3<i32>, blocks: &BlockRegistry) -> HashSet<Vector3<i32>> {
+        WorldLight { chunks: &mut self.chunks }.light_chunk(position, blocks)
+    }
+
+    /// Relights around a block that has just changed, see WorldLight::update_light
+    pub fn update_light(&mut self, pos: Vector3<i32>, blocks: &BlockRegistry) -> HashSet<Vector3<i32>> {
+        WorldLight { chunks: &mut self.chunks }.update_light(pos, blocks)
+    }
+
+    /// Gets the light at a position in the world, None if the chunk isn't loaded
+    pub fn light_at(&self, pos: Vector3<i32>, channel: LightChannel) -> Option<u8> {
+        light_in(&self.chunks, pos, channel)
+    }
+}
+
+/// The light flood fills, which only need the loaded chunks
+pub struct WorldLight<'a> {
+    pub chunks: &'a mut HashMap<Vector3<i32>, Chunk>
+}
+
+impl WorldLight<'_> {
+    /// Lights a chunk that has just been loaded. Sunlight comes down from the chunk above, or from the open sky if there isn't one,
+    /// and light already in the chunks around it spreads in. Load columns from the top down so sunlight is blocked correctly.
+    pub fn light_chunk(&mut self, position: Vector3<i32>, blocks: &BlockRegistry) -> HashSet<Vector3<i32>> {
+        let mut changed = HashSet::new();
+        let mut sky_queue = VecDeque::new();
+        let mut block_queue = VecDeque::new();
+        let origin = position * CHUNK_SIZE as i32;
+
+        // Full sunlight reaching the bottom of the chunk above keeps falling
+        let mut incoming = [[MAX_LIGHT; CHUNK_SIZE]; CHUNK_SIZE];
+        if let Some(above) = self.chunks.get(&(position + Vector3 { x: 0, y: 1, z: 0 })) {
+            for (x, row) in incoming.iter_mut().enumerate() {
+                for (z, light) in row.iter_mut().enumerate() {
+                    *light = above.light.get(x, 0, z, LightChannel::Sky);
+                }
+            }
+        }
+
+        let chunk = match self.chunks.get_mut(&position) {
+            Some(chunk) => chunk,
+            None => return changed
+        };
+
+        chunk.light = ChunkLight::new();
+
+        for (x, row) in incoming.iter().enumerate() {
+            for (z, light) in row.iter().enumerate() {
+                if *light != MAX_LIGHT {
+                    continue;
+                }
+
+                for y in (0..CHUNK_SIZE).rev() {
+                    if blocks.light_opacity(chunk.world.get(x, y, z)) != 0 {
+                        break;
+                    }
+
+                    chunk.light.set(x, y, z, LightChannel::Sky, MAX_LIGHT);
+                    sky_queue.push_back(origin + Vector3 { x: x as i32, y: y as i32, z: z as i32 });
+                }
+            }
+        }
+
+        // Only look for light sources if the chunk contains any
+        if chunk.world.palette().iter().any(|state| blocks.light_emission(*state) > 0) {
+            for x in 0..CHUNK_SIZE {
+                for y in 0..CHUNK_SIZE {
+                    for z in 0..CHUNK_SIZE {
+                        let emission = blocks.light_emission(chunk.world.get(x, y, z));
+
+                        if emission > 0 {
+                            chunk.light.set(x, y, z, LightChannel::Block, emission);
+                            block_queue.push_back(origin + Vector3 { x: x as i32, y: y as i32, z: z as i32 });
+                        }
+                    }
+                }
+            }
+        }
+
+        // Let light in neighbouring chunks spread in through the border
+        let size = CHUNK_SIZE as i32;
+        for a in 0..size {
+            for b in 0..size {
+                let border = [
+                    Vector3 { x: -1, y: a, z: b }, Vector3 { x: size, y: a, z: b },
+                    Vector3 { x: a, y: -1, z: b }, Vector3 { x: a, y: size, z: b },
+                    Vector3 { x: a, y: b, z: -1 }, Vector3 { x: a, y: b, z: size }
+                ];
+
+                for pos in border.iter() {
+                    sky_queue.push_back(origin + *pos);
+                    block_queue.push_back(origin + *pos);
+                }
+            }
+        }
+
+        changed.insert(position);
+        self.propagate_light(sky_queue, LightChannel::Sky, blocks, &mut changed);
+        self.propagate_light(block_queue, LightChannel::Block, blocks, &mut changed);
+
+        changed
+    }
+
+    /// Relights around a block that has just changed. Returns the chunks whose light changed so they can be meshed again.
+    pub fn update_light(&mut self, pos: Vector3<i32>, blocks: &BlockRegistry) -> HashSet<Vector3<i32>> {
+        let mut changed = HashSet::new();
+
+        let state = match self.get_block(pos) {
+            Some(state) => state,
+            None => return changed
+        };
+
+        for channel in [LightChannel::Sky, LightChannel::Block].iter() {
+            let mut queue = self.remove_light(pos, *channel, blocks, &mut changed);
+
+            // Light around the block can flow back in if it's now transparent
+            for offset in NEIGHBOURS.iter() {
+                queue.push_back(pos + *offset);
+            }
+
+            let emission = blocks.light_emission(state);
+            if *channel == LightChannel::Block && emission > 0 {
+                self.set_light_at(pos, *channel, emission, &mut changed);
+                queue.push_back(pos);
+            }
+
+            self.propagate_light(queue, *channel, blocks, &mut changed);
+        }
+
+        changed
+    }
+
+    fn light_at(&self, pos: Vector3<i32>, channel: LightChannel) -> Option<u8> {
+        light_in(self.chunks, pos, channel)
+    }
+
+    fn get_block(&self, pos: Vector3<i32>) -> Option<BlockState> {
+        let (chunk, local) = chunk_position(pos);
+
+        self.chunks.get(&chunk).map(|chunk| chunk.world.get(local[0], local[1], local[2]))
+    }
+
+    fn set_light_at(&mut self, pos: Vector3<i32>, channel: LightChannel, level: u8, changed: &mut HashSet<Vector3<i32>>) {
+        let (chunk_pos, local) = chunk_position(pos);
+
+        if let Some(chunk) = self.chunks.get_mut(&chunk_pos) {
+            chunk.light.set(local[0], local[1], local[2], channel, level);
+
+            // Faces in the next chunk over are lit by this block too
+            changed.insert(chunk_pos);
+            for offset in NEIGHBOURS.iter() {
+                changed.insert(chunk_position(pos + *offset).0);
+            }
+        }
+    }
+
+    /// Spreads light outwards from every position in the queue, each step losing at least one level
+    fn propagate_light(&mut self, mut queue: VecDeque<Vector3<i32>>, channel: LightChannel, blocks: &BlockRegistry, changed: &mut HashSet<Vector3<i32>>) {
+        while let Some(pos) = queue.pop_front() {
+            let level = match self.light_at(pos, channel) {
+                Some(level) => level,
+                None => continue
+            };
+
+            if level <= 1 {
+                continue;
+            }
+
+            for offset in NEIGHBOURS.iter() {
+                let next = pos + *offset;
+
+                let state = match self.get_block(next) {
+                    Some(state) => state,
+                    None => continue
+                };
+
+                let opacity = blocks.light_opacity(state);
+
+                let new_level = if falls_unchanged(channel, level, offset, opacity) {
+                    MAX_LIGHT
+                } else {
+                    level.saturating_sub(opacity.max(1))
+                };
+
+                if new_level > self.light_at(next, channel).unwrap_or(MAX_LIGHT) {
+                    self.set_light_at(next, channel, new_level, changed);
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+
+    /// Darkens everything that was lit through a position. Returns the lit positions around the dark area,
+    /// which need to spread their light back in.
+    fn remove_light(&mut self, start: Vector3<i32>, channel: LightChannel, blocks: &BlockRegistry, changed: &mut HashSet<Vector3<i32>>) -> VecDeque<Vector3<i32>> {
+        let mut removal = VecDeque::new();
+        let mut refill = VecDeque::new();
+
+        let level = self.light_at(start, channel).unwrap_or(0);
+        self.set_light_at(start, channel, 0, changed);
+        removal.push_back((start, level));
+
+        while let Some((pos, level)) = removal.pop_front() {
+            for offset in NEIGHBOURS.iter() {
+                let next = pos + *offset;
+
+                let next_level = match self.light_at(next, channel) {
+                    Some(next_level) => next_level,
+                    None => continue
+                };
+
+                if next_level == 0 {
+                    continue;
+                }
+
+                let opacity = self.get_block(next).map_or(0, |state| blocks.light_opacity(state));
+
+                if next_level < level || (next_level == MAX_LIGHT && falls_unchanged(channel, level, offset, opacity)) {
+                    self.set_light_at(next, channel, 0, changed);
+                    removal.push_back((next, next_level));
+                } else {
+                    refill.push_back(next);
+                }
+            }
+        }
+
+        refill
+    }
+}
+
+/// Full sunlight travels straight down through clear blocks without getting any dimmer
+fn falls_unchanged(channel: LightChannel, level: u8, offset: &Vector3<i32>, opacity: u8) -> bool {
+    channel == LightChannel::Sky && level == MAX_LIGHT && offset.y == -1 && opacity == 0
+}
+
+fn light_in(chunks: &HashMap<Vector3<i32>, Chunk>, pos: Vector3<i32>, channel: LightChannel) -> Option<u8> {
+    let (chunk, local) = chunk_position(pos);
+
+    chunks.get(&chunk).map(|chunk| chunk.light.get(local[0], local[1], local[2], channel))
+}
+
+fn index(x: usize, y: usize, z: usize) -> usize {
+    (x * CHUNK_SIZE + y) * CHUNK_SIZE + z
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::chunk_service::storage::ChunkStorage;
+
+    fn world(positions: &[Vector3<i32>]) -> HashMap<Vector3<i32>, Chunk> {
+        positions.iter()
+            .map(|position| (*position, Chunk::new(ChunkStorage::empty(), *position)))
+            .collect()
+    }
+
+    fn set_block(chunks: &mut HashMap<Vector3<i32>, Chunk>, pos: Vector3<i32>, state: BlockState) {
+        let (chunk, local) = chunk_position(pos);
+        chunks.get_mut(&chunk).unwrap().world.set(local[0], local[1], local[2], state);
+    }
+
+    fn light(chunks: &HashMap<Vector3<i32>, Chunk>, x: i32, y: i32, z: i32, channel: LightChannel) -> u8 {
+        light_in(chunks, Vector3 { x, y, z }, channel).unwrap()
+    }
+
+    /// Every light level of the chunks, in the order given
+    fn snapshot(chunks: &HashMap<Vector3<i32>, Chunk>, positions: &[Vector3<i32>]) -> Vec<u8> {
+        let mut levels = Vec::new();
+
+        for position in positions {
+            let chunk = &chunks[position];
+            for x in 0..CHUNK_SIZE {
+                for y in 0..CHUNK_SIZE {
+                    for z in 0..CHUNK_SIZE {
+                        levels.push(chunk.light.packed(x, y, z));
+                    }
+                }
+            }
+        }
+
+        levels
+    }
+
+    #[test]
+    fn sunlight_falls_straight_down_at_full_strength() {
+        let blocks = BlockRegistry::built_in();
+        let stone = blocks.default_state("minecraft:stone").unwrap();
+        let position = Vector3 { x: 0, y: 0, z: 0 };
+        let mut chunks = world(&[position]);
+
+        // A roof over the middle of the chunk and a floor under all of it
+        for x in 0..CHUNK_SIZE as i32 {
+            for z in 0..CHUNK_SIZE as i32 {
+                set_block(&mut chunks, Vector3 { x, y: 0, z }, stone);
+            }
+        }
+        set_block(&mut chunks, Vector3 { x: 8, y: 10, z: 8 }, stone);
+
+        WorldLight { chunks: &mut chunks }.light_chunk(position, &blocks);
+
+        for y in 1..CHUNK_SIZE as i32 {
+            assert_eq!(light(&chunks, 3, y, 3, LightChannel::Sky), MAX_LIGHT);
+        }
+        assert_eq!(light(&chunks, 3, 0, 3, LightChannel::Sky), 0);
+        assert_eq!(light(&chunks, 8, 11, 8, LightChannel::Sky), MAX_LIGHT);
+
+        // Under the roof the light comes in from the side
+        for y in 1..10 {
+            assert_eq!(light(&chunks, 8, y, 8, LightChannel::Sky), MAX_LIGHT - 1);
+        }
+    }
+
+    #[test]
+    fn block_light_fades_by_one_per_block_across_chunks() {
+        let blocks = BlockRegistry::built_in();
+        let glowstone = blocks.default_state("minecraft:glowstone").unwrap();
+        let positions = [Vector3 { x: 0, y: 0, z: 0 }, Vector3 { x: 1, y: 0, z: 0 }];
+        let mut chunks = world(&positions);
+
+        set_block(&mut chunks, Vector3 { x: 8, y: 8, z: 8 }, glowstone);
+
+        let mut world = WorldLight { chunks: &mut chunks };
+        world.light_chunk(positions[0], &blocks);
+        world.light_chunk(positions[1], &blocks);
+
+        assert_eq!(light(&chunks, 8, 8, 8, LightChannel::Block), 15);
+        for distance in 1..15 {
+            assert_eq!(light(&chunks, 8 + distance, 8, 8, LightChannel::Block), 15 - distance as u8);
+        }
+        assert_eq!(light(&chunks, 23, 8, 8, LightChannel::Block), 0);
+        assert_eq!(light(&chunks, 10, 10, 10, LightChannel::Block), 9);
+    }
+
+    #[test]
+    fn removing_and_restoring_a_block_restores_the_light() {
+        let blocks = BlockRegistry::built_in();
+        let stone = blocks.default_state("minecraft:stone").unwrap();
+        let glowstone = blocks.default_state("minecraft:glowstone").unwrap();
+        let positions = [Vector3 { x: 0, y: 0, z: 0 }, Vector3 { x: 1, y: 0, z: 0 }];
+        let mut chunks = world(&positions);
+
+        let lamp = Vector3 { x: 15, y: 8, z: 8 };
+        set_block(&mut chunks, lamp, glowstone);
+        for z in 0..CHUNK_SIZE as i32 {
+            set_block(&mut chunks, Vector3 { x: 18, y: 12, z }, stone);
+        }
+
+        let mut world = WorldLight { chunks: &mut chunks };
+        world.light_chunk(positions[0], &blocks);
+        world.light_chunk(positions[1], &blocks);
+        let original = snapshot(world.chunks, &positions);
+
+        // A block on the other side of the border, right next to the lamp and under open sky
+        let wall = Vector3 { x: 16, y: 8, z: 8 };
+        set_block(world.chunks, wall, stone);
+        world.update_light(wall, &blocks);
+        // The lamp now has to light the block behind the wall the long way round
+        assert_eq!(light(world.chunks, 17, 8, 8, LightChannel::Block), 11);
+        assert_eq!(light(world.chunks, 16, 7, 8, LightChannel::Sky), MAX_LIGHT - 1);
+
+        set_block(world.chunks, wall, BlockState::AIR);
+        world.update_light(wall, &blocks);
+        assert!(snapshot(world.chunks, &positions) == original);
+
+        // Taking the lamp away darkens both chunks, putting it back lights them the same way again
+        set_block(world.chunks, lamp, BlockState::AIR);
+        world.update_light(lamp, &blocks);
+        assert_eq!(light(world.chunks, 16, 8, 8, LightChannel::Block), 0);
+
+        set_block(world.chunks, lamp, glowstone);
+        world.update_light(lamp, &blocks);
+        assert!(snapshot(world.chunks, &positions) == original);
+    }
+}
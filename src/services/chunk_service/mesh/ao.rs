@@ -0,0 +1,48 @@
+//
+// Classic voxel ambient occlusion, corners tucked against other blocks are drawn darker
+//
+
+use crate::block::BlockDirection;
+use crate::block::registry::BlockRegistry;
+use crate::services::chunk_service::mesh::block::face_axes;
+use crate::services::chunk_service::mesh::neighbourhood::ChunkNeighbourhood;
+
+/// A corner with nothing around it
+pub const NO_OCCLUSION: [u8; 4] = [3; 4];
+
+/// Occlusion for the four corners of a block face, in the corner order push_quad uses.
+/// 0 is fully dark and 3 is fully lit, worked out from the two blocks beside the corner and the one diagonal to it.
+pub fn face_ao(neighbourhood: &ChunkNeighbourhood, blocks: &BlockRegistry, pos: [i32; 3], direction: BlockDirection) -> [u8; 4] {
+    let (axis, a_axis, b_axis, _, on_max) = face_axes(direction);
+
+    // The layer of blocks the face looks out into
+    let mut front = pos;
+    front[axis] += if on_max { 1 } else { -1 };
+
+    let solid = |a: i32, b: i32| {
+        let mut pos = front;
+        pos[a_axis] += a;
+        pos[b_axis] += b;
+
+        blocks.is_full_cube(neighbourhood.get(pos[0], pos[1], pos[2])) as u8
+    };
+
+    let mut ao = [0; 4];
+
+    for (corner, value) in ao.iter_mut().enumerate() {
+        let a = if corner & 1 == 1 { 1 } else { -1 };
+        let b = if corner & 2 == 2 { 1 } else { -1 };
+
+        let side_a = solid(a, 0);
+        let side_b = solid(0, b);
+
+        // Two sides are enough to hide the corner completely
+        *value = if side_a == 1 && side_b == 1 {
+            0
+        } else {
+            3 - side_a - side_b - solid(a, b)
+        };
+    }
+
+    ao
+}
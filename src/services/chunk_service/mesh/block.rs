@@ -1,215 +1,185 @@
-use crate::services::asset_service::atlas::TextureAtlasIndex;
-use crate::block::Block;
+use crate::block::{Block, BlockDirection};
+use crate::block::shape::{BlockShape, BlockBox};
+use crate::block::state::{BlockState, BlockFace};
 use crate::services::chunk_service::mesh::culling::ViewableDirection;
-use crate::services::chunk_service::mesh::{ViewableDirectionBitMap, Vertex};
-use cgmath::{Point3, Vector3};
+use crate::services::chunk_service::mesh::{ViewableDirectionBitMap, Vertex, MeshBuilder, BLOCK_Y_OFFSET};
+use crate::services::chunk_service::mesh::ao::NO_OCCLUSION;
+use crate::services::asset_service::atlas::TextureAtlasIndex;
+use cgmath::{Point3, Vector3, InnerSpace};
+
+pub const DIRECTIONS: [BlockDirection; 6] = [BlockDirection::Up, BlockDirection::Front, BlockDirection::Back, BlockDirection::Left, BlockDirection::Right, BlockDirection::Bottom];
+
+/// Occlusion and light around a block, used to shade the faces drawn for it
+pub struct BlockShading {
+    /// Occlusion of each face of the block's cell, in BlockDirection order
+    pub ao: [[u8; 4]; 6],
+    /// Packed light of the block in front of each face, in BlockDirection order
+    pub light: [u8; 6],
+    /// Packed light of the block's own cell, for faces that aren't on its edge
+    pub inside_light: u8
+}
 
-pub fn draw_block(point: Point3<f32>, viewable: ViewableDirection, vertices: &mut Vec<Vertex>, indices: &mut Vec<u16>, block: &Block) {
-    if viewable.has_flag(ViewableDirectionBitMap::Top) {
-        draw_y_face(point.x, point.y - 1.0, point.z, vertices, indices, true, block.texture_atlas_lookups[0]);
-    }
+/// Draws a block that isn't a full cube
+pub fn draw_block(point: Point3<f32>, viewable: ViewableDirection, mesh: &mut MeshBuilder, block: &Block, state: BlockState, shading: &BlockShading) {
+    let faces = block.faces(state);
 
-    if viewable.has_flag(ViewableDirectionBitMap::Bottom) {
-        draw_y_face(point.x, point.y - 2.0, point.z, vertices, indices, false, block.texture_atlas_lookups[5]);
+    if let BlockShape::Cross = block.shape {
+        draw_cross(point, mesh, faces[BlockDirection::Front as usize], shading.inside_light);
+        return;
     }
 
-    if viewable.has_flag(ViewableDirectionBitMap::Front) {
-        draw_x_face(point.x, point.y - 2.0, point.z, vertices, indices, true, block.texture_atlas_lookups[1]);
+    for block_box in block.boxes(state).iter() {
+        draw_box(point, block_box, viewable, mesh, &faces, shading);
     }
+}
 
-    if viewable.has_flag(ViewableDirectionBitMap::Back) {
-        draw_x_face(point.x, point.y - 2.0, point.z + 1.0, vertices, indices, false, block.texture_atlas_lookups[3]);
+/// Draws the faces of one box of a block. Faces on the edge of the block are culled like a normal cube,
+/// faces inside the block (the top of a bottom slab) are always drawn.
+pub fn draw_box(point: Point3<f32>, block_box: &BlockBox, viewable: ViewableDirection, mesh: &mut MeshBuilder, faces: &[BlockFace; 6], shading: &BlockShading) {
+    let from = [block_box.from[0] / 16.0, block_box.from[1] / 16.0, block_box.from[2] / 16.0];
+    let to = [block_box.to[0] / 16.0, block_box.to[1] / 16.0, block_box.to[2] / 16.0];
+
+    for direction in DIRECTIONS.iter() {
+        if block_box.touches_edge(*direction) && !viewable.has_flag(viewable_flag(*direction)) {
+            continue;
+        }
+
+        let (axis, a_axis, b_axis, normal, on_max) = face_axes(*direction);
+
+        let plane = if on_max { to[axis] } else { from[axis] };
+
+        // Custom uvs are in sixteenths with v going down the texture, otherwise the face shows the part of the texture it covers
+        let (a_range, b_range) = match block_box.uvs[*direction as usize] {
+            Some(uv) => ([uv[0] / 16.0, uv[2] / 16.0], [1.0 - uv[3] / 16.0, 1.0 - uv[1] / 16.0]),
+            None => ([from[a_axis], to[a_axis]], [from[b_axis], to[b_axis]])
+        };
+
+        let face = &faces[*direction as usize];
+        let mut positions = [[0.0; 3]; 4];
+        let mut tex_coords = [[0.0; 2]; 4];
+
+        // Corners in the order (min, min), (max, min), (min, max), (max, max)
+        for corner in 0..4 {
+            let a_max = corner & 1 == 1;
+            let b_max = corner & 2 == 2;
+
+            let mut position = [point.x, point.y, point.z];
+            position[axis] += plane;
+            position[a_axis] += if a_max { to[a_axis] } else { from[a_axis] };
+            position[b_axis] += if b_max { to[b_axis] } else { from[b_axis] };
+
+            positions[corner] = position;
+            tex_coords[corner] = tex_coord(face, a_range[a_max as usize], b_range[b_max as usize]);
+        }
+
+        // Only faces on the edge of the block sit against the neighbours the shading was worked out from
+        let (face_ao, face_light) = if block_box.touches_edge(*direction) {
+            (shading.ao[*direction as usize], shading.light[*direction as usize])
+        } else {
+            (NO_OCCLUSION, shading.inside_light)
+        };
+
+        push_quad(positions, tex_coords, face.texture, normal, face_ao, face_light, mesh);
     }
+}
 
-    if viewable.has_flag(ViewableDirectionBitMap::Left) {
-        draw_z_face(point.x, point.y - 2.0, point.z, vertices, indices, true, block.texture_atlas_lookups[2]);
+/// Draws two quads crossing diagonally through the block, from both sides so they can be seen from anywhere
+pub fn draw_cross(point: Point3<f32>, mesh: &mut MeshBuilder, face: BlockFace, light: u8) {
+    let diagonals = [([0.0, 0.0], [1.0, 1.0]), ([0.0, 1.0], [1.0, 0.0])];
+
+    for (start, end) in diagonals.iter() {
+        let positions = [
+            [point.x + start[0], point.y, point.z + start[1]],
+            [point.x + end[0], point.y, point.z + end[1]],
+            [point.x + start[0], point.y + 1.0, point.z + start[1]],
+            [point.x + end[0], point.y + 1.0, point.z + end[1]]
+        ];
+
+        let tex_coords = [
+            tex_coord(&face, 0.0, 0.0),
+            tex_coord(&face, 1.0, 0.0),
+            tex_coord(&face, 0.0, 1.0),
+            tex_coord(&face, 1.0, 1.0)
+        ];
+
+        let normal = Vector3::new(end[1] - start[1], 0.0, start[0] - end[0]).normalize();
+
+        push_quad(positions, tex_coords, face.texture, normal.into(), NO_OCCLUSION, light, mesh);
+        push_quad(positions, tex_coords, face.texture, (-normal).into(), NO_OCCLUSION, light, mesh);
     }
+}
 
-    if viewable.has_flag(ViewableDirectionBitMap::Right) {
-        draw_z_face(point.x + 1.0, point.y - 2.0, point.z as f32, vertices, indices, false, block.texture_atlas_lookups[4]);
+/// Adds a quad with corners in the order (min, min), (max, min), (min, max), (max, max),
+/// wound so that it faces along the normal. Texture coordinates are in tiles, the texture repeats across them.
+/// Light is packed the same way as ChunkLight.
+pub fn push_quad(positions: [[f32; 3]; 4], tex_coords: [[f32; 2]; 4], texture: TextureAtlasIndex, normals: [f32; 3], ao: [u8; 4], light: u8, mesh: &mut MeshBuilder) {
+    let starting_vertices = mesh.vertices.len() as u16;
+    let (start_atlas, end_atlas) = texture;
+
+    for corner in 0..4 {
+        mesh.vertices.push(Vertex {
+            position: [positions[corner][0], positions[corner][1] + BLOCK_Y_OFFSET, positions[corner][2]],
+            tex_coords: tex_coords[corner],
+            normals,
+            atlas: [start_atlas[0], start_atlas[1], end_atlas[0], end_atlas[1]],
+            ao: ao[corner] as f32 / 3.0,
+            light: [(light >> 4) as f32 / 15.0, (light & 0xF) as f32 / 15.0],
+        });
     }
-}
 
-pub fn draw_y_face(x: f32, y: f32, z: f32, vertices: &mut Vec<Vertex>, indices: &mut Vec<u16>, top: bool, atlas: TextureAtlasIndex) {
-    let (start_atlas, end_atlas) = atlas;
-    let normals = if top { [0.0, 1.0, 0.0] } else { [0.0, -1.0, 0.0] };
-
-    let starting_vertices = vertices.len() as u16;
-
-    vertices.push(Vertex {
-        position: [x, y, z],
-        tex_coords: [start_atlas[0], end_atlas[1]],
-        normals,
-    });
-    vertices.push(Vertex {
-        position: [x + 1.0, y, z],
-        tex_coords: end_atlas,
-        normals,
-    });
-    vertices.push(Vertex {
-        position: [x, y, z + 1.0],
-        tex_coords: start_atlas,
-        normals,
-    });
-    vertices.push(Vertex {
-        position: [x + 1.0, y, z + 1.0],
-        tex_coords: [ end_atlas[0], start_atlas[1]],
-        normals,
-    });
-
-    if top {
-        indices.push(starting_vertices + 0);
-        indices.push(starting_vertices + 1);
-        indices.push(starting_vertices + 3);
-
-        indices.push(starting_vertices + 0);
-        indices.push(starting_vertices + 3);
-        indices.push(starting_vertices + 2);
-    } else {
-        indices.push(starting_vertices + 0);
-        indices.push(starting_vertices + 3);
-        indices.push(starting_vertices + 1);
-
-        indices.push(starting_vertices + 0);
-        indices.push(starting_vertices + 2);
-        indices.push(starting_vertices + 3);
+    let corner = |i: usize| Vector3::new(positions[i][0], positions[i][1], positions[i][2]);
+    let facing = (corner(1) - corner(0)).cross(corner(3) - corner(0)).dot(Vector3::from(normals));
+
+    // Split the quad along the diagonal that keeps a dark corner inside one triangle,
+    // otherwise the occlusion gets smeared differently depending on which way the quad faces
+    let flip = (ao[0] as u16 + ao[3] as u16) < (ao[1] as u16 + ao[2] as u16);
+
+    let triangles: [u16; 6] = match (facing < 0.0, flip) {
+        (true, false) => [0, 1, 3, 0, 3, 2],
+        (true, true) => [0, 1, 2, 1, 3, 2],
+        (false, false) => [0, 3, 1, 0, 2, 3],
+        (false, true) => [0, 2, 1, 1, 2, 3]
+    };
+
+    for index in triangles.iter() {
+        mesh.indices.push(starting_vertices + index);
     }
 }
 
-pub fn draw_x_face(x: f32, y: f32, z: f32, vertices: &mut Vec<Vertex>, indices: &mut Vec<u16>, forwards: bool, atlas: TextureAtlasIndex) {
-    let (start_atlas, end_atlas) = atlas;
-    let normals = if forwards { [1.0, 0.0, 0.0] } else { [-1.0, 0.0, 0.0] };
-
-    let starting_vertices = vertices.len() as u16;
-
-    vertices.push(Vertex {
-        position: [x, y, z],
-        tex_coords: [start_atlas[0], end_atlas[1]],
-        normals,
-    });
-    vertices.push(Vertex {
-        position: [x + 1.0, y, z],
-        tex_coords: end_atlas,
-        normals,
-    });
-    vertices.push(Vertex {
-        position: [x, y + 1.0, z],
-        tex_coords: start_atlas,
-        normals,
-    });
-    vertices.push(Vertex {
-        position: [x + 1.0, y + 1.0, z],
-        tex_coords: [ end_atlas[0], start_atlas[1]],
-        normals,
-    });
-
-    if forwards {
-        indices.push(starting_vertices + 0);
-        indices.push(starting_vertices + 1);
-        indices.push(starting_vertices + 3);
-
-        indices.push(starting_vertices + 0);
-        indices.push(starting_vertices + 3);
-        indices.push(starting_vertices + 2);
-    } else {
-        indices.push(starting_vertices + 0);
-        indices.push(starting_vertices + 3);
-        indices.push(starting_vertices + 1);
-
-        indices.push(starting_vertices + 0);
-        indices.push(starting_vertices + 2);
-        indices.push(starting_vertices + 3);
+/// The axis a face sits on, the two axes across it in the order tex_coord expects, its normal and
+/// whether it's on the far side of the block
+pub fn face_axes(direction: BlockDirection) -> (usize, usize, usize, [f32; 3], bool) {
+    match direction {
+        BlockDirection::Up => (1, 0, 2, [0.0, 1.0, 0.0], true),
+        BlockDirection::Bottom => (1, 0, 2, [0.0, -1.0, 0.0], false),
+        BlockDirection::Front => (2, 0, 1, [0.0, 0.0, -1.0], false),
+        BlockDirection::Back => (2, 0, 1, [0.0, 0.0, 1.0], true),
+        BlockDirection::Left => (0, 2, 1, [-1.0, 0.0, 0.0], false),
+        BlockDirection::Right => (0, 2, 1, [1.0, 0.0, 0.0], true)
     }
 }
 
-pub fn draw_z_face(x: f32, y: f32, z: f32, vertices: &mut Vec<Vertex>, indices: &mut Vec<u16>, left: bool, atlas: TextureAtlasIndex) {
-    let (start_atlas, end_atlas) = atlas;
-    let normals = if left { [0.0, 0.0, 1.0] } else { [0.0, 0.0, -1.0] };
-
-    let starting_vertices = vertices.len() as u16;
-
-    vertices.push(Vertex {
-        position: [x, y, z],
-        tex_coords: [start_atlas[0], end_atlas[1]],
-        normals,
-    });
-    vertices.push(Vertex {
-        position: [x, y + 1.0, z],
-        tex_coords: start_atlas,
-        normals,
-    });
-    vertices.push(Vertex {
-        position: [x, y, z + 1.0],
-        tex_coords: end_atlas,
-        normals,
-    });
-    vertices.push(Vertex {
-        position: [x, y + 1.0, z + 1.0],
-        tex_coords: [ end_atlas[0], start_atlas[1]],
-        normals,
-    });
-
-    if left {
-        indices.push(starting_vertices + 0);
-        indices.push(starting_vertices + 1);
-        indices.push(starting_vertices + 3);
-
-        indices.push(starting_vertices + 0);
-        indices.push(starting_vertices + 3);
-        indices.push(starting_vertices + 2);
-    } else {
-        indices.push(starting_vertices + 0);
-        indices.push(starting_vertices + 3);
-        indices.push(starting_vertices + 1);
-
-        indices.push(starting_vertices + 0);
-        indices.push(starting_vertices + 2);
-        indices.push(starting_vertices + 3);
+pub fn viewable_flag(direction: BlockDirection) -> ViewableDirectionBitMap {
+    match direction {
+        BlockDirection::Up => ViewableDirectionBitMap::Top,
+        BlockDirection::Bottom => ViewableDirectionBitMap::Bottom,
+        BlockDirection::Front => ViewableDirectionBitMap::Front,
+        BlockDirection::Back => ViewableDirectionBitMap::Back,
+        BlockDirection::Left => ViewableDirectionBitMap::Left,
+        BlockDirection::Right => ViewableDirectionBitMap::Right
     }
 }
 
-pub fn draw_vertical_face(start_position: Point3<f32>, size: Vector3<f32>, vertices: &mut Vec<Vertex>, indices: &mut Vec<u16>, top: bool, atlas: TextureAtlasIndex) {
-    let (start_atlas, end_atlas) = atlas;
-    let normals = if top { [0.0, 1.0, 0.0] } else { [0.0, -1.0, 0.0] };
-
-    let starting_vertices = vertices.len() as u16;
-
-    vertices.push(Vertex {
-        position: [start_position.x, start_position.y, start_position.z],
-        tex_coords: [start_atlas[0], end_atlas[1]],
-        normals,
-    });
-    vertices.push(Vertex {
-        position: [start_position.x + size.x, start_position.y, start_position.z],
-        tex_coords: end_atlas,
-        normals,
-    });
-    vertices.push(Vertex {
-        position: [start_position.x, start_position.y, start_position.z + size.z],
-        tex_coords: start_atlas,
-        normals,
-    });
-    vertices.push(Vertex {
-        position: [start_position.x + size.x, start_position.y, start_position.z + size.z],
-        tex_coords: [ end_atlas[0], start_atlas[1]],
-        normals,
-    });
-
-    if top {
-        indices.push(starting_vertices + 0);
-        indices.push(starting_vertices + 1);
-        indices.push(starting_vertices + 3);
-
-        indices.push(starting_vertices + 0);
-        indices.push(starting_vertices + 3);
-        indices.push(starting_vertices + 2);
-    } else {
-        indices.push(starting_vertices + 0);
-        indices.push(starting_vertices + 3);
-        indices.push(starting_vertices + 1);
-
-        indices.push(starting_vertices + 0);
-        indices.push(starting_vertices + 2);
-        indices.push(starting_vertices + 3);
+/// Texture coordinate of a face corner in tiles, where a and b are how far across the face the corner is.
+/// The shader wraps these into the face's texture in the atlas, so a quad several blocks wide repeats its texture.
+pub fn tex_coord(face: &BlockFace, a: f32, b: f32) -> [f32; 2] {
+    let (mut a, mut b) = (a, b);
+
+    for _ in 0..(face.rotation % 4) {
+        let turned = (b, 1.0 - a);
+        a = turned.0;
+        b = turned.1;
     }
-}
\ No newline at end of file
+
+    [a, b]
+}
@@ -3,35 +3,50 @@ use cgmath::{Matrix4, Vector3};
 use crate::services::chunk_service::mesh::culling::{calculate_viewable, ViewableDirection};
 use crate::services::chunk_service::chunk::Chunk;
 use crate::services::settings_service::{CHUNK_SIZE};
-use std::collections::HashMap;
+use crate::services::chunk_service::mesh::neighbourhood::ChunkNeighbourhood;
 use crate::block::Block;
-use crate::services::chunk_service::mesh::{ViewableDirectionBitMap, Vertex};
+use crate::block::registry::BlockRegistry;
+use crate::services::chunk_service::mesh::MeshBuilder;
 
 pub struct ChunkMeshData {
     pub viewable: [[[ViewableDirection; 16]; 16]; 16],
-    pub vertices: Vec<Vertex>,
-    pub indices: Vec<u16>
+    /// One mesh per render layer, in RenderLayer order
+    pub layers: [MeshBuilder; 3]
+}
+
+impl ChunkMeshData {
+    pub fn vertices_count(&self) -> usize {
+        self.layers.iter().map(|layer| layer.vertices.len()).sum()
+    }
 }
 
 impl<'a> Chunk {
 
     pub fn create_buffers(&mut self, device: &Device, bind_group_layout: &BindGroupLayout) {
-        let vertices = self.vertices.as_ref().unwrap();
-
-        let vertex_buffer = device
-            .create_buffer_mapped(vertices.len(), wgpu::BufferUsage::VERTEX)
-            .fill_from_slice(vertices.as_slice());
+        for mesh in self.meshes.iter_mut() {
+            let vertices = mesh.vertices.as_ref().unwrap();
+            let indices = mesh.indices.take().unwrap();
+            mesh.indices_buffer_len = indices.len() as u32;
+
+            // Most chunks have nothing in some layers, don't make empty buffers for them
+            if indices.is_empty() {
+                mesh.vertices_buffer = None;
+                mesh.indices_buffer = None;
+                continue;
+            }
 
-        self.vertices_buffer = Some(vertex_buffer);
+            let vertex_buffer = device
+                .create_buffer_mapped(vertices.len(), wgpu::BufferUsage::VERTEX)
+                .fill_from_slice(vertices.as_slice());
 
-        let indices = self.indices.take().unwrap();
-        self.indices_buffer_len = indices.len() as u32;
+            mesh.vertices_buffer = Some(vertex_buffer);
 
-        let indices_buffer = device
-            .create_buffer_mapped(indices.len(), wgpu::BufferUsage::INDEX)
-            .fill_from_slice(indices.as_slice());
+            let indices_buffer = device
+                .create_buffer_mapped(indices.len(), wgpu::BufferUsage::INDEX)
+                .fill_from_slice(indices.as_slice());
 
-        self.indices_buffer = Some(indices_buffer);
+            mesh.indices_buffer = Some(indices_buffer);
+        }
 
         // Create model buffer
         let model: [[f32; 4]; 4] = Matrix4::from_translation(Vector3 {
@@ -60,78 +75,32 @@ impl<'a> Chunk {
         self.model_bind_group = Some(model_bind_group);
     }
 
-    pub fn generate_viewable_map(&self, adjacent_chunks: HashMap<Vector3<i32>, Option<&Chunk>>) -> [[[ViewableDirection; 16]; 16]; 16] {
-
-        let mut data = [[[ViewableDirection(0); CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
-        let world = self.world;
-
-        let directions: [Vector3<i32>; 6] = [Vector3 { x: 1, y: 0, z: 0 }, Vector3 { x: -1, y: 0, z: 0 }, Vector3 { x: 0, y: 1, z: 0 }, Vector3 { x: 0, y: -1, z: 0 }, Vector3 { x: 0, y: 0, z: 1 }, Vector3 { x: 0, y: 0, z: -1 }];
-
-        for x in 0..world.len() {
-            for z in 0..world[0][0].len() {
-                for y in 0..world[0].len() {
-                    let mut viewable = calculate_viewable(&self, [x, y, z]);
-                    //viewable = ViewableDirection(0);
-
-                    for direction in directions.iter() {
-
-                        // Calculates if block is bordering on this direction
-                        if (direction.x == 1 && x == 15) || (direction.x == -1 && x == 0) ||
-                            (direction.y == 1 && y == 15) || (direction.y == -1 && y == 0) ||
-                            (direction.z == 1 && z == 15) || (direction.z == -1 && z == 0) {
-
-                            // Make it so we get the block on the other chunk closest to our block
-                            let block_pos: Vector3<usize> = Vector3 {
-                                x: if direction.x == -1 {x as usize} else if direction.x == -1 {0} else {15},
-                                y: if direction.y == -1 {y as usize} else if direction.y == -1 {0} else {15},
-                                z: if direction.z == -1 {z as usize} else if direction.z == -1 {0} else {15}
-                            };
-
-                            // Checks if the block in an adjacent chunk is transparent
-                            if adjacent_chunks.get(&direction).unwrap().is_some() {
-
-                                let chunk = adjacent_chunks.get(&direction).unwrap().unwrap();
-
-                                let block = {
-                                    let block_id = chunk.world[block_pos.x][block_pos.y][block_pos.z];
-                                    if block_id != 0 {
-                                        chunk.blocks.get(block_id as usize - 1)
-                                    } else {
-                                        None
-                                    }
-                                };
-
-                                // Check if face visible
-                                if block.map_or(true, |x| x.transparent) {
-                                    viewable.add_flag(ViewableDirectionBitMap::from(direction));
-                                }
-                            } else {
-                                viewable.add_flag(ViewableDirectionBitMap::from(direction));
-                            }
-                        }
-                    }
+    pub fn get_block<'b>(&self, pos: Vector3<usize>, blocks: &'b BlockRegistry) -> Option<&'b Block> {
+        blocks.get_by_state(self.world.get(pos.x, pos.y, pos.z))
+    }
 
-                    data[x][y][z] = viewable;
-                }
-            }
+    pub fn update_mesh(&mut self, mut data: ChunkMeshData) {
+        for (mesh, layer) in self.meshes.iter_mut().zip(data.layers.iter_mut()) {
+            mesh.indices = Some(std::mem::take(&mut layer.indices));
+            mesh.vertices = Some(std::mem::take(&mut layer.vertices));
         }
 
-        // Check top faces
-        data
+        self.viewable_map = Some(data.viewable);
     }
+}
+
+/// Finds the visible faces of every block in a chunk, including the faces against the chunks around it
+pub fn generate_viewable_map(neighbourhood: &ChunkNeighbourhood, blocks: &BlockRegistry) -> [[[ViewableDirection; 16]; 16]; 16] {
+
+    let mut data = [[[ViewableDirection(0); CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
 
-    pub fn get_block(&self, pos: Vector3<usize>) -> Option<&Block> {
-        let block_id = self.world[pos.x][pos.y][pos.z];
-        if block_id == 0 {
-            self.blocks.get(block_id as usize - 1)
-        } else {
-            None
+    for (x, plane) in data.iter_mut().enumerate() {
+        for (y, row) in plane.iter_mut().enumerate() {
+            for (z, viewable) in row.iter_mut().enumerate() {
+                *viewable = calculate_viewable(neighbourhood, [x as i32, y as i32, z as i32], blocks);
+            }
         }
     }
 
-    pub fn update_mesh(&mut self, data: ChunkMeshData) {
-        self.indices = Some(data.indices);
-        self.vertices= Some(data.vertices);
-        self.viewable_map = Some(data.viewable);
-    }
-}
\ No newline at end of file
+    data
+}
@@ -1,6 +1,8 @@
-use crate::block::Block;
+use crate::block::registry::BlockRegistry;
+use crate::block::BlockDirection;
+use crate::block::state::BlockState;
 use crate::services::chunk_service::mesh::ViewableDirectionBitMap;
-use crate::services::chunk_service::chunk::{Chunk, RawChunkData};
+use crate::services::chunk_service::mesh::neighbourhood::ChunkNeighbourhood;
 
 #[derive(Copy, Clone)]
 pub struct ViewableDirection(pub u8);
@@ -16,45 +18,41 @@ impl ViewableDirection {
     }
 }
 
-pub fn calculate_viewable(chunk: &Chunk, pos: [usize; 3]) -> ViewableDirection {
-    let world = &chunk.world;
+/// Works out which faces of a block can be seen. Blocks on the edge of the chunk are checked against the chunk next to them.
+pub fn calculate_viewable(neighbourhood: &ChunkNeighbourhood, pos: [i32; 3], blocks: &BlockRegistry) -> ViewableDirection {
+    let state = neighbourhood.get(pos[0], pos[1], pos[2]);
     let mut direction: u8 = 0;
 
-    if pos[1] != world[0].len() - 1 && is_offset_transparent(world, pos, &chunk.blocks, [0, 1, 0]) {
+    if is_offset_visible(neighbourhood, pos, state, blocks, [0, 1, 0], BlockDirection::Bottom) {
         direction += ViewableDirectionBitMap::Top as u8;
     }
 
-    if pos[1] != 0 && is_offset_transparent(world, pos, &chunk.blocks, [0, -1, 0]) {
+    if is_offset_visible(neighbourhood, pos, state, blocks, [0, -1, 0], BlockDirection::Up) {
         direction += ViewableDirectionBitMap::Bottom as u8;
     }
 
-    if pos[0] != world.len() - 1 && is_offset_transparent(world, pos, &chunk.blocks, [1, 0, 0]) {
+    if is_offset_visible(neighbourhood, pos, state, blocks, [1, 0, 0], BlockDirection::Left) {
         direction += ViewableDirectionBitMap::Right as u8;
     }
 
-    if pos[0] != 0 && is_offset_transparent(world, pos, &chunk.blocks, [-1, 0, 0]) {
+    if is_offset_visible(neighbourhood, pos, state, blocks, [-1, 0, 0], BlockDirection::Right) {
         direction += ViewableDirectionBitMap::Left as u8;
     }
 
-    if pos[2] != world[0][0].len() - 1 && is_offset_transparent(world, pos, &chunk.blocks, [0, 0, 1]) {
+    if is_offset_visible(neighbourhood, pos, state, blocks, [0, 0, 1], BlockDirection::Front) {
         direction += ViewableDirectionBitMap::Back as u8;
     }
 
-    if pos[2] != 0 && is_offset_transparent(world, pos, &chunk.blocks, [0, 0, -1]) {
+    if is_offset_visible(neighbourhood, pos, state, blocks, [0, 0, -1], BlockDirection::Back) {
         direction += ViewableDirectionBitMap::Front as u8;
     }
 
     ViewableDirection(direction)
 }
 
-fn is_offset_transparent(world: &RawChunkData, pos: [usize; 3], blocks: &Vec<Block>, offset: [isize; 3]) -> bool {
-    let block_id = world[(pos[0] as isize + offset[0]) as usize]
-        [(pos[1] as isize + offset[1]) as usize]
-        [(pos[2] as isize + offset[2]) as usize];
+/// Checks if the neighbouring block leaves our face visible, face is the neighbour's face pointing back at us
+fn is_offset_visible(neighbourhood: &ChunkNeighbourhood, pos: [i32; 3], state: BlockState, blocks: &BlockRegistry, offset: [i32; 3], face: BlockDirection) -> bool {
+    let neighbour = neighbourhood.get(pos[0] + offset[0], pos[1] + offset[1], pos[2] + offset[2]);
 
-    if block_id == 0 {
-        return true;
-    }
-
-    blocks.get(block_id as usize - 1).unwrap().transparent
-}
\ No newline at end of file
+    !blocks.hides_face(state, neighbour, face)
+}
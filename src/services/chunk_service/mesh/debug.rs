@@ -43,23 +43,35 @@ pub fn draw_y_face(x: f32, y: f32, z: f32, vertices: &mut Vec<Vertex>, indices:
 
     vertices.push(Vertex {
         position: [x, y, z],
-        tex_coords: [start_atlas[0], end_atlas[1]],
+        tex_coords: [0.0, 0.0],
         normals,
+        atlas: [start_atlas[0], start_atlas[1], end_atlas[0], end_atlas[1]],
+        ao: 1.0,
+        light: [1.0, 0.0],
     });
     vertices.push(Vertex {
         position: [x + 1.0, y, z],
-        tex_coords: end_atlas,
+        tex_coords: [1.0, 0.0],
         normals,
+        atlas: [start_atlas[0], start_atlas[1], end_atlas[0], end_atlas[1]],
+        ao: 1.0,
+        light: [1.0, 0.0],
     });
     vertices.push(Vertex {
         position: [x, y, z + 1.0],
-        tex_coords: start_atlas,
+        tex_coords: [0.0, 1.0],
         normals,
+        atlas: [start_atlas[0], start_atlas[1], end_atlas[0], end_atlas[1]],
+        ao: 1.0,
+        light: [1.0, 0.0],
     });
     vertices.push(Vertex {
         position: [x + 1.0, y, z + 1.0],
-        tex_coords: [ end_atlas[0], start_atlas[1]],
+        tex_coords: [1.0, 1.0],
         normals,
+        atlas: [start_atlas[0], start_atlas[1], end_atlas[0], end_atlas[1]],
+        ao: 1.0,
+        light: [1.0, 0.0],
     });
 
     if top {
@@ -89,23 +101,35 @@ pub fn draw_x_face(x: f32, y: f32, z: f32, vertices: &mut Vec<Vertex>, indices:
 
     vertices.push(Vertex {
         position: [x, y, z],
-        tex_coords: [start_atlas[0], end_atlas[1]],
+        tex_coords: [0.0, 0.0],
         normals,
+        atlas: [start_atlas[0], start_atlas[1], end_atlas[0], end_atlas[1]],
+        ao: 1.0,
+        light: [1.0, 0.0],
     });
     vertices.push(Vertex {
         position: [x + 1.0, y, z],
-        tex_coords: end_atlas,
+        tex_coords: [1.0, 0.0],
         normals,
+        atlas: [start_atlas[0], start_atlas[1], end_atlas[0], end_atlas[1]],
+        ao: 1.0,
+        light: [1.0, 0.0],
     });
     vertices.push(Vertex {
         position: [x, y + 1.0, z],
-        tex_coords: start_atlas,
+        tex_coords: [0.0, 1.0],
         normals,
+        atlas: [start_atlas[0], start_atlas[1], end_atlas[0], end_atlas[1]],
+        ao: 1.0,
+        light: [1.0, 0.0],
     });
     vertices.push(Vertex {
         position: [x + 1.0, y + 1.0, z],
-        tex_coords: [ end_atlas[0], start_atlas[1]],
+        tex_coords: [1.0, 1.0],
         normals,
+        atlas: [start_atlas[0], start_atlas[1], end_atlas[0], end_atlas[1]],
+        ao: 1.0,
+        light: [1.0, 0.0],
     });
 
     if forwards {
@@ -135,23 +159,35 @@ pub fn draw_z_face(x: f32, y: f32, z: f32, vertices: &mut Vec<Vertex>, indices:
 
     vertices.push(Vertex {
         position: [x, y, z],
-        tex_coords: [start_atlas[0], end_atlas[1]],
+        tex_coords: [0.0, 0.0],
         normals,
+        atlas: [start_atlas[0], start_atlas[1], end_atlas[0], end_atlas[1]],
+        ao: 1.0,
+        light: [1.0, 0.0],
     });
     vertices.push(Vertex {
         position: [x, y + 1.0, z],
-        tex_coords: start_atlas,
+        tex_coords: [0.0, 1.0],
         normals,
+        atlas: [start_atlas[0], start_atlas[1], end_atlas[0], end_atlas[1]],
+        ao: 1.0,
+        light: [1.0, 0.0],
     });
     vertices.push(Vertex {
         position: [x, y, z + 1.0],
-        tex_coords: end_atlas,
+        tex_coords: [1.0, 0.0],
         normals,
+        atlas: [start_atlas[0], start_atlas[1], end_atlas[0], end_atlas[1]],
+        ao: 1.0,
+        light: [1.0, 0.0],
     });
     vertices.push(Vertex {
         position: [x, y + 1.0, z + 1.0],
-        tex_coords: [ end_atlas[0], start_atlas[1]],
+        tex_coords: [1.0, 1.0],
         normals,
+        atlas: [start_atlas[0], start_atlas[1], end_atlas[0], end_atlas[1]],
+        ao: 1.0,
+        light: [1.0, 0.0],
     });
 
     if left {
@@ -1,59 +1,110 @@
-use crate::services::chunk_service::chunk::Chunk;
 use crate::services::chunk_service::mesh::culling::{ViewableDirection};
-use crate::services::chunk_service::mesh::block::{draw_block};
+use crate::services::chunk_service::mesh::block::{draw_block, face_axes, BlockShading, DIRECTIONS};
+use crate::services::chunk_service::mesh::greedy::draw_greedy_faces;
+use crate::block::shape::BlockShape;
+use crate::services::chunk_service::mesh::neighbourhood::ChunkNeighbourhood;
+use crate::services::chunk_service::mesh::ao::face_ao;
 use cgmath::{Point3, Vector3};
 use crate::services::chunk_service::ChunkService;
-use std::collections::HashMap;
-use crate::services::chunk_service::mesh::chunk::ChunkMeshData;
+use crate::services::chunk_service::mesh::chunk::{ChunkMeshData, generate_viewable_map};
+use crate::services::chunk_service::storage::ChunkStorage;
+use crate::services::chunk_service::mesh::MeshBuilder;
+use crate::services::settings_service::CHUNK_SIZE;
+use crate::block::registry::BlockRegistry;
 
 //
 // Our greedy meshing system
 //
 
-impl Chunk {
+/// A copy of everything needed to mesh a chunk, so it can be meshed on a worker thread while the world keeps changing
+pub struct MeshSnapshot {
+    pub position: Vector3<i32>,
+    pub world: ChunkStorage,
+    /// The chunk with a one block border from the chunks around it, so faces and occlusion are right at the edges
+    pub neighbourhood: ChunkNeighbourhood
+}
 
-    pub fn generate_mesh(&self, chunk_service: &ChunkService) -> ChunkMeshData {
+impl ChunkService {
+    /// Copies a loaded chunk and its surroundings ready for meshing
+    pub fn mesh_snapshot(&self, position: Vector3<i32>) -> Option<MeshSnapshot> {
+        let chunk = self.chunks.get(&position)?;
 
-        // Get adjacent chunks
-        let mut map = HashMap::new();
-        map.insert(Vector3 { x: 0, y: 1, z: 0 }, chunk_service.chunks.get(&(self.position + Vector3{x: 0, y: 1, z: 0})));
-        map.insert(Vector3 { x: 0, y: -1, z: 0 }, chunk_service.chunks.get(&(self.position + Vector3{x: 0, y: -1, z: 0})));
-        map.insert(Vector3 { x: 1, y: 0, z: 0 }, chunk_service.chunks.get(&(self.position + Vector3{x: 1, y: 0, z: 0})));
-        map.insert(Vector3 { x: -1, y: 0, z: 0 }, chunk_service.chunks.get(&(self.position + Vector3{x: -1, y: 0, z: 0})));
-        map.insert(Vector3 { x: 0, y: 0, z: 1 }, chunk_service.chunks.get(&(self.position + Vector3{x: 0, y: 1, z: 0})));
-        map.insert(Vector3 { x: 0, y: 0, z: -1 }, chunk_service.chunks.get(&(self.position + Vector3{x: 0, y: -1, z: 0})));
+        Some(MeshSnapshot {
+            position,
+            world: chunk.world.clone(),
+            neighbourhood: ChunkNeighbourhood::new(self, position)
+        })
+    }
+}
+
+impl MeshSnapshot {
 
-        let viewable = self.generate_viewable_map(map);
+    pub fn generate_mesh(&self, blocks: &BlockRegistry) -> ChunkMeshData {
 
-        let mut vertices = Vec::new();
-        let mut indices = Vec::new();
+        let mut layers = [MeshBuilder::default(), MeshBuilder::default(), MeshBuilder::default()];
+
+        // Nothing to draw in a chunk of air
+        if self.world.is_empty() {
+            return ChunkMeshData {
+                layers,
+                viewable: [[[ViewableDirection(0); CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]
+            };
+        }
 
-        // Create the buffers to add the mesh data into
-        let chunk = self.world;
+        let viewable = generate_viewable_map(&self.neighbourhood, blocks);
 
-        for x in 0..chunk.len() {
-            for z in 0..chunk[0][0].len() {
-                for y in 0..chunk[0].len() {
+        let neighbourhood = &self.neighbourhood;
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for y in 0..CHUNK_SIZE {
                     let viewable = viewable[x][y][z].0;
 
                     //Isn't air
-                    if chunk[x][y][z] != 0 && viewable != 0 {
-                        let block = &self.blocks[chunk[x][y][z] as usize - 1];
-
-                        //Found it, draw vertices for it
-                        draw_block(Point3 {
-                            x: x as f32,
-                            y: y as f32,
-                            z: z as f32
-                        }, ViewableDirection(viewable), &mut vertices, &mut indices, block);
+                    if viewable != 0 {
+                        let state = self.world.get(x, y, z);
+
+                        if let Some(block) = blocks.get_by_state(state) {
+
+                            // Full cubes are merged together below
+                            if let BlockShape::Cube = block.shape {
+                                continue;
+                            }
+
+                            let pos = [x as i32, y as i32, z as i32];
+                            let mut shading = BlockShading {
+                                ao: [[0; 4]; 6],
+                                light: [0; 6],
+                                inside_light: neighbourhood.light(pos[0], pos[1], pos[2])
+                            };
+
+                            for direction in DIRECTIONS.iter() {
+                                let (axis, _, _, _, on_max) = face_axes(*direction);
+                                let mut front = pos;
+                                front[axis] += if on_max { 1 } else { -1 };
+
+                                shading.ao[*direction as usize] = face_ao(neighbourhood, blocks, pos, *direction);
+                                shading.light[*direction as usize] = neighbourhood.light(front[0], front[1], front[2]);
+                            }
+
+                            let layer = &mut layers[block.render_layer as usize];
+
+                            //Found it, draw vertices for it
+                            draw_block(Point3 {
+                                x: x as f32,
+                                y: y as f32,
+                                z: z as f32
+                            }, ViewableDirection(viewable), layer, block, state, &shading);
+                        }
                     }
                 }
             }
         }
 
+        draw_greedy_faces(neighbourhood, &viewable, blocks, &mut layers);
+
         ChunkMeshData {
-            vertices,
-            indices,
+            layers,
             viewable
         }
     }
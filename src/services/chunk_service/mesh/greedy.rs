@@ -0,0 +1,131 @@
+//
+// Merges neighbouring cube faces that look the same into larger quads
+//
+
+use crate::block::shape::BlockShape;
+use crate::block::registry::BlockRegistry;
+use crate::block::state::BlockFace;
+use crate::services::chunk_service::mesh::neighbourhood::ChunkNeighbourhood;
+use crate::services::chunk_service::mesh::ao::face_ao;
+use crate::services::chunk_service::mesh::culling::ViewableDirection;
+use crate::services::chunk_service::mesh::block::{DIRECTIONS, face_axes, viewable_flag, tex_coord, push_quad};
+use crate::services::chunk_service::mesh::MeshBuilder;
+use crate::block::RenderLayer;
+use crate::services::settings_service::CHUNK_SIZE;
+
+/// Everything that decides how a face is drawn, its texture, occlusion, light and layer
+type FaceKey = (BlockFace, [u8; 4], u8, RenderLayer);
+
+/// Faces can only be merged if they'd be drawn exactly the same. Occlusion has to be the same at all four corners too,
+/// otherwise stretching the quad would stretch the shading across every face in it.
+fn same_face(a: &FaceKey, b: &FaceKey) -> bool {
+    let flat_ao = a.1.iter().all(|corner| *corner == a.1[0]);
+
+    flat_ao && a.0.texture == b.0.texture && a.0.rotation == b.0.rotation && a.1 == b.1 && a.2 == b.2 && a.3 == b.3
+}
+
+/// Draws every visible face of the full cube blocks in a chunk. Each direction is swept one slice at a time,
+/// and the faces in a slice are grown into the largest rectangles of matching faces they can make.
+pub fn draw_greedy_faces(neighbourhood: &ChunkNeighbourhood, viewable: &[[[ViewableDirection; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE], blocks: &BlockRegistry, layers: &mut [MeshBuilder; 3]) {
+    for direction in DIRECTIONS.iter() {
+        let (axis, a_axis, b_axis, normal, on_max) = face_axes(*direction);
+
+        for slice in 0..CHUNK_SIZE {
+            // The faces in this slice that still need drawing
+            let mut mask: [[Option<FaceKey>; CHUNK_SIZE]; CHUNK_SIZE] = [[None; CHUNK_SIZE]; CHUNK_SIZE];
+
+            for (a, row) in mask.iter_mut().enumerate() {
+                for (b, cell) in row.iter_mut().enumerate() {
+                    let mut pos = [0; 3];
+                    pos[axis] = slice;
+                    pos[a_axis] = a;
+                    pos[b_axis] = b;
+
+                    if !viewable[pos[0]][pos[1]][pos[2]].has_flag(viewable_flag(*direction)) {
+                        continue;
+                    }
+
+                    let pos = [pos[0] as i32, pos[1] as i32, pos[2] as i32];
+                    let state = neighbourhood.get(pos[0], pos[1], pos[2]);
+
+                    if let Some(block) = blocks.get_by_state(state) {
+                        if let BlockShape::Cube = block.shape {
+                            let ao = face_ao(neighbourhood, blocks, pos, *direction);
+
+                            // Faces are lit by the block in front of them
+                            let mut front = pos;
+                            front[axis] += if on_max { 1 } else { -1 };
+                            let light = neighbourhood.light(front[0], front[1], front[2]);
+
+                            *cell = Some((block.faces(state)[*direction as usize], ao, light, block.render_layer));
+                        }
+                    }
+                }
+            }
+
+            let plane = (if on_max { slice + 1 } else { slice }) as f32;
+
+            for b in 0..CHUNK_SIZE {
+                let mut a = 0;
+
+                while a < CHUNK_SIZE {
+                    let (face, ao, light, layer) = match mask[a][b] {
+                        Some(face) => face,
+                        None => {
+                            a += 1;
+                            continue;
+                        }
+                    };
+
+                    let matches = |face_at: Option<FaceKey>| face_at.is_some_and(|other| same_face(&(face, ao, light, layer), &other));
+
+                    let mut width = 1;
+                    while a + width < CHUNK_SIZE && matches(mask[a + width][b]) {
+                        width += 1;
+                    }
+
+                    let mut height = 1;
+                    'grow: while b + height < CHUNK_SIZE {
+                        for i in 0..width {
+                            if !matches(mask[a + i][b + height]) {
+                                break 'grow;
+                            }
+                        }
+                        height += 1;
+                    }
+
+                    for i in 0..width {
+                        for j in 0..height {
+                            mask[a + i][b + j] = None;
+                        }
+                    }
+
+                    let a_range = [a as f32, (a + width) as f32];
+                    let b_range = [b as f32, (b + height) as f32];
+
+                    let mut positions = [[0.0; 3]; 4];
+                    let mut tex_coords = [[0.0; 2]; 4];
+
+                    // Corners in the order (min, min), (max, min), (min, max), (max, max)
+                    for corner in 0..4 {
+                        let a_max = corner & 1 == 1;
+                        let b_max = corner & 2 == 2;
+
+                        let mut position = [0.0; 3];
+                        position[axis] = plane;
+                        position[a_axis] = a_range[a_max as usize];
+                        position[b_axis] = b_range[b_max as usize];
+
+                        positions[corner] = position;
+                        tex_coords[corner] = tex_coord(&face, a_range[a_max as usize], b_range[b_max as usize]);
+                    }
+
+                    let layer = &mut layers[layer as usize];
+                    push_quad(positions, tex_coords, face.texture, normal, ao, light, layer);
+
+                    a += width;
+                }
+            }
+        }
+    }
+}
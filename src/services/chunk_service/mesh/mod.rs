@@ -5,6 +5,12 @@ pub mod culling;
 pub mod block;
 pub mod debug;
 pub mod generation;
+pub mod greedy;
+pub mod neighbourhood;
+pub mod ao;
+
+/// Blocks are drawn this far below their position, as they always have been
+pub const BLOCK_Y_OFFSET: f32 = -2.0;
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -13,6 +19,19 @@ pub struct Vertex {
     pub tex_coords: [f32; 2],
     /*pub relative_coords: [f32; 2],*/
     pub normals: [f32; 3],
+    /// Corners of the face's texture in the atlas as [start x, start y, end x, end y], tex_coords wrap inside it
+    pub atlas: [f32; 4],
+    /// Ambient occlusion from 0 (dark) to 1 (unoccluded)
+    pub ao: f32,
+    /// Sky and block light from 0 to 1
+    pub light: [f32; 2],
+}
+
+/// Vertices and indices being built for one render layer of a chunk
+#[derive(Default)]
+pub struct MeshBuilder {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u16>
 }
 
 #[repr(C)]
@@ -73,6 +92,21 @@ impl Vertex {
                     shader_location: 2,
                     format: wgpu::VertexFormat::Float3,
                 },
+                wgpu::VertexAttributeDescriptor {
+                    offset: (mem::size_of::<[f32; 3]>() * 2 + mem::size_of::<[f32; 2]>()) as wgpu::BufferAddress,
+                    shader_location: 3,
+                    format: wgpu::VertexFormat::Float4,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: (mem::size_of::<[f32; 3]>() * 2 + mem::size_of::<[f32; 2]>() + mem::size_of::<[f32; 4]>()) as wgpu::BufferAddress,
+                    shader_location: 4,
+                    format: wgpu::VertexFormat::Float,
+                },
+                wgpu::VertexAttributeDescriptor {
+                    offset: (mem::size_of::<[f32; 3]>() * 2 + mem::size_of::<[f32; 2]>() + mem::size_of::<[f32; 4]>() + mem::size_of::<f32>()) as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float2,
+                },
             ]
         }
     }
@@ -0,0 +1,75 @@
+//
+// A copy of a chunk's blocks with a one block border taken from the chunks around it,
+// so meshing can look at neighbouring blocks without caring which chunk they are in
+//
+
+use crate::block::state::BlockState;
+use crate::services::chunk_service::ChunkService;
+use crate::services::chunk_service::light::MAX_LIGHT;
+use crate::services::settings_service::CHUNK_SIZE;
+use cgmath::Vector3;
+
+const PADDED_SIZE: usize = CHUNK_SIZE + 2;
+
+pub struct ChunkNeighbourhood {
+    states: Vec<BlockState>,
+    /// Packed the same way as ChunkLight
+    light: Vec<u8>
+}
+
+impl ChunkNeighbourhood {
+    /// Copies the chunk at position and the edges of the 26 chunks around it. Chunks that aren't loaded are treated as sunlit air.
+    pub fn new(chunk_service: &ChunkService, position: Vector3<i32>) -> ChunkNeighbourhood {
+        let mut states = vec![BlockState::AIR; PADDED_SIZE * PADDED_SIZE * PADDED_SIZE];
+        let mut light = vec![MAX_LIGHT << 4; PADDED_SIZE * PADDED_SIZE * PADDED_SIZE];
+        let size = CHUNK_SIZE as i32;
+
+        for chunk_x in -1..=1 {
+            for chunk_y in -1..=1 {
+                for chunk_z in -1..=1 {
+                    let chunk = match chunk_service.chunks.get(&(position + Vector3 { x: chunk_x, y: chunk_y, z: chunk_z })) {
+                        Some(chunk) => chunk,
+                        None => continue
+                    };
+
+                    // The part of the padded area this chunk covers
+                    let range = |offset: i32| match offset {
+                        -1 => -1..0,
+                        0 => 0..size,
+                        _ => size..size + 1
+                    };
+
+                    for x in range(chunk_x) {
+                        for y in range(chunk_y) {
+                            for z in range(chunk_z) {
+                                let local = [x.rem_euclid(size) as usize, y.rem_euclid(size) as usize, z.rem_euclid(size) as usize];
+
+                                states[padded_index(x, y, z)] = chunk.world.get(local[0], local[1], local[2]);
+                                light[padded_index(x, y, z)] = chunk.light.packed(local[0], local[1], local[2]);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        ChunkNeighbourhood {
+            states,
+            light
+        }
+    }
+
+    /// Gets a block relative to the chunk, each coordinate can be from -1 to CHUNK_SIZE
+    pub fn get(&self, x: i32, y: i32, z: i32) -> BlockState {
+        self.states[padded_index(x, y, z)]
+    }
+
+    /// Gets the packed sky and block light of a block relative to the chunk
+    pub fn light(&self, x: i32, y: i32, z: i32) -> u8 {
+        self.light[padded_index(x, y, z)]
+    }
+}
+
+fn padded_index(x: i32, y: i32, z: i32) -> usize {
+    (((x + 1) as usize * PADDED_SIZE + (y + 1) as usize) * PADDED_SIZE) + (z + 1) as usize
+}
@@ -2,28 +2,66 @@
 // Handles chunk loading, chunk unloading and chunk animations
 //
 
-use crate::services::settings_service::SettingsService;
+use crate::services::settings_service::CHUNK_SIZE;
 use crate::services::ServicesContext;
-use wgpu::{BindGroupLayout, Device};
-use crate::world::generator::World;
-use crate::services::chunk_service::chunk::{Chunk, ChunkData};
-use crate::block::Block;
-use cgmath::{Vector3};
-use std::collections::HashMap;
+use wgpu::BindGroupLayout;
+use crate::world::generator::WorldGenerator;
+use crate::services::chunk_service::chunk::Chunk;
+use crate::services::chunk_service::storage::ChunkStorage;
+use crate::services::chunk_service::mesh::chunk::ChunkMeshData;
+use crate::services::chunk_service::workers::WorkerPool;
+use crate::services::chunk_service::streaming::WORLD_HEIGHT;
+use crate::world::save::WorldSave;
+use crate::world::biome::BiomeMap;
+use crate::world::generator::structures::Structure;
+use crate::block::registry::BlockRegistry;
+use crate::block::state::BlockState;
+use cgmath::{Vector2, Vector3};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use wgpu::Device;
 
 pub mod mesh;
 pub mod chunk;
+pub mod storage;
+pub mod light;
+pub mod streaming;
+pub mod workers;
 
 pub struct ChunkService {
     pub(crate) bind_group_layout: BindGroupLayout,
     pub(crate) chunks: HashMap<Vector3<i32>, Chunk>,
     pub(crate) vertices_count: u64,
-    pub(crate) chunk_keys: Vec<Vector3<i32>>
+    pub(crate) workers: WorkerPool,
+    pub(crate) save: WorldSave,
+    /// Columns that have been generated or changed since they were last saved
+    pub(crate) unsaved_columns: HashSet<Vector2<i32>>,
+    /// Columns of chunks (x, z) that are loaded
+    pub(crate) loaded_columns: HashSet<Vector2<i32>>,
+    /// Columns waiting to be loaded, nearest to the player last
+    pub(crate) load_queue: Vec<Vector2<i32>>,
+    /// The column the player was in last frame
+    pub(crate) player_column: Option<Vector2<i32>>,
+    /// Chunks being generated by the workers, with the flag that cancels them
+    pub(crate) pending_generation: HashMap<Vector3<i32>, Arc<AtomicBool>>,
+    /// Chunks being meshed by the workers, with the version of the latest job and the flag that cancels it
+    pub(crate) pending_meshes: HashMap<Vector3<i32>, (u32, Arc<AtomicBool>)>,
+    pub(crate) mesh_version: u32,
+    /// Finished meshes waiting for their gpu buffers
+    pub(crate) upload_queue: VecDeque<(Vector3<i32>, ChunkMeshData)>,
+    /// Chunks with changed blocks, remeshed at the end of the frame
+    pub(crate) dirty_chunks: HashSet<Vector3<i32>>,
+    /// Biomes of loaded columns, generated alongside their chunks
+    pub(crate) column_biomes: HashMap<Vector2<i32>, BiomeMap>,
+    /// Kept for asking about the world, chunks are generated by the workers' copy
+    pub(crate) generator: Arc<dyn WorldGenerator>
 }
 
 impl ChunkService {
 
-    pub fn new(settings: &SettingsService, context: &mut ServicesContext) -> ChunkService {
+    /// Chunks are loaded in the background as the player moves, starting from the first frame
+    pub fn new(blocks: Arc<BlockRegistry>, generator: Arc<dyn WorldGenerator>, save: WorldSave, context: &mut ServicesContext) -> ChunkService {
 
         let bind_group_layout_descriptor = wgpu::BindGroupLayoutDescriptor {
             bindings: &[
@@ -40,63 +78,148 @@ impl ChunkService {
         // Create the chunk bind group layout
         let bind_group_layout = context.device.create_bind_group_layout(&bind_group_layout_descriptor);
 
-        let mut service = ChunkService {
+        ChunkService {
             bind_group_layout,
             chunks: HashMap::new(),
             vertices_count: 0,
-            chunk_keys: Vec::new()
+            workers: WorkerPool::new(generator.clone(), blocks).expect("Chunks can't be loaded without any workers"),
+            save,
+            unsaved_columns: HashSet::new(),
+            loaded_columns: HashSet::new(),
+            load_queue: Vec::new(),
+            player_column: None,
+            pending_generation: HashMap::new(),
+            pending_meshes: HashMap::new(),
+            mesh_version: 0,
+            upload_queue: VecDeque::new(),
+            dirty_chunks: HashSet::new(),
+            column_biomes: HashMap::new(),
+            generator
+        }
+    }
+
+    /// Adds a chunk without lighting or meshing it
+    pub fn load_chunk(&mut self, data: ChunkStorage, chunk_coords: Vector3<i32>) {
+        let chunk = Chunk::new(data, chunk_coords);
+
+        self.chunks.insert(chunk_coords, chunk);
+    }
+
+    /// Gets the biome id at a position in the world, None if its column isn't loaded or hasn't had its biomes generated yet
+    pub fn get_biome(&self, pos: Vector3<i32>) -> Option<u8> {
+        let (chunk, local) = chunk_position(pos);
+
+        self.column_biomes.get(&Vector2 { x: chunk.x, y: chunk.z }).map(|biomes| biomes.get(local[0], local[2]))
+    }
+
+    /// Structures with bounding boxes around a position in the world, such as the village the player is in.
+    /// Structures are worked out from the seed, so chunks don't need to be loaded.
+    pub fn structures_at(&self, pos: Vector3<i32>) -> Vec<Arc<Structure>> {
+        self.generator.structures_at(pos)
+    }
+
+    /// Gets the block at a position in the world, None if its chunk isn't loaded
+    pub fn get_block(&self, pos: Vector3<i32>) -> Option<BlockState> {
+        let (chunk, local) = chunk_position(pos);
+
+        self.chunks.get(&chunk).map(|chunk| chunk.world.get(local[0], local[1], local[2]))
+    }
+
+    /// Sets the block at a position in the world and relights around it. The chunk it's in, and any chunk
+    /// it borders, are remeshed at the end of the frame. Returns false if its chunk isn't loaded.
+    pub fn set_block(&mut self, pos: Vector3<i32>, state: BlockState, blocks: &BlockRegistry) -> bool {
+        let (chunk_pos, local) = chunk_position(pos);
+
+        let chunk = match self.chunks.get_mut(&chunk_pos) {
+            Some(chunk) => chunk,
+            None => return false
         };
 
-        //TODO: Remove this once we have networking
-        for x in -(settings.render_distance as i32)..(settings.render_distance as i32 * 8) {
-            for z in -(settings.render_distance as i32)..(settings.render_distance as i32) {
-                for y in 0..16 {
-                    let data = ChunkService::generate_chunk(x, y, z, context.blocks);
-                    service.load_chunk(context.device, data, Vector3 { x, y, z }, &settings);
+        if chunk.world.get(local[0], local[1], local[2]) == state {
+            return true;
+        }
+
+        chunk.world.set(local[0], local[1], local[2], state);
+        self.unsaved_columns.insert(Vector2 { x: chunk_pos.x, y: chunk_pos.z });
+
+        // Chunks mesh with a one block border from their neighbours, so a block on the edge of a chunk
+        // changes the faces and occlusion of the chunks touching that edge
+        let border = |coord: usize| if coord == 0 { -1..=0 } else if coord == CHUNK_SIZE - 1 { 0..=1 } else { 0..=0 };
+        for x in border(local[0]) {
+            for y in border(local[1]) {
+                for z in border(local[2]) {
+                    self.dirty_chunks.insert(chunk_pos + Vector3 { x, y, z });
                 }
             }
         }
 
-        for i in 0..service.chunks.len() {
-            let chunk_key = service.chunk_keys
-                .get(i)
-                .unwrap();
+        let relit = self.update_light(pos, blocks);
+        self.dirty_chunks.extend(relit);
 
-            let mesh_data = {
-                let chunk = service.chunks
-                    .get(chunk_key)
-                    .unwrap();
+        true
+    }
 
-                chunk.generate_mesh(&service)
-            };
+    /// Meshes and uploads every chunk that has changed since last frame. This is done straight away
+    /// rather than on the workers so edits show up on the next frame.
+    pub fn remesh_dirty_chunks(&mut self, blocks: &BlockRegistry, device: &Device) {
+        for position in std::mem::take(&mut self.dirty_chunks) {
+            // Anything already being meshed is out of date now
+            if let Some((_, cancelled)) = self.pending_meshes.remove(&position) {
+                cancelled.store(true, Ordering::Relaxed);
+            }
+            self.upload_queue.retain(|(queued, _)| *queued != position);
 
-            // Add new vertices to count
-            service.vertices_count += mesh_data.vertices.len() as u64;
+            // Columns that are still loading are meshed once they've been lit
+            if !self.column_generated(Vector2 { x: position.x, y: position.z }) {
+                continue;
+            }
 
-            let chunk = service.chunks
-                .get_mut(chunk_key)
-                .unwrap();
+            if let Some(snapshot) = self.mesh_snapshot(position) {
+                let mesh = snapshot.generate_mesh(blocks);
+                self.upload_mesh(position, mesh, device);
+            }
+        }
+    }
 
-            chunk.update_mesh(mesh_data);
-            chunk.create_buffers(&context.device, &service.bind_group_layout);
+    /// Saves every column that has changed, and waits for them to be written. Call this before exiting.
+    pub fn save_world(&mut self) {
+        for column in std::mem::take(&mut self.unsaved_columns) {
+            self.save_column(column);
         }
 
-        service
+        self.save.flush();
     }
 
-    //TODO: Remove this once we have networking setup
-    fn generate_chunk(x: i32, y: i32, z: i32, blocks: &Vec<Block>) -> ChunkData {
-        return World::generate_chunk(Vector3 { x, y, z }, blocks);
-    }
+    /// Sends the chunks of a column to be saved
+    pub(crate) fn save_column(&self, column: Vector2<i32>) {
+        let chunks: Vec<(i32, ChunkStorage)> = (0..WORLD_HEIGHT)
+            .filter_map(|y| self.chunks.get(&Vector3 { x: column.x, y, z: column.y }).map(|chunk| (y, chunk.world.clone())))
+            .collect();
 
-    pub fn load_chunk(&mut self, device: &Device, data: ChunkData, chunk_coords: Vector3<i32>, settings: &SettingsService) {
-        let mut chunk = Chunk::new(data, chunk_coords);
+        if !chunks.is_empty() {
+            self.save.save_column(column, chunks);
+        }
+    }
 
-        // chunk.generate_mesh(&self);
-        // self.vertices += chunk.vertices.as_ref().unwrap().len() as u64;
-        // chunk.create_buffers(device, &self.bind_group_layout);
+    /// Swaps in a new mesh for a loaded chunk and creates its gpu buffers
+    pub(crate) fn upload_mesh(&mut self, position: Vector3<i32>, mesh: ChunkMeshData, device: &Device) {
+        if let Some(chunk) = self.chunks.get_mut(&position) {
+            self.vertices_count -= chunk.vertices_count() as u64;
+            self.vertices_count += mesh.vertices_count() as u64;
 
-        self.chunk_keys.push(chunk_coords.clone());
-        self.chunks.insert(chunk_coords, chunk);
+            chunk.update_mesh(mesh);
+            chunk.create_buffers(device, &self.bind_group_layout);
+        }
     }
-}
\ No newline at end of file
+}
+
+/// Splits a block position in the world into the chunk it's in and its position inside that chunk
+pub fn chunk_position(pos: Vector3<i32>) -> (Vector3<i32>, [usize; 3]) {
+    let size = CHUNK_SIZE as i32;
+
+    (Vector3 {
+        x: pos.x.div_euclid(size),
+        y: pos.y.div_euclid(size),
+        z: pos.z.div_euclid(size)
+    }, [pos.x.rem_euclid(size) as usize, pos.y.rem_euclid(size) as usize, pos.z.rem_euclid(size) as usize])
+}
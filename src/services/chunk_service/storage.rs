@@ -0,0 +1,342 @@
+//
+// Palette compressed block storage for a single chunk
+//
+
+use crate::services::settings_service::CHUNK_SIZE;
+use crate::block::state::BlockState;
+
+pub const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+
+/// Stores the block states of a chunk as indices into a per chunk palette, packed into as few bits as the palette allows.
+/// Chunks made of a single block (all air, all stone) keep no index data at all.
+#[derive(Clone, Debug)]
+pub struct ChunkStorage {
+    palette: Vec<BlockState>,
+    bits_per_entry: usize,
+    data: Vec<u64>
+}
+
+impl ChunkStorage {
+    /// Creates a chunk made entirely out of one block
+    pub fn filled(block: BlockState) -> ChunkStorage {
+        ChunkStorage {
+            palette: vec![block],
+            bits_per_entry: 0,
+            data: Vec::new()
+        }
+    }
+
+    /// Creates a chunk made entirely out of air
+    pub fn empty() -> ChunkStorage {
+        ChunkStorage::filled(BlockState::AIR)
+    }
+
+    pub fn get(&self, x: usize, y: usize, z: usize) -> BlockState {
+        if self.bits_per_entry == 0 {
+            return self.palette[0];
+        }
+
+        self.palette[self.read_index(index(x, y, z))]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, block: BlockState) {
+        let palette_index = match self.palette.iter().position(|entry| *entry == block) {
+            Some(palette_index) => palette_index,
+            None => {
+                self.palette.push(block);
+
+                let bits = bits_needed(self.palette.len());
+                if bits > self.bits_per_entry {
+                    self.resize(bits);
+                }
+
+                self.palette.len() - 1
+            }
+        };
+
+        // Single value chunks don't store indices
+        if self.bits_per_entry == 0 {
+            return;
+        }
+
+        self.write_index(index(x, y, z), palette_index);
+    }
+
+    /// Returns the block if the whole chunk is made out of it
+    pub fn single_value(&self) -> Option<BlockState> {
+        if self.bits_per_entry == 0 {
+            Some(self.palette[0])
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the chunk only contains air
+    pub fn is_empty(&self) -> bool {
+        self.single_value() == Some(BlockState::AIR)
+    }
+
+    pub fn palette(&self) -> &Vec<BlockState> {
+        &self.palette
+    }
+
+    /// The packed palette indices, entries take as few bits as the palette size needs
+    pub fn packed_data(&self) -> &Vec<u64> {
+        &self.data
+    }
+
+    /// Rebuilds a chunk from its palette and packed indices, as given by palette() and packed_data().
+    /// Returns None if the data is the wrong length for the palette.
+    pub fn from_packed(palette: Vec<BlockState>, data: Vec<u64>) -> Option<ChunkStorage> {
+        let bits = bits_needed(palette.len());
+
+        if palette.is_empty() || data.len() != longs_needed(bits) {
+            return None;
+        }
+
+        let storage = ChunkStorage {
+            palette,
+            bits_per_entry: bits,
+            data
+        };
+
+        // Every index has to point into the palette
+        if bits != 0 && (0..CHUNK_VOLUME).any(|i| storage.read_index(i) >= storage.palette.len()) {
+            return None;
+        }
+
+        Some(storage)
+    }
+
+    /// Removes palette entries that are no longer used and packs the indices into the smallest size possible.
+    /// Call this once a chunk has finished being built, as sets never shrink the palette.
+    pub fn compact(&mut self) {
+        if self.bits_per_entry == 0 {
+            return;
+        }
+
+        let mut used = vec![false; self.palette.len()];
+        for i in 0..CHUNK_VOLUME {
+            used[self.read_index(i)] = true;
+        }
+
+        // Map old palette indices onto the new palette
+        let mut remap = vec![0; self.palette.len()];
+        let mut palette = Vec::new();
+        for (old_index, block) in self.palette.iter().enumerate() {
+            if used[old_index] {
+                remap[old_index] = palette.len();
+                palette.push(*block);
+            }
+        }
+
+        let bits = bits_needed(palette.len());
+        let mut data = vec![0; longs_needed(bits)];
+
+        if bits != 0 {
+            for i in 0..CHUNK_VOLUME {
+                write_packed(&mut data, bits, i, remap[self.read_index(i)]);
+            }
+        }
+
+        self.palette = palette;
+        self.bits_per_entry = bits;
+        self.data = data;
+    }
+
+    /// Approximate heap usage in bytes, used for debugging memory
+    pub fn memory_usage(&self) -> usize {
+        self.palette.len() * std::mem::size_of::<BlockState>() + self.data.len() * std::mem::size_of::<u64>()
+    }
+
+    fn resize(&mut self, bits: usize) {
+        let mut data = vec![0; longs_needed(bits)];
+
+        if self.bits_per_entry != 0 {
+            for i in 0..CHUNK_VOLUME {
+                write_packed(&mut data, bits, i, self.read_index(i));
+            }
+        }
+
+        self.bits_per_entry = bits;
+        self.data = data;
+    }
+
+    fn read_index(&self, i: usize) -> usize {
+        let entries_per_long = 64 / self.bits_per_entry;
+        let offset = (i % entries_per_long) * self.bits_per_entry;
+        let mask = (1u64 << self.bits_per_entry) - 1;
+
+        ((self.data[i / entries_per_long] >> offset) & mask) as usize
+    }
+
+    fn write_index(&mut self, i: usize, palette_index: usize) {
+        write_packed(&mut self.data, self.bits_per_entry, i, palette_index);
+    }
+}
+
+fn write_packed(data: &mut [u64], bits: usize, i: usize, value: usize) {
+    let entries_per_long = 64 / bits;
+    let offset = (i % entries_per_long) * bits;
+    let mask = ((1u64 << bits) - 1) << offset;
+    let long = &mut data[i / entries_per_long];
+
+    *long = (*long & !mask) | ((value as u64) << offset);
+}
+
+/// Entries never straddle two longs, so some bits at the top of each long may go unused
+fn longs_needed(bits: usize) -> usize {
+    if bits == 0 {
+        return 0;
+    }
+
+    let entries_per_long = 64 / bits;
+    CHUNK_VOLUME.div_ceil(entries_per_long)
+}
+
+fn bits_needed(palette_len: usize) -> usize {
+    if palette_len <= 1 {
+        0
+    } else {
+        (64 - ((palette_len - 1) as u64).leading_zeros()) as usize
+    }
+}
+
+fn index(x: usize, y: usize, z: usize) -> usize {
+    (x * CHUNK_SIZE + y) * CHUNK_SIZE + z
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(id: u32) -> BlockState {
+        BlockState::new(id, 0)
+    }
+
+    /// Gives every position a block from 0 to count - 1, so each palette entry is used somewhere
+    fn fill_pattern(storage: &mut ChunkStorage, count: u32) {
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    storage.set(x, y, z, block(index(x, y, z) as u32 % count));
+                }
+            }
+        }
+    }
+
+    fn assert_pattern(storage: &ChunkStorage, count: u32) {
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    assert_eq!(storage.get(x, y, z), block(index(x, y, z) as u32 % count), "block at {} {} {}", x, y, z);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_chunk_stores_no_indices() {
+        let storage = ChunkStorage::empty();
+
+        assert!(storage.is_empty());
+        assert_eq!(storage.get(3, 4, 5), BlockState::AIR);
+        assert!(storage.packed_data().is_empty());
+    }
+
+    #[test]
+    fn set_get_round_trip_as_palette_grows() {
+        let mut storage = ChunkStorage::empty();
+
+        // 1, 2, 5 and 17 entries need 0, 1, 3 and 5 bits, every step moves the existing indices to a new size
+        for (count, bits) in [(1, 0), (2, 1), (5, 3), (17, 5)].iter() {
+            fill_pattern(&mut storage, *count);
+
+            assert_eq!(storage.palette().len(), *count as usize);
+            assert_eq!(storage.packed_data().len(), longs_needed(*bits));
+            assert_pattern(&storage, *count);
+        }
+    }
+
+    #[test]
+    fn growing_keeps_blocks_set_before() {
+        let mut storage = ChunkStorage::empty();
+        storage.set(0, 0, 0, block(1));
+        storage.set(15, 15, 15, block(2));
+
+        for id in 3..20 {
+            storage.set(id as usize % CHUNK_SIZE, 7, 7, block(id));
+        }
+
+        assert_eq!(storage.get(0, 0, 0), block(1));
+        assert_eq!(storage.get(15, 15, 15), block(2));
+        assert_eq!(storage.get(3, 7, 7), block(19));
+        assert_eq!(storage.get(1, 1, 1), BlockState::AIR);
+    }
+
+    #[test]
+    fn compact_after_clearing_returns_to_single_value() {
+        let mut storage = ChunkStorage::empty();
+        storage.set(1, 2, 3, block(1));
+        storage.set(4, 5, 6, block(2));
+
+        storage.set(1, 2, 3, BlockState::AIR);
+        storage.set(4, 5, 6, BlockState::AIR);
+        storage.compact();
+
+        assert!(storage.is_empty());
+        assert_eq!(storage.palette().len(), 1);
+        assert!(storage.packed_data().is_empty());
+    }
+
+    #[test]
+    fn compact_drops_unused_entries_and_shrinks_indices() {
+        let mut storage = ChunkStorage::empty();
+        fill_pattern(&mut storage, 17);
+
+        // Only air and block 1 are left
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let state = if x == 0 { block(1) } else { BlockState::AIR };
+                    storage.set(x, y, z, state);
+                }
+            }
+        }
+
+        assert_eq!(storage.palette().len(), 17);
+        storage.compact();
+
+        assert_eq!(storage.palette(), &vec![BlockState::AIR, block(1)]);
+        assert_eq!(storage.packed_data().len(), longs_needed(1));
+        assert_eq!(storage.get(0, 9, 9), block(1));
+        assert_eq!(storage.get(1, 9, 9), BlockState::AIR);
+    }
+
+    #[test]
+    fn packed_round_trip() {
+        let mut storage = ChunkStorage::empty();
+        fill_pattern(&mut storage, 5);
+
+        let copy = ChunkStorage::from_packed(storage.palette().clone(), storage.packed_data().clone()).unwrap();
+        assert_pattern(&copy, 5);
+
+        // Data that's the wrong length for the palette is refused
+        assert!(ChunkStorage::from_packed(vec![BlockState::AIR, block(1)], Vec::new()).is_none());
+    }
+
+    #[test]
+    fn memory_usage_counts_palette_and_indices() {
+        let state_size = std::mem::size_of::<BlockState>();
+        let mut storage = ChunkStorage::empty();
+        assert_eq!(storage.memory_usage(), state_size);
+
+        // One bit per block
+        storage.set(0, 0, 0, block(1));
+        assert_eq!(storage.memory_usage(), 2 * state_size + CHUNK_VOLUME / 64 * 8);
+
+        // Five bits per block, twelve to a long
+        fill_pattern(&mut storage, 17);
+        assert_eq!(storage.memory_usage(), 17 * state_size + CHUNK_VOLUME.div_ceil(12) * 8);
+    }
+}
@@ -0,0 +1,195 @@
+//
+// Loads chunks around the player as they move and unloads the ones left behind
+//
+
+use crate::services::chunk_service::ChunkService;
+use crate::services::chunk_service::workers::{ChunkJob, JobKind};
+use crate::services::settings_service::{SettingsService, CHUNK_SIZE};
+use crate::block::registry::BlockRegistry;
+use crate::world::save::ColumnChunks;
+use cgmath::{Vector2, Vector3};
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Height of the world in chunks, columns are always loaded from 0 up to this
+pub const WORLD_HEIGHT: i32 = 16;
+
+/// Columns are only unloaded once they're this many chunks outside the render distance,
+/// so walking back and forth over a chunk border doesn't keep loading and unloading the same chunks
+pub const UNLOAD_MARGIN: i32 = 2;
+
+/// How many columns are handed to the workers each frame while moving, anything more waits for the next frame
+pub const COLUMNS_PER_FRAME: usize = 4;
+
+impl ChunkService {
+    /// Loads and unloads columns of chunks around the player. Up to budget columns start loading, nearest first,
+    /// and columns the workers have finished generating are lit and sent off to be meshed.
+    pub fn update_streaming(&mut self, player_pos: Vector3<f32>, settings: &SettingsService, blocks: &BlockRegistry, budget: usize) {
+        let column = Vector2 {
+            x: (player_pos.x / CHUNK_SIZE as f32).floor() as i32,
+            y: (player_pos.z / CHUNK_SIZE as f32).floor() as i32
+        };
+
+        if self.player_column != Some(column) {
+            self.player_column = Some(column);
+            self.unload_distant_columns(column, settings);
+            self.queue_columns(column, settings);
+        }
+
+        for _ in 0..budget {
+            let next = match self.load_queue.pop() {
+                Some(next) => next,
+                None => break
+            };
+
+            self.load_column(next);
+        }
+
+        self.receive_jobs(blocks);
+    }
+
+    /// Finds every column in render distance that isn't loaded, sorted so the nearest is at the end of the queue
+    pub fn queue_columns(&mut self, centre: Vector2<i32>, settings: &SettingsService) {
+        let radius = settings.render_distance as i32;
+
+        self.load_queue.clear();
+
+        for x in -radius..=radius {
+            for z in -radius..=radius {
+                let column = centre + Vector2 { x, y: z };
+
+                if x * x + z * z <= radius * radius && !self.loaded_columns.contains(&column) {
+                    self.load_queue.push(column);
+                }
+            }
+        }
+
+        let distance = |column: &Vector2<i32>| (column.x - centre.x).pow(2) + (column.y - centre.y).pow(2);
+        self.load_queue.sort_by_key(|column| std::cmp::Reverse(distance(column)));
+    }
+
+    fn unload_distant_columns(&mut self, centre: Vector2<i32>, settings: &SettingsService) {
+        let radius = settings.render_distance as i32 + UNLOAD_MARGIN;
+
+        let distant: Vec<Vector2<i32>> = self.loaded_columns.iter()
+            .filter(|column| (column.x - centre.x).pow(2) + (column.y - centre.y).pow(2) > radius * radius)
+            .cloned()
+            .collect();
+
+        for column in distant {
+            self.unload_column(column);
+        }
+    }
+
+    /// Starts loading a column of chunks from the save, anything that hasn't been saved is generated once the save has been checked
+    fn load_column(&mut self, column: Vector2<i32>) {
+        if !self.loaded_columns.insert(column) {
+            return;
+        }
+
+        for y in 0..WORLD_HEIGHT {
+            self.pending_generation.insert(Vector3 { x: column.x, y, z: column.y }, Arc::new(AtomicBool::new(false)));
+        }
+
+        self.save.load_column(column);
+
+        // Biomes aren't saved, they come from the seed so can always be generated again
+        self.workers.submit(ChunkJob {
+            position: Vector3 { x: column.x, y: 0, z: column.y },
+            kind: JobKind::Biomes,
+            cancelled: Arc::new(AtomicBool::new(false)),
+            version: 0
+        });
+    }
+
+    /// Adds the chunks of a column that were read from the save, and sends the rest to the workers to be generated
+    pub(crate) fn receive_saved_column(&mut self, column: Vector2<i32>, chunks: Option<ColumnChunks>, blocks: &BlockRegistry) {
+        // Unloaded while it was being read
+        if !self.loaded_columns.contains(&column) {
+            return;
+        }
+
+        for (y, data) in chunks.unwrap_or_default() {
+            let position = Vector3 { x: column.x, y, z: column.y };
+
+            if self.pending_generation.remove(&position).is_some() {
+                self.load_chunk(data, position);
+            }
+        }
+
+        if self.column_generated(column) {
+            self.finish_column(column, blocks);
+            return;
+        }
+
+        // Top down, so the chunks that are lit first arrive first
+        for y in (0..WORLD_HEIGHT).rev() {
+            let position = Vector3 { x: column.x, y, z: column.y };
+
+            if let Some(cancelled) = self.pending_generation.get(&position) {
+                self.workers.submit(ChunkJob {
+                    position,
+                    kind: JobKind::Generate,
+                    cancelled: cancelled.clone(),
+                    version: 0
+                });
+            }
+        }
+    }
+
+    /// Lights a column once all of its chunks have been generated, then meshes it and the columns next to it as their borders have changed
+    pub(crate) fn finish_column(&mut self, column: Vector2<i32>, blocks: &BlockRegistry) {
+        // Light from the top down so sunlight is blocked by the chunks above
+        let mut remesh = HashSet::new();
+        for y in (0..WORLD_HEIGHT).rev() {
+            remesh.extend(self.light_chunk(Vector3 { x: column.x, y, z: column.y }, blocks));
+        }
+
+        for offset in [Vector2 { x: 0, y: 0 }, Vector2 { x: 1, y: 0 }, Vector2 { x: -1, y: 0 }, Vector2 { x: 0, y: 1 }, Vector2 { x: 0, y: -1 }].iter() {
+            let neighbour = column + *offset;
+
+            for y in 0..WORLD_HEIGHT {
+                remesh.insert(Vector3 { x: neighbour.x, y, z: neighbour.y });
+            }
+        }
+
+        for position in remesh {
+            self.request_mesh(position);
+        }
+    }
+
+    /// Whether every chunk in a column has come back from the workers
+    pub(crate) fn column_generated(&self, column: Vector2<i32>) -> bool {
+        (0..WORLD_HEIGHT).all(|y| !self.pending_generation.contains_key(&Vector3 { x: column.x, y, z: column.y }))
+    }
+
+    /// Saves a column if it has changed then drops every chunk in it, which frees their gpu buffers and bind groups,
+    /// and cancels any work still queued for it
+    fn unload_column(&mut self, column: Vector2<i32>) {
+        self.loaded_columns.remove(&column);
+        self.column_biomes.remove(&column);
+
+        if self.unsaved_columns.remove(&column) {
+            self.save_column(column);
+        }
+
+        for y in 0..WORLD_HEIGHT {
+            let position = Vector3 { x: column.x, y, z: column.y };
+
+            if let Some(cancelled) = self.pending_generation.remove(&position) {
+                cancelled.store(true, Ordering::Relaxed);
+            }
+
+            if let Some((_, cancelled)) = self.pending_meshes.remove(&position) {
+                cancelled.store(true, Ordering::Relaxed);
+            }
+
+            if let Some(chunk) = self.chunks.remove(&position) {
+                self.vertices_count -= chunk.vertices_count() as u64;
+            }
+        }
+
+        self.upload_queue.retain(|(position, _)| position.x != column.x || position.z != column.y);
+    }
+}
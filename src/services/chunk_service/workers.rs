@@ -0,0 +1,234 @@
+//
+// Threads that generate and mesh chunks in the background, so the main thread only has to upload the results
+//
+
+use crate::services::chunk_service::ChunkService;
+use crate::services::chunk_service::storage::ChunkStorage;
+use crate::services::chunk_service::mesh::chunk::ChunkMeshData;
+use crate::services::chunk_service::mesh::generation::MeshSnapshot;
+use crate::world::generator::WorldGenerator;
+use crate::block::registry::BlockRegistry;
+use crate::world::save::LoadedColumn;
+use crate::world::biome::BiomeMap;
+use cgmath::{Vector2, Vector3};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Sender, Receiver, TryIter};
+use std::thread;
+use wgpu::Device;
+
+pub const WORKER_THREADS: usize = 4;
+
+/// How many meshes with something in them get gpu buffers each frame, the rest wait for the next frame
+pub const UPLOADS_PER_FRAME: usize = 8;
+
+pub enum JobKind {
+    Generate,
+    /// Biomes for the column the position is in
+    Biomes,
+    Mesh(MeshSnapshot)
+}
+
+pub struct ChunkJob {
+    pub position: Vector3<i32>,
+    pub kind: JobKind,
+    /// Set when the chunk is no longer wanted, workers skip the job if it hasn't started yet
+    pub cancelled: Arc<AtomicBool>,
+    /// Lets the results of an older mesh job be told apart from a newer one for the same chunk
+    pub version: u32
+}
+
+/// Every result is boxed, so moving them around the channel only copies a pointer
+pub enum JobResult {
+    Generated {
+        position: Vector3<i32>,
+        data: Box<ChunkStorage>
+    },
+    Biomes {
+        column: Vector2<i32>,
+        biomes: Box<BiomeMap>
+    },
+    Meshed {
+        position: Vector3<i32>,
+        version: u32,
+        mesh: Box<ChunkMeshData>
+    }
+}
+
+pub struct WorkerPool {
+    jobs: Sender<ChunkJob>,
+    results: Receiver<JobResult>
+}
+
+impl WorkerPool {
+    /// Starts the worker threads, failing if none of them could be started
+    pub fn new(generator: Arc<dyn WorldGenerator>, blocks: Arc<BlockRegistry>) -> Result<WorkerPool, String> {
+        let (job_sender, job_receiver) = channel::<ChunkJob>();
+        let (result_sender, result_receiver) = channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let mut started = 0;
+
+        for i in 0..WORKER_THREADS {
+            let jobs = job_receiver.clone();
+            let results = result_sender.clone();
+            let generator = generator.clone();
+            let blocks = blocks.clone();
+
+            let worker = thread::Builder::new().name(format!("Chunk worker {}", i)).spawn(move || loop {
+                // The lock is only held while waiting for the next job. Workers stop once the pool is dropped.
+                let job = match jobs.lock().unwrap().recv() {
+                    Ok(job) => job,
+                    Err(_) => return
+                };
+
+                if job.cancelled.load(Ordering::Relaxed) {
+                    continue;
+                }
+
+                let result = match job.kind {
+                    JobKind::Generate => JobResult::Generated {
+                        position: job.position,
+                        data: Box::new(generator.generate_chunk(job.position, &blocks))
+                    },
+                    JobKind::Biomes => {
+                        let column = Vector2 { x: job.position.x, y: job.position.z };
+                        JobResult::Biomes {
+                            column,
+                            biomes: Box::new(generator.generate_biomes(column))
+                        }
+                    }
+                    JobKind::Mesh(snapshot) => JobResult::Meshed {
+                        position: job.position,
+                        version: job.version,
+                        mesh: Box::new(snapshot.generate_mesh(&blocks))
+                    }
+                };
+
+                if results.send(result).is_err() {
+                    return;
+                }
+            });
+
+            match worker {
+                Ok(_) => started += 1,
+                Err(e) => {
+                    log_error!("Failed to start chunk worker: {}", e);
+                }
+            }
+        }
+
+        if started == 0 {
+            return Err(String::from("no chunk workers could be started"));
+        }
+
+        Ok(WorkerPool {
+            jobs: job_sender,
+            results: result_receiver
+        })
+    }
+
+    pub fn submit(&self, job: ChunkJob) {
+        if self.jobs.send(job).is_err() {
+            log_error!("Chunk workers have stopped, job dropped");
+        }
+    }
+
+    /// Results that have finished since the last call, without waiting for any others
+    pub fn results(&self) -> TryIter<'_, JobResult> {
+        self.results.try_iter()
+    }
+}
+
+impl ChunkService {
+    /// Queues a loaded chunk to be meshed again, replacing any mesh job it already has waiting.
+    /// Chunks in columns that are still generating are meshed once the whole column has been lit.
+    pub fn request_mesh(&mut self, position: Vector3<i32>) {
+        if !self.column_generated(Vector2 { x: position.x, y: position.z }) {
+            return;
+        }
+
+        let snapshot = match self.mesh_snapshot(position) {
+            Some(snapshot) => snapshot,
+            None => return
+        };
+
+        if let Some((_, cancelled)) = self.pending_meshes.remove(&position) {
+            cancelled.store(true, Ordering::Relaxed);
+        }
+
+        self.mesh_version = self.mesh_version.wrapping_add(1);
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        self.pending_meshes.insert(position, (self.mesh_version, cancelled.clone()));
+        self.workers.submit(ChunkJob {
+            position,
+            kind: JobKind::Mesh(snapshot),
+            cancelled,
+            version: self.mesh_version
+        });
+    }
+
+    /// Takes in everything the workers and the save have finished. Loaded and generated chunks are added and lit once their column is complete,
+    /// and finished meshes wait for their turn to be uploaded.
+    pub(crate) fn receive_jobs(&mut self, blocks: &BlockRegistry) {
+        let saved: Vec<LoadedColumn> = self.save.loaded().collect();
+
+        for loaded in saved {
+            self.receive_saved_column(loaded.column, loaded.chunks, blocks);
+        }
+
+        let results: Vec<JobResult> = self.workers.results().collect();
+
+        for result in results {
+            match result {
+                JobResult::Generated { position, data } => {
+                    // Columns that were unloaded while generating aren't wanted any more
+                    if self.pending_generation.remove(&position).is_none() {
+                        continue;
+                    }
+
+                    self.load_chunk(*data, position);
+
+                    let column = Vector2 { x: position.x, y: position.z };
+                    self.unsaved_columns.insert(column);
+
+                    if self.column_generated(column) {
+                        self.finish_column(column, blocks);
+                    }
+                }
+                JobResult::Biomes { column, biomes } => {
+                    if self.loaded_columns.contains(&column) {
+                        self.column_biomes.insert(column, *biomes);
+                    }
+                }
+                JobResult::Meshed { position, version, mesh } => {
+                    // Older meshes were already out of date when a newer one was asked for
+                    let latest = self.pending_meshes.get(&position).is_some_and(|(latest, _)| *latest == version);
+
+                    if latest {
+                        self.pending_meshes.remove(&position);
+                        self.upload_queue.push_back((position, *mesh));
+                    }
+                }
+            }
+        }
+    }
+
+    /// Creates the gpu buffers for up to budget finished meshes. Empty meshes are cheap so they don't count towards it.
+    pub fn upload_meshes(&mut self, device: &Device, budget: usize) {
+        let mut uploaded = 0;
+
+        while uploaded < budget {
+            let (position, mesh) = match self.upload_queue.pop_front() {
+                Some(upload) => upload,
+                None => break
+            };
+
+            if mesh.vertices_count() > 0 && self.chunks.contains_key(&position) {
+                uploaded += 1;
+            }
+
+            self.upload_mesh(position, mesh, device);
+        }
+    }
+}
@@ -4,10 +4,19 @@ use crate::services::settings_service::SettingsService;
 use crate::services::logging_service::LoggingService;
 use wgpu::{Device, Queue};
 use crate::services::chunk_service::ChunkService;
-use crate::block::Block;
+use crate::block::registry::BlockRegistry;
 use crate::services::asset_service::atlas::atlas_update_blocks;
 use crate::services::ui_service::UIService;
-use cgmath::Point2;
+use crate::world::generator::create_generator;
+use crate::world::save::WorldSave;
+use crate::world::metadata::WorldMetadata;
+use crate::world::biome::BiomeRegistry;
+use crate::world::template::{StructureTemplate, TemplateRegistry, template_directory, valid_template_name};
+use crate::world::anvil::AnvilImporter;
+use cgmath::{Point2, Vector3};
+use std::fs;
+use std::sync::Arc;
+use std::path::{Path, PathBuf};
 use winit::dpi::PhysicalSize;
 
 #[macro_use]
@@ -24,22 +33,24 @@ pub struct Services {
     pub settings: SettingsService,
     pub logging: LoggingService,
     pub chunk: ChunkService,
-    pub ui: UIService
+    pub ui: UIService,
+    pub blocks: Arc<BlockRegistry>,
+    pub biomes: BiomeRegistry,
+    /// Seed, spawn, time and rules of the open world
+    pub world: WorldMetadata
 }
 
 pub struct ServicesContext<'a> {
     pub device: &'a mut Device,
     pub queue: &'a mut Queue,
-    pub blocks: &'a mut Vec<Block>,
     pub size: &'a PhysicalSize<u32>
 }
 
 impl<'a> ServicesContext<'_> {
-    pub fn new(device: &'a mut Device, queue: &'a mut Queue, blocks: &'a mut Vec<Block>, size: &'a PhysicalSize<u32>) -> ServicesContext<'a> {
+    pub fn new(device: &'a mut Device, queue: &'a mut Queue, size: &'a PhysicalSize<u32>) -> ServicesContext<'a> {
         ServicesContext {
             device,
             queue,
-            blocks,
             size
         }
     }
@@ -51,9 +62,45 @@ impl Services {
         let settings = SettingsService::new();
         let logging = LoggingService::new(&settings);
         let asset = AssetService::new(&settings, &mut context);
+        let mut blocks = BlockRegistry::load(&settings);
+        if let Some(models) = asset.models.as_ref() {
+            models.apply_to_blocks(&mut blocks);
+        }
         //TODO: Remove this once we have networking
-        atlas_update_blocks(asset.atlas_index.as_ref().unwrap(), &mut context.blocks);
-        let chunk = ChunkService::new(&settings, &mut context);
+        atlas_update_blocks(asset.atlas_index.as_ref().unwrap(), &mut blocks);
+        // Shared with the chunk workers, so it can't change after this
+        let blocks = Arc::new(blocks);
+        let save_directory = PathBuf::from(format!("{}saves/{}/", settings.path, settings.world_name));
+        let new_save = !save_directory.exists();
+        let mut world = WorldMetadata::load_or_create(&save_directory, &settings);
+        let save = WorldSave::new(save_directory, blocks.clone());
+
+        // Only import into a new save, so an imported world isn't overwritten each time the game starts
+        if let Some(import) = settings.import_world.as_ref().filter(|_| new_save) {
+            match AnvilImporter::new(&blocks).import_world(Path::new(import), &save) {
+                Ok(columns) => {
+                    log!(format!("Imported {} columns from {}", columns, import));
+                }
+                Err(e) => {
+                    log_error!(format!("Failed to import world {}: {}", import, e));
+                }
+            }
+        }
+
+        let biomes = BiomeRegistry::load(&settings);
+        let templates = TemplateRegistry::load(&settings, &blocks);
+        let generator = create_generator(&world, &settings, &blocks, &biomes, &templates);
+
+        // Put the player on the ground in a new world, rather than wherever the default spawn happens to be
+        if new_save && settings.import_world.is_none() {
+            let [x, _, z] = world.spawn;
+            if let Some(y) = generator.spawn_height(x.floor() as i32, z.floor() as i32) {
+                world.spawn[1] = y as f32;
+                world.save();
+            }
+        }
+
+        let chunk = ChunkService::new(blocks.clone(), generator, save, &mut context);
         let audio = AudioService::new();
         let mut ui = UIService::new(&mut context, &asset);
 
@@ -80,7 +127,45 @@ impl Services {
             settings,
             logging,
             chunk,
-            ui
+            ui,
+            blocks,
+            biomes,
+            world
+        }
+    }
+
+    /// Saves every changed column and the world's metadata, and waits for them to be written. Call this before exiting.
+    pub fn save_world(&mut self) {
+        self.chunk.save_world();
+        self.world.save();
+    }
+
+    /// Saves the blocks between two corners as a structure template, which structures in any world can then use.
+    /// Returns false if part of it isn't loaded or it couldn't be written.
+    pub fn save_template(&self, name: &str, from: Vector3<i32>, to: Vector3<i32>) -> bool {
+        if !valid_template_name(name) {
+            log_error!("Can't save structure template {}, names can't be empty or contain path separators or ..", name);
+            return false;
+        }
+
+        let template = match StructureTemplate::capture(from, to, |pos| self.chunk.get_block(pos), &self.blocks) {
+            Some(template) => template,
+            None => {
+                log_error!("Can't save structure template {}, part of it isn't loaded", name);
+                return false;
+            }
+        };
+
+        let directory = PathBuf::from(template_directory(&self.settings));
+        match fs::create_dir_all(&directory).and_then(|_| template.write(&directory.join(format!("{}.nbt", name)))) {
+            Ok(()) => {
+                log!("Saved structure template {}", name);
+                true
+            }
+            Err(e) => {
+                log_error!(format!("Failed to save structure template {}: {}", name, e));
+                false
+            }
         }
     }
 }
@@ -3,6 +3,8 @@
 //
 
 use std::ops::Add;
+use crate::world::generator::flat::DEFAULT_FLAT_PRESET;
+use crate::world::generator::presets::DEFAULT_PRESET;
 
 pub const CHUNK_SIZE: usize = 16;
 
@@ -14,7 +16,19 @@ pub struct SettingsService {
     pub render_distance: u32,
     /// Changes the texture atlas to generate random textures instead
     pub debug_vertices: bool,
-    pub debug_atlas: bool
+    pub debug_atlas: bool,
+    /// Seed for new worlds, a random one is picked if this is None. Existing worlds keep their own seed.
+    pub world_seed: Option<u32>,
+    /// Which generator makes new worlds: noise, flat, void or debug
+    pub world_generator: String,
+    /// Layers used by new flat worlds, from the bottom up, e.g. "stone,2*dirt,grass"
+    pub flat_preset: String,
+    /// Terrain preset used by new noise worlds: default, amplified, islands or one from resources/worldgen/
+    pub terrain_preset: String,
+    /// Folder in saves that the world is loaded from and saved to
+    pub world_name: String,
+    /// A Minecraft Java world folder to import into the save before loading, if the save doesn't exist yet
+    pub import_world: Option<String>
 }
 
 impl SettingsService {
@@ -40,9 +54,15 @@ impl SettingsService {
             path,
             atlas_cache_reading: false,
             atlas_cache_writing: true,
-            render_distance: 1,
+            render_distance: 4,
             debug_vertices,
-            debug_atlas: false
+            debug_atlas: false,
+            world_seed: None,
+            world_generator: String::from("noise"),
+            flat_preset: String::from(DEFAULT_FLAT_PRESET),
+            terrain_preset: String::from(DEFAULT_PRESET),
+            world_name: String::from("world"),
+            import_world: None
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,421 @@
+//
+// Imports Minecraft Java edition worlds from their Anvil region (.mca) files
+//
+
+use crate::nbt::Tag;
+use crate::nbt::read::read_nbt;
+use crate::world::region::{RegionFile, REGION_SIZE};
+use crate::world::save::{ColumnChunks, WorldSave};
+use crate::services::chunk_service::storage::ChunkStorage;
+use crate::services::chunk_service::streaming::WORLD_HEIGHT;
+use crate::services::settings_service::CHUNK_SIZE;
+use crate::block::registry::BlockRegistry;
+use crate::block::state::BlockState;
+use cgmath::Vector2;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Blocks we don't have are replaced with this
+pub const PLACEHOLDER_BLOCK: &str = "rustcraft:unknown";
+
+/// The first data version (1.16) where block state entries stopped being split across two longs
+const PADDED_STATES_VERSION: i64 = 2529;
+
+/// Blocks Minecraft has that are all plain air to us
+const AIR_BLOCKS: [&str; 3] = ["minecraft:air", "minecraft:cave_air", "minecraft:void_air"];
+
+/// Turns Minecraft block names into our block states, remembering the names it couldn't find
+pub struct AnvilImporter<'a> {
+    blocks: &'a BlockRegistry,
+    placeholder: BlockState,
+    pub unknown_blocks: HashSet<String>
+}
+
+impl<'a> AnvilImporter<'a> {
+    pub fn new(blocks: &'a BlockRegistry) -> AnvilImporter<'a> {
+        let placeholder = match blocks.default_state(PLACEHOLDER_BLOCK) {
+            Some(placeholder) => placeholder,
+            None => {
+                log_error!("No {} block registered, unknown blocks will be imported as air", PLACEHOLDER_BLOCK);
+                BlockState::AIR
+            }
+        };
+
+        AnvilImporter {
+            blocks,
+            placeholder,
+            unknown_blocks: HashSet::new()
+        }
+    }
+
+    /// Reads every column in a region file. Columns that can't be read are logged and skipped.
+    pub fn import_region(&mut self, path: &Path) -> io::Result<Vec<(Vector2<i32>, ColumnChunks)>> {
+        let mut region = RegionFile::open_read_only(path)?;
+        let mut columns = Vec::new();
+
+        for x in 0..REGION_SIZE {
+            for z in 0..REGION_SIZE {
+                let data = match region.read_column(Vector2 { x, y: z }) {
+                    Ok(Some(data)) => data,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        log_error!(format!("Failed to read column {}, {} of {}: {}", x, z, path.display(), e));
+                        continue;
+                    }
+                };
+
+                let root = match read_nbt(&mut data.as_slice()) {
+                    Ok((_, root)) => root,
+                    Err(e) => {
+                        log_error!(format!("Column {}, {} of {} isn't valid NBT: {}", x, z, path.display(), e));
+                        continue;
+                    }
+                };
+
+                match self.decode_column(&root) {
+                    Ok(column) => columns.push(column),
+                    Err(e) => {
+                        log_error!(format!("Failed to import column {}, {} of {}: {}", x, z, path.display(), e));
+                    }
+                }
+            }
+        }
+
+        Ok(columns)
+    }
+
+    /// Imports every region in a Minecraft world's region folder straight into our save
+    pub fn import_world(&mut self, world: &Path, save: &WorldSave) -> io::Result<usize> {
+        let mut count = 0;
+
+        for entry in fs::read_dir(world.join("region"))? {
+            let path = entry?.path();
+
+            if path.extension().is_none_or(|extension| extension != "mca") {
+                continue;
+            }
+
+            for (column, chunks) in self.import_region(&path)? {
+                save.save_column(column, chunks);
+                count += 1;
+            }
+        }
+
+        save.flush();
+
+        if !self.unknown_blocks.is_empty() {
+            let mut unknown: Vec<&String> = self.unknown_blocks.iter().collect();
+            unknown.sort();
+            log_error!("Blocks replaced with placeholders: {:?}", unknown);
+        }
+
+        Ok(count)
+    }
+
+    /// Decodes a chunk column from its NBT, handles both the old Level layout and the 1.18 layout
+    pub fn decode_column(&mut self, root: &Tag) -> Result<(Vector2<i32>, ColumnChunks), String> {
+        let data_version = root.get("DataVersion").and_then(Tag::as_i64).unwrap_or(0);
+
+        // Before 1.18 everything was inside Level
+        let level = root.get("Level").unwrap_or(root);
+
+        let position = Vector2 {
+            x: level.get("xPos").and_then(Tag::as_i64).ok_or("missing xPos")? as i32,
+            y: level.get("zPos").and_then(Tag::as_i64).ok_or("missing zPos")? as i32
+        };
+
+        let sections = level.get("Sections").or_else(|| level.get("sections"))
+            .and_then(Tag::as_list)
+            .ok_or("missing sections")?;
+
+        let mut chunks = Vec::new();
+
+        for section in sections {
+            let y = section.get("Y").and_then(Tag::as_i64).ok_or("section missing Y")? as i32;
+
+            // We only have room for the chunks Minecraft had before 1.18 went below 0
+            if !(0..WORLD_HEIGHT).contains(&y) {
+                continue;
+            }
+
+            let (palette, states) = match section.get("block_states") {
+                Some(block_states) => (block_states.get("palette"), block_states.get("data")),
+                None => (section.get("Palette"), section.get("BlockStates"))
+            };
+
+            // Sections that only hold light have no palette
+            let palette = match palette.and_then(Tag::as_list) {
+                Some(palette) => palette,
+                None => continue
+            };
+
+            let states = states.and_then(Tag::as_long_array).map(|states| states.as_slice()).unwrap_or(&[]);
+
+            chunks.push((y, self.decode_section(palette, states, data_version >= PADDED_STATES_VERSION)
+                .map_err(|e| format!("section {}: {}", y, e))?));
+        }
+
+        // Sections Minecraft didn't save are empty, they mustn't be filled in by our generator
+        for y in 0..WORLD_HEIGHT {
+            if !chunks.iter().any(|(chunk_y, _)| *chunk_y == y) {
+                chunks.push((y, ChunkStorage::empty()));
+            }
+        }
+
+        Ok((position, chunks))
+    }
+
+    /// Turns a section's palette and packed block states into a chunk. Minecraft orders blocks y, z, x.
+    fn decode_section(&mut self, palette: &[Tag], states: &[i64], padded: bool) -> Result<ChunkStorage, String> {
+        let palette: Vec<BlockState> = palette.iter().map(|entry| self.block_state(entry)).collect();
+
+        if palette.is_empty() {
+            return Err(String::from("empty palette"));
+        }
+
+        // A palette with one entry doesn't need any block data since 1.18
+        if states.is_empty() {
+            return Ok(ChunkStorage::filled(palette[0]));
+        }
+
+        let volume = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+        let bits = bits_for(palette.len());
+
+        let expected = if padded {
+            let per_long = 64 / bits;
+            volume.div_ceil(per_long)
+        } else {
+            (volume * bits).div_ceil(64)
+        };
+
+        if states.len() != expected {
+            return Err(format!("expected {} longs of block states but found {}", expected, states.len()));
+        }
+
+        let mut storage = ChunkStorage::empty();
+
+        for i in 0..volume {
+            let index = if padded {
+                read_padded(states, bits, i)
+            } else {
+                read_spanning(states, bits, i)
+            };
+
+            let state = *palette.get(index).ok_or_else(|| format!("block state index {} is outside the palette", index))?;
+
+            let x = i % CHUNK_SIZE;
+            let z = (i / CHUNK_SIZE) % CHUNK_SIZE;
+            let y = i / (CHUNK_SIZE * CHUNK_SIZE);
+            storage.set(x, y, z, state);
+        }
+
+        storage.compact();
+
+        Ok(storage)
+    }
+
+    fn block_state(&mut self, entry: &Tag) -> BlockState {
+        let name = entry.get("Name").and_then(Tag::as_str).unwrap_or("minecraft:air");
+
+        if AIR_BLOCKS.contains(&name) {
+            return BlockState::AIR;
+        }
+
+        let block = match self.blocks.get_by_name(name) {
+            Some(block) => block,
+            None => {
+                self.unknown_blocks.insert(name.to_string());
+                return self.placeholder;
+            }
+        };
+
+        // Properties we don't have, like waterlogged, are dropped
+        let mut state = block.default_state();
+        if let Some(properties) = entry.get("Properties").and_then(Tag::as_compound) {
+            for (property, value) in properties {
+                if let Some(value) = value.as_str() {
+                    state = block.with_property(state, property, value);
+                }
+            }
+        }
+
+        state
+    }
+}
+
+/// Minecraft never packs block states into fewer than 4 bits
+fn bits_for(palette_len: usize) -> usize {
+    let mut bits = 4;
+
+    while (1 << bits) < palette_len {
+        bits += 1;
+    }
+
+    bits
+}
+
+/// 1.16 and later, entries never cross into the next long
+fn read_padded(states: &[i64], bits: usize, i: usize) -> usize {
+    let per_long = 64 / bits;
+    let long = states[i / per_long] as u64;
+
+    ((long >> ((i % per_long) * bits)) & ((1 << bits) - 1)) as usize
+}
+
+/// Before 1.16, entries are packed end to end and can be split across two longs
+fn read_spanning(states: &[i64], bits: usize, i: usize) -> usize {
+    let start = i * bits;
+    let long = start / 64;
+    let offset = start % 64;
+    let mask = (1u64 << bits) - 1;
+
+    let mut value = (states[long] as u64) >> offset;
+    if offset + bits > 64 {
+        value |= (states[long + 1] as u64) << (64 - offset);
+    }
+
+    (value & mask) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Written by tests/fixtures/make_anvil_fixture.py, which lists what each column holds
+    const ANVIL_FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/r.0.0.mca");
+
+    /// The fixture's palette, each entry is imported as the block with the same name and property
+    const PALETTE: [(&str, Option<(&str, &str)>); 17] = [
+        ("minecraft:air", None),
+        ("minecraft:stone", None),
+        ("minecraft:dirt", None),
+        ("minecraft:grass_block", None),
+        ("minecraft:sand", None),
+        ("minecraft:sandstone", None),
+        ("minecraft:oak_log", Some(("axis", "x"))),
+        ("minecraft:oak_planks", None),
+        ("minecraft:smooth_stone_slab", Some(("type", "top"))),
+        ("minecraft:glass", None),
+        ("minecraft:cave_air", None),
+        ("minecraft:gravel", None),
+        ("minecraft:coal_ore", None),
+        ("minecraft:iron_ore", None),
+        ("minecraft:gold_ore", None),
+        ("minecraft:not_a_real_block", None),
+        ("minecraft:diamond_ore", None)
+    ];
+
+    fn expected_state(blocks: &BlockRegistry, index: usize) -> BlockState {
+        let (name, property) = PALETTE[index];
+
+        if AIR_BLOCKS.contains(&name) {
+            return BlockState::AIR;
+        }
+
+        match blocks.get_by_name(name) {
+            Some(block) => property.map_or(block.default_state(), |(property, value)| block.with_property(block.default_state(), property, value)),
+            None => blocks.default_state(PLACEHOLDER_BLOCK).unwrap()
+        }
+    }
+
+    /// Every section in the fixture follows the same pattern through its palette
+    fn assert_section(chunk: &ChunkStorage, blocks: &BlockRegistry, palette_len: usize) {
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    let index = (x + 3 * y + 7 * z) % palette_len;
+                    assert_eq!(chunk.get(x, y, z), expected_state(blocks, index), "block {}, {}, {}", x, y, z);
+                }
+            }
+        }
+    }
+
+    fn import_fixture(blocks: &BlockRegistry) -> (Vec<(Vector2<i32>, ColumnChunks)>, HashSet<String>) {
+        let mut importer = AnvilImporter::new(blocks);
+        let columns = importer.import_region(Path::new(ANVIL_FIXTURE)).unwrap();
+
+        (columns, importer.unknown_blocks)
+    }
+
+    fn section(chunks: &ColumnChunks, y: i32) -> &ChunkStorage {
+        &chunks.iter().find(|(chunk_y, _)| *chunk_y == y).unwrap().1
+    }
+
+    #[test]
+    fn imports_both_columns_of_the_fixture() {
+        let blocks = BlockRegistry::built_in();
+        let (columns, _) = import_fixture(&blocks);
+
+        let positions: Vec<Vector2<i32>> = columns.iter().map(|(position, _)| *position).collect();
+        assert_eq!(positions, vec![Vector2 { x: 0, y: 0 }, Vector2 { x: 2, y: 1 }]);
+
+        for (_, chunks) in columns.iter() {
+            assert_eq!(chunks.len(), WORLD_HEIGHT as usize);
+        }
+    }
+
+    #[test]
+    fn spanning_block_states_before_1_16() {
+        let blocks = BlockRegistry::built_in();
+        let (columns, _) = import_fixture(&blocks);
+        let chunks = &columns[0].1;
+
+        // 5 bits spill over from one long into the next, 4 bits never do
+        assert_section(section(chunks, 0), &blocks, 17);
+        assert_section(section(chunks, 1), &blocks, 2);
+
+        // Sections below 0 are dropped and ones that weren't saved are empty
+        assert!(!chunks.iter().any(|(y, _)| *y < 0));
+        assert!(section(chunks, 2).is_empty());
+    }
+
+    #[test]
+    fn padded_block_states_since_1_18() {
+        let blocks = BlockRegistry::built_in();
+        let (columns, _) = import_fixture(&blocks);
+        let chunks = &columns[1].1;
+
+        assert_section(section(chunks, 0), &blocks, 17);
+        assert_eq!(section(chunks, 2).single_value(), blocks.default_state("minecraft:stone"));
+    }
+
+    #[test]
+    fn unknown_blocks_are_imported_as_the_placeholder() {
+        let blocks = BlockRegistry::built_in();
+        let (columns, unknown) = import_fixture(&blocks);
+        let placeholder = blocks.default_state(PLACEHOLDER_BLOCK).unwrap();
+
+        // Palette entry 15 is the unknown block
+        assert_eq!(section(&columns[0].1, 0).get(15, 0, 0), placeholder);
+        assert_ne!(placeholder, BlockState::AIR);
+        assert_eq!(unknown.into_iter().collect::<Vec<String>>(), vec![String::from("minecraft:not_a_real_block")]);
+    }
+
+    #[test]
+    fn read_spanning_joins_entries_split_across_longs() {
+        // The 13th 5 bit entry starts at bit 60, so 4 bits are in the first long and 1 in the second
+        let value: u64 = 0b10110;
+        let states = [((value & 0xF) << 60) as i64, (value >> 4) as i64];
+
+        assert_eq!(read_spanning(&states, 5, 12), 0b10110);
+        assert_eq!(read_spanning(&states, 5, 11), 0);
+    }
+
+    #[test]
+    fn read_padded_skips_the_unused_bits() {
+        // 12 entries of 5 bits fit in a long, the top 4 bits are padding and the 13th entry starts the next long
+        let states = [(0b1111 << 60) | (0b00011 << 55), 0b00101];
+
+        assert_eq!(read_padded(&states, 5, 11), 3);
+        assert_eq!(read_padded(&states, 5, 12), 5);
+    }
+
+    #[test]
+    fn bits_never_go_below_four() {
+        assert_eq!(bits_for(1), 4);
+        assert_eq!(bits_for(16), 4);
+        assert_eq!(bits_for(17), 5);
+    }
+}
@@ -0,0 +1,245 @@
+//
+// Biomes decide what the surface of the terrain is made of and how tall it is. They're loaded from json like blocks.
+//
+
+use crate::services::settings_service::{SettingsService, CHUNK_SIZE};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+
+/// The biomes the game ships with, extra definitions are read from resources/biomes/
+const DEFAULT_BIOMES: &str = include_str!("../../assets/worldgen/biomes.json");
+
+/// Used for columns with no biome information, such as ones made by generators that don't place biomes
+pub const DEFAULT_BIOME: u8 = 0;
+
+/// Where a biome can appear. Land biomes are picked by climate, the others by the height of the terrain.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BiomePlacement {
+    Land,
+    /// Terrain below sea level
+    Ocean,
+    /// Land right next to sea level
+    Beach
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Biome {
+    pub id: u8,
+    pub name: String,
+    #[serde(default = "default_placement")]
+    pub placement: BiomePlacement,
+    /// Where the biome sits in climate space, both 0 to 1
+    #[serde(default)]
+    pub temperature: f64,
+    #[serde(default)]
+    pub humidity: f64,
+    /// Top block of the terrain
+    pub surface: String,
+    /// The blocks under the surface, down to filler_depth, below which it's stone
+    pub filler: String,
+    #[serde(default = "default_filler_depth")]
+    pub filler_depth: i32,
+    /// Multiplies how far land rises above sea level
+    #[serde(default = "default_height_scale")]
+    pub height_scale: f64,
+    /// Blocks added to land height, faded out towards the coast
+    #[serde(default)]
+    pub height_offset: f64,
+    #[serde(default)]
+    pub vegetation: Vegetation
+}
+
+/// Plants that grow on a biome's grass
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Vegetation {
+    /// Trees tried in each column of chunks, a fraction is the chance of one more
+    pub trees: f64,
+    /// Chance of each grass block having tall grass on it
+    pub grass: f64,
+    /// Chance of each grass block having a flower on it
+    pub flowers: f64
+}
+
+fn default_placement() -> BiomePlacement {
+    BiomePlacement::Land
+}
+
+fn default_filler_depth() -> i32 {
+    3
+}
+
+fn default_height_scale() -> f64 {
+    1.0
+}
+
+pub struct BiomeRegistry {
+    biomes: Vec<Biome>,
+    ids: HashMap<u8, usize>
+}
+
+impl BiomeRegistry {
+    pub fn load(settings: &SettingsService) -> BiomeRegistry {
+        let mut biomes = read_biomes("default biomes", DEFAULT_BIOMES);
+
+        // Load any extra definitions in name order so replacements are always applied the same way
+        let path = format!("{}resources/biomes/", settings.path);
+        if let Ok(files) = fs::read_dir(&path) {
+            let mut files: Vec<String> = files
+                .filter_map(|file| file.ok())
+                .map(|file| file.path().to_string_lossy().to_string())
+                .filter(|file| file.ends_with(".json"))
+                .collect();
+            files.sort();
+
+            for file in files {
+                match fs::read_to_string(&file) {
+                    Ok(data) => biomes.append(&mut read_biomes(&file, &data)),
+                    Err(e) => {
+                        log_error!(format!("Failed to read biome definitions {}: {}", file, e));
+                    }
+                }
+            }
+        }
+
+        let registry = BiomeRegistry::new(biomes);
+
+        log!("Loaded {} biomes", registry.biomes.len());
+
+        registry
+    }
+
+    /// Later biomes replace earlier ones with the same id
+    pub fn new(biomes: Vec<Biome>) -> BiomeRegistry {
+        let mut registry = BiomeRegistry {
+            biomes: Vec::new(),
+            ids: HashMap::new()
+        };
+
+        for biome in biomes {
+            match registry.ids.get(&biome.id) {
+                Some(index) => registry.biomes[*index] = biome,
+                None => {
+                    registry.ids.insert(biome.id, registry.biomes.len());
+                    registry.biomes.push(biome);
+                }
+            }
+        }
+
+        registry
+    }
+
+    pub fn get(&self, id: u8) -> Option<&Biome> {
+        self.ids.get(&id).map(|index| &self.biomes[*index])
+    }
+
+    pub fn get_by_name(&self, name: &str) -> Option<&Biome> {
+        self.biomes.iter().find(|biome| biome.name == name)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Biome> {
+        self.biomes.iter()
+    }
+
+    /// The first biome with a placement, used for oceans and beaches
+    pub fn with_placement(&self, placement: BiomePlacement) -> Option<&Biome> {
+        self.biomes.iter().find(|biome| biome.placement == placement)
+    }
+}
+
+#[cfg(test)]
+impl BiomeRegistry {
+    /// Only the biomes that come with the game, for tests that need real biomes
+    pub fn built_in() -> BiomeRegistry {
+        BiomeRegistry::new(read_biomes("default biomes", DEFAULT_BIOMES))
+    }
+}
+
+fn read_biomes(source: &str, data: &str) -> Vec<Biome> {
+    match serde_json::from_str(data) {
+        Ok(biomes) => biomes,
+        Err(e) => {
+            log_error!(format!("Invalid biome definitions in {}: {}", source, e));
+            Vec::new()
+        }
+    }
+}
+
+/// The biome id of every x, z position in a column
+#[derive(Clone)]
+pub struct BiomeMap {
+    ids: [u8; CHUNK_SIZE * CHUNK_SIZE]
+}
+
+impl BiomeMap {
+    pub fn filled(id: u8) -> BiomeMap {
+        BiomeMap {
+            ids: [id; CHUNK_SIZE * CHUNK_SIZE]
+        }
+    }
+
+    pub fn get(&self, x: usize, z: usize) -> u8 {
+        self.ids[x * CHUNK_SIZE + z]
+    }
+
+    pub fn set(&mut self, x: usize, z: usize, id: u8) {
+        self.ids[x * CHUNK_SIZE + z] = id;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::registry::BlockRegistry;
+    use std::collections::HashSet;
+
+    fn biome(id: u8, name: &str) -> Biome {
+        serde_json::from_str(&format!(r#"{{ "id": {}, "name": "{}", "surface": "grass", "filler": "dirt" }}"#, id, name)).unwrap()
+    }
+
+    #[test]
+    fn bundled_biomes_parse_and_validate() {
+        let biomes: Vec<Biome> = serde_json::from_str(DEFAULT_BIOMES).unwrap();
+        let blocks = BlockRegistry::built_in();
+
+        let ids: HashSet<u8> = biomes.iter().map(|biome| biome.id).collect();
+        assert_eq!(ids.len(), biomes.len(), "biome ids are repeated");
+        assert!(ids.contains(&DEFAULT_BIOME));
+
+        for biome in &biomes {
+            assert!(blocks.get_by_name(&biome.surface).is_some(), "{} has an unknown surface", biome.name);
+            assert!(blocks.get_by_name(&biome.filler).is_some(), "{} has an unknown filler", biome.name);
+            assert!((0.0..=1.0).contains(&biome.temperature) && (0.0..=1.0).contains(&biome.humidity), "{} is outside climate space", biome.name);
+            assert!(biome.filler_depth >= 0 && biome.height_scale > 0.0, "{} has a negative size", biome.name);
+        }
+
+        let registry = BiomeRegistry::built_in();
+        assert_eq!(registry.with_placement(BiomePlacement::Ocean).unwrap().name, "ocean");
+        assert_eq!(registry.with_placement(BiomePlacement::Beach).unwrap().name, "beach");
+        assert!(registry.iter().filter(|biome| biome.placement == BiomePlacement::Land).count() > 1);
+    }
+
+    #[test]
+    fn later_biomes_replace_ones_with_the_same_id() {
+        let registry = BiomeRegistry::new(vec![biome(0, "plains"), biome(1, "desert"), biome(0, "meadow")]);
+
+        assert_eq!(registry.iter().count(), 2);
+        assert_eq!(registry.get(0).unwrap().name, "meadow");
+        assert!(registry.get_by_name("plains").is_none());
+        assert_eq!(registry.get_by_name("desert").unwrap().id, 1);
+        assert_eq!(registry.get(0).unwrap().filler_depth, 3);
+    }
+
+    #[test]
+    fn biome_maps_keep_each_column() {
+        let mut map = BiomeMap::filled(2);
+        map.set(15, 0, 7);
+        map.set(0, 15, 9);
+
+        assert_eq!(map.get(15, 0), 7);
+        assert_eq!(map.get(0, 15), 9);
+        assert_eq!(map.get(8, 8), 2);
+    }
+}
@@ -0,0 +1,281 @@
+//
+// Carves caves out of generated terrain. Noise caves come from 3D noise, cheese caves are large open caverns
+// and spaghetti caves are long thin tunnels. Worms are tunnels that wander from a starting point picked per column.
+//
+
+use crate::block::state::BlockState;
+use crate::services::chunk_service::storage::ChunkStorage;
+use crate::services::settings_service::CHUNK_SIZE;
+use crate::world::generator::position_rng;
+use crate::world::generator::presets::{CaveSettings, WormSettings};
+use crate::world::generator::terrain::Fractal;
+use cgmath::Vector3;
+use rand::Rng;
+use std::f64::consts::PI;
+
+/// Salt for the random numbers used by worms, so they don't line up with anything else seeded from the column
+const WORM_SALT: u64 = 1;
+
+/// Surface heights of each x, z in a chunk
+pub type Heights = [[i32; CHUNK_SIZE]; CHUNK_SIZE];
+
+pub struct CaveCarver {
+    seed: u32,
+    settings: CaveSettings,
+    sea_level: i32,
+    cheese: Option<Fractal>,
+    /// Tunnels follow where both of these are near zero
+    spaghetti: Option<(Fractal, Fractal)>
+}
+
+impl CaveCarver {
+    pub fn new(seed: u32, sea_level: i32, settings: &CaveSettings) -> CaveCarver {
+        CaveCarver {
+            seed,
+            settings: settings.clone(),
+            sea_level,
+            cheese: settings.cheese.as_ref().map(|cheese| Fractal::new(seed, 9, &cheese.noise)),
+            spaghetti: settings.spaghetti.as_ref().map(|spaghetti| (Fractal::new(seed, 10, &spaghetti.noise), Fractal::new(seed, 11, &spaghetti.noise)))
+        }
+    }
+
+    /// Replaces solid blocks inside caves with air. Water is never carved, and the sea floor is left sealed.
+    pub fn carve(&self, chunk: &mut ChunkStorage, chunk_pos: Vector3<i32>, heights: &Heights, water: BlockState) {
+        let origin = chunk_pos * CHUNK_SIZE as i32;
+
+        let surface = heights.iter().flat_map(|row| row.iter()).cloned().max().unwrap_or(0);
+
+        if origin.y > self.settings.max_y.min(surface) || origin.y + (CHUNK_SIZE as i32) <= self.settings.min_y {
+            return;
+        }
+
+        self.carve_noise(chunk, origin, heights, water);
+
+        if let Some(worms) = self.settings.worms.as_ref() {
+            self.carve_worms(chunk, chunk_pos, worms, heights, water);
+        }
+    }
+
+    /// The highest y a cave can reach at a column, noise caves keep a few blocks of roof and nothing opens into the sea
+    fn roof(&self, height: i32, noise_cave: bool) -> i32 {
+        let roof = if noise_cave || height < self.sea_level {
+            height - self.settings.surface_margin
+        } else {
+            height
+        };
+
+        roof.min(self.settings.max_y)
+    }
+
+    fn carve_noise(&self, chunk: &mut ChunkStorage, origin: Vector3<i32>, heights: &Heights, water: BlockState) {
+        if self.cheese.is_none() && self.spaghetti.is_none() {
+            return;
+        }
+
+        for (x, row) in heights.iter().enumerate() {
+            for (z, height) in row.iter().enumerate() {
+                let roof = self.roof(*height, true);
+
+                for y_offset in 0..CHUNK_SIZE {
+                    let y = origin.y + y_offset as i32;
+
+                    if y < self.settings.min_y || y > roof {
+                        continue;
+                    }
+
+                    let (x_pos, y_pos, z_pos) = ((origin.x + x as i32) as f64, y as f64, (origin.z + z as i32) as f64);
+
+                    let cheese = match (self.cheese.as_ref(), self.settings.cheese.as_ref()) {
+                        (Some(noise), Some(cheese)) => noise.get3(x_pos, y_pos, z_pos) > cheese.threshold,
+                        _ => false
+                    };
+
+                    let carved = cheese || match (self.spaghetti.as_ref(), self.settings.spaghetti.as_ref()) {
+                        (Some((first, second)), Some(spaghetti)) => {
+                            first.get3(x_pos, y_pos, z_pos).abs() < spaghetti.width && second.get3(x_pos, y_pos, z_pos).abs() < spaghetti.width
+                        }
+                        _ => false
+                    };
+
+                    if carved && chunk.get(x, y_offset, z) != water {
+                        chunk.set(x, y_offset, z, BlockState::AIR);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Every column close enough for its worm to reach this chunk has its worm followed from the start,
+    /// so the same tunnel is carved into each chunk it passes through whichever order they're generated in
+    fn carve_worms(&self, chunk: &mut ChunkStorage, chunk_pos: Vector3<i32>, worms: &WormSettings, heights: &Heights, water: BlockState) {
+        let reach = ((worms.length as f64 + worms.max_radius) / CHUNK_SIZE as f64).ceil() as i32;
+        let min_y = self.settings.min_y;
+        let max_y = self.settings.max_y.max(min_y + 1);
+
+        for source_x in chunk_pos.x - reach..=chunk_pos.x + reach {
+            for source_z in chunk_pos.z - reach..=chunk_pos.z + reach {
+                let mut rng = position_rng(self.seed, Vector3 { x: source_x, y: 0, z: source_z }, WORM_SALT);
+
+                if rng.gen::<f64>() >= worms.chance {
+                    continue;
+                }
+
+                let mut position = [
+                    (source_x * CHUNK_SIZE as i32) as f64 + rng.gen_range(0.0, CHUNK_SIZE as f64),
+                    rng.gen_range(min_y, max_y) as f64,
+                    (source_z * CHUNK_SIZE as i32) as f64 + rng.gen_range(0.0, CHUNK_SIZE as f64)
+                ];
+                let mut yaw = rng.gen_range(0.0, PI * 2.0);
+                let mut pitch: f64 = rng.gen_range(-0.5, 0.5);
+                let radius = rng.gen_range(worms.min_radius, worms.max_radius.max(worms.min_radius + 0.01));
+
+                for step in 0..worms.length {
+                    // Every random number is taken whether or not this chunk is reached, so the path is always the same
+                    yaw += rng.gen_range(-0.3, 0.3);
+                    pitch = (pitch * 0.8 + rng.gen_range(-0.25, 0.25)).clamp(-1.0, 1.0);
+
+                    position[0] += yaw.cos() * pitch.cos();
+                    position[1] += pitch.sin();
+                    position[2] += yaw.sin() * pitch.cos();
+
+                    // Thickest in the middle, narrowing to each end
+                    let size = radius * (0.5 + 0.5 * (step as f64 * PI / worms.length as f64).sin());
+
+                    self.carve_sphere(chunk, chunk_pos, position, size.max(1.0), heights, water);
+                }
+            }
+        }
+    }
+
+    fn carve_sphere(&self, chunk: &mut ChunkStorage, chunk_pos: Vector3<i32>, centre: [f64; 3], radius: f64, heights: &Heights, water: BlockState) {
+        let origin = chunk_pos * CHUNK_SIZE as i32;
+
+        // Local bounds of the sphere clamped to the chunk, empty if they don't overlap
+        let bounds = |axis: usize, origin: i32| {
+            let min = ((centre[axis] - radius).floor() as i32 - origin).max(0);
+            let max = ((centre[axis] + radius).ceil() as i32 - origin).min(CHUNK_SIZE as i32 - 1);
+            (min, max)
+        };
+
+        let (x_range, y_range, z_range) = (bounds(0, origin.x), bounds(1, origin.y), bounds(2, origin.z));
+        if x_range.0 > x_range.1 || y_range.0 > y_range.1 || z_range.0 > z_range.1 {
+            return;
+        }
+
+        for x in x_range.0..=x_range.1 {
+            for z in z_range.0..=z_range.1 {
+                let roof = self.roof(heights[x as usize][z as usize], false);
+
+                for y in y_range.0..=y_range.1 {
+                    let world_y = origin.y + y;
+
+                    if world_y < self.settings.min_y || world_y > roof {
+                        continue;
+                    }
+
+                    let offset = [
+                        (origin.x + x) as f64 + 0.5 - centre[0],
+                        world_y as f64 + 0.5 - centre[1],
+                        (origin.z + z) as f64 + 0.5 - centre[2]
+                    ];
+
+                    if offset[0] * offset[0] + offset[1] * offset[1] + offset[2] * offset[2] > radius * radius {
+                        continue;
+                    }
+
+                    let (x, y, z) = (x as usize, y as usize, z as usize);
+                    if chunk.get(x, y, z) != water {
+                        chunk.set(x, y, z, BlockState::AIR);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STONE: BlockState = BlockState(1);
+    const WATER: BlockState = BlockState(16);
+
+    fn carver(seed: u32) -> CaveCarver {
+        let settings = CaveSettings {
+            worms: Some(WormSettings {
+                chance: 0.5,
+                ..WormSettings::default()
+            }),
+            ..CaveSettings::default()
+        };
+
+        CaveCarver::new(seed, 62, &settings)
+    }
+
+    fn carve(carver: &CaveCarver, chunk_pos: Vector3<i32>) -> ChunkStorage {
+        let mut chunk = ChunkStorage::filled(STONE);
+        carver.carve(&mut chunk, chunk_pos, &[[100; CHUNK_SIZE]; CHUNK_SIZE], WATER);
+        chunk
+    }
+
+    fn blocks(chunk: &ChunkStorage) -> Vec<BlockState> {
+        let mut blocks = Vec::new();
+
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    blocks.push(chunk.get(x, y, z));
+                }
+            }
+        }
+
+        blocks
+    }
+
+    #[test]
+    fn chunks_carve_the_same_whatever_order_they_are_generated_in() {
+        let target = Vector3 { x: 3, y: 3, z: -2 };
+        let alone = blocks(&carve(&carver(77), target));
+        assert!(alone.contains(&BlockState::AIR), "nothing was carved, the test needs a different seed");
+
+        // The same carver is used for every neighbour first, as the generator's would be
+        let carver = carver(77);
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    if (x, y, z) != (0, 0, 0) {
+                        carve(&carver, target + Vector3 { x, y, z });
+                    }
+                }
+            }
+        }
+
+        assert_eq!(blocks(&carve(&carver, target)), alone);
+    }
+
+    #[test]
+    fn worms_cross_chunk_borders() {
+        let carver = carver(77);
+        let position = |x: i32, z: i32| Vector3 { x, y: 3, z };
+
+        // A worm starting in one column carves into its neighbours too, so some border has air on both sides
+        let crosses = (-4..4).any(|x| {
+            let (left, right) = (carve(&carver, position(x, 0)), carve(&carver, position(x + 1, 0)));
+
+            (0..CHUNK_SIZE).any(|y| (0..CHUNK_SIZE).any(|z| left.get(CHUNK_SIZE - 1, y, z).is_air() && right.get(0, y, z).is_air()))
+        });
+
+        assert!(crosses);
+    }
+
+    #[test]
+    fn water_and_chunks_above_the_caves_are_left_alone() {
+        let carver = carver(77);
+
+        let mut sea = ChunkStorage::filled(WATER);
+        carver.carve(&mut sea, Vector3 { x: 0, y: 2, z: 0 }, &[[100; CHUNK_SIZE]; CHUNK_SIZE], WATER);
+        assert_eq!(sea.single_value(), Some(WATER));
+
+        assert_eq!(carve(&carver, Vector3 { x: 0, y: 9, z: 0 }).single_value(), Some(STONE));
+    }
+}
@@ -0,0 +1,127 @@
+//
+// Temperature and humidity noise, which picks the land biome at each position and blends the heights of biomes near each other
+//
+
+use crate::world::biome::Biome;
+use crate::world::generator::presets::TerrainPreset;
+use crate::world::generator::terrain::Fractal;
+
+/// How far apart in climate space biomes can be and still affect each other's height.
+/// Bigger values give wider, smoother transitions.
+const BLEND_DISTANCE: f64 = 0.08;
+
+pub struct ClimateNoise {
+    temperature: Fractal,
+    humidity: Fractal
+}
+
+/// Land biome heights mixed by climate
+pub struct BiomeBlend {
+    /// Index of the nearest land biome, which decides the surface
+    pub nearest: usize,
+    pub height_scale: f64,
+    pub height_offset: f64
+}
+
+impl ClimateNoise {
+    pub fn new(seed: u32, preset: &TerrainPreset) -> ClimateNoise {
+        ClimateNoise {
+            temperature: Fractal::new(seed, 7, &preset.temperature),
+            humidity: Fractal::new(seed, 8, &preset.humidity)
+        }
+    }
+
+    /// Temperature and humidity at a world x and z, from 0 to 1
+    pub fn sample(&self, x: f64, z: f64) -> (f64, f64) {
+        let scale = |value: f64| ((value + 1.0) * 0.5).clamp(0.0, 1.0);
+        (scale(self.temperature.get(x, z)), scale(self.humidity.get(x, z)))
+    }
+
+    /// Weights every land biome by how much closer the nearest one is, so the nearest always has a weight of 1
+    /// and the heights change smoothly as the climate moves from one biome to the next
+    pub fn blend(&self, land: &[Biome], x: f64, z: f64) -> Option<BiomeBlend> {
+        let (temperature, humidity) = self.sample(x, z);
+
+        let distances: Vec<f64> = land.iter()
+            .map(|biome| (biome.temperature - temperature).powi(2) + (biome.humidity - humidity).powi(2))
+            .collect();
+
+        let (nearest, closest) = distances.iter().cloned().enumerate()
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))?;
+
+        let mut total = 0.0;
+        let mut height_scale = 0.0;
+        let mut height_offset = 0.0;
+
+        for (biome, distance) in land.iter().zip(distances) {
+            let weight = (-(distance - closest) / (BLEND_DISTANCE * BLEND_DISTANCE)).exp();
+
+            total += weight;
+            height_scale += biome.height_scale * weight;
+            height_offset += biome.height_offset * weight;
+        }
+
+        Some(BiomeBlend {
+            nearest,
+            height_scale: height_scale / total,
+            height_offset: height_offset / total
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::biome::{BiomePlacement, BiomeRegistry};
+    use crate::world::generator::presets::{built_in_preset, DEFAULT_PRESET};
+
+    fn land_biomes() -> Vec<Biome> {
+        BiomeRegistry::built_in().iter()
+            .filter(|biome| biome.placement == BiomePlacement::Land)
+            .cloned()
+            .collect()
+    }
+
+    #[test]
+    fn climate_stays_between_zero_and_one() {
+        let climate = ClimateNoise::new(99, &built_in_preset(DEFAULT_PRESET));
+
+        for x in (-5000..5000).step_by(97) {
+            for z in (-5000..5000).step_by(89) {
+                let (temperature, humidity) = climate.sample(x as f64, z as f64);
+                assert!((0.0..=1.0).contains(&temperature) && (0.0..=1.0).contains(&humidity));
+            }
+        }
+    }
+
+    #[test]
+    fn blend_picks_the_nearest_biome_and_mixes_heights() {
+        let climate = ClimateNoise::new(99, &built_in_preset(DEFAULT_PRESET));
+        let land = land_biomes();
+        let lowest = land.iter().map(|biome| biome.height_scale).fold(f64::MAX, f64::min);
+        let highest = land.iter().map(|biome| biome.height_scale).fold(f64::MIN, f64::max);
+
+        for x in (-3000..3000).step_by(131) {
+            let (x, z) = (x as f64, x as f64 * 0.7);
+            let blend = climate.blend(&land, x, z).unwrap();
+            let (temperature, humidity) = climate.sample(x, z);
+
+            let distance = |biome: &Biome| (biome.temperature - temperature).powi(2) + (biome.humidity - humidity).powi(2);
+            assert!(land.iter().all(|biome| distance(&land[blend.nearest]) <= distance(biome)));
+            assert!(blend.height_scale >= lowest - 1e-9 && blend.height_scale <= highest + 1e-9);
+        }
+
+        assert!(climate.blend(&[], 0.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn one_biome_keeps_its_own_heights() {
+        let climate = ClimateNoise::new(5, &built_in_preset(DEFAULT_PRESET));
+        let land = vec![land_biomes().remove(0)];
+        let blend = climate.blend(&land, 120.0, -40.0).unwrap();
+
+        assert_eq!(blend.nearest, 0);
+        assert!((blend.height_scale - land[0].height_scale).abs() < 1e-9);
+        assert!((blend.height_offset - land[0].height_offset).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,78 @@
+//
+// Lays every state of every block out in a grid, so they can all be checked in one place
+//
+
+use crate::block::registry::BlockRegistry;
+use crate::block::state::BlockState;
+use crate::services::chunk_service::storage::ChunkStorage;
+use crate::services::settings_service::CHUNK_SIZE;
+use crate::world::generator::WorldGenerator;
+use cgmath::Vector3;
+
+/// Height of the grid
+const GRID_Y: i32 = 70;
+
+/// Blocks are placed every other block so each can be seen from all sides
+const SPACING: i32 = 2;
+
+pub struct DebugGenerator {
+    seed: u32
+}
+
+impl DebugGenerator {
+    pub fn new(seed: u32) -> DebugGenerator {
+        DebugGenerator {
+            seed
+        }
+    }
+}
+
+impl WorldGenerator for DebugGenerator {
+    fn name(&self) -> &str {
+        "debug"
+    }
+
+    fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    fn generate_chunk(&self, chunk_pos: Vector3<i32>, blocks: &BlockRegistry) -> ChunkStorage {
+        let mut chunk = ChunkStorage::empty();
+        let size = CHUNK_SIZE as i32;
+
+        if GRID_Y.div_euclid(size) != chunk_pos.y {
+            return chunk;
+        }
+
+        let states: Vec<BlockState> = blocks.blocks().iter()
+            .flat_map(|block| (0..block.state_count()).map(move |state| BlockState::new(block.id, state)))
+            .collect();
+
+        // Square grid starting at 0, 0
+        let width = (states.len() as f64).sqrt().ceil() as i32;
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let world_x = chunk_pos.x * size + x as i32;
+                let world_z = chunk_pos.z * size + z as i32;
+
+                if world_x < 0 || world_z < 0 || world_x % SPACING != 0 || world_z % SPACING != 0 {
+                    continue;
+                }
+
+                let (column, row) = (world_x / SPACING, world_z / SPACING);
+                if column >= width {
+                    continue;
+                }
+
+                if let Some(state) = states.get((row * width + column) as usize) {
+                    chunk.set(x, GRID_Y.rem_euclid(size) as usize, z, *state);
+                }
+            }
+        }
+
+        chunk.compact();
+
+        chunk
+    }
+}
@@ -0,0 +1,295 @@
+//
+// Grows trees, tall grass and flowers on generated terrain. Trees can spill into the chunks around the one they grow from,
+// so every chunk works out all the trees that could reach it from the height map, the same way whichever chunk comes first.
+//
+
+use crate::block::registry::BlockRegistry;
+use crate::block::state::BlockState;
+use crate::services::chunk_service::storage::ChunkStorage;
+use crate::services::settings_service::CHUNK_SIZE;
+use crate::world::biome::Biome;
+use crate::world::generator::position_rng;
+use crate::world::generator::caves::Heights;
+use cgmath::Vector3;
+use rand::Rng;
+use rand::rngs::StdRng;
+
+const TREE_SALT: u64 = 200;
+const PLANT_SALT: u64 = 201;
+
+/// Flowers that can grow on grass, one is picked at random
+const FLOWERS: [&str; 2] = ["minecraft:poppy", "minecraft:dandelion"];
+
+/// How far leaves reach out from a trunk
+const LEAF_RADIUS: i32 = 2;
+
+/// The blocks decoration is made of, looked up once per chunk
+struct DecorationBlocks<'b> {
+    blocks: &'b BlockRegistry,
+    grass_block: BlockState,
+    log: BlockState,
+    leaves: BlockState,
+    tall_grass: Option<BlockState>,
+    flowers: Vec<BlockState>
+}
+
+pub struct Decorator {
+    seed: u32,
+    sea_level: i32
+}
+
+impl Decorator {
+    pub fn new(seed: u32, sea_level: i32) -> Decorator {
+        Decorator {
+            seed,
+            sea_level
+        }
+    }
+
+    /// Decorates a chunk that has had its terrain generated. column gives the surface height and biome at any world x and z,
+    /// and heights are the surface heights of the chunk's own columns.
+    pub fn decorate<'a, F: Fn(i32, i32) -> (i32, Option<&'a Biome>)>(&self, chunk: &mut ChunkStorage, chunk_pos: Vector3<i32>, heights: &Heights, column: F, blocks: &BlockRegistry) {
+        let decoration = match DecorationBlocks::new(blocks) {
+            Some(decoration) => decoration,
+            None => return
+        };
+
+        // Trees first so plants don't grow under them
+        for source_x in chunk_pos.x - 1..=chunk_pos.x + 1 {
+            for source_z in chunk_pos.z - 1..=chunk_pos.z + 1 {
+                self.grow_trees(chunk, chunk_pos, Vector3 { x: source_x, y: 0, z: source_z }, &column, &decoration);
+            }
+        }
+
+        self.grow_plants(chunk, chunk_pos, heights, &column, &decoration);
+    }
+
+    fn grow_trees<'a, F: Fn(i32, i32) -> (i32, Option<&'a Biome>)>(&self, chunk: &mut ChunkStorage, chunk_pos: Vector3<i32>, source: Vector3<i32>, column: &F, decoration: &DecorationBlocks) {
+        let mut rng = position_rng(self.seed, source, TREE_SALT);
+        let origin = Vector3 { x: source.x * CHUNK_SIZE as i32, y: 0, z: source.z * CHUNK_SIZE as i32 };
+
+        // How many trees a column has is decided by the biome in its middle
+        let half = CHUNK_SIZE as i32 / 2;
+        let trees = column(origin.x + half, origin.z + half).1.map_or(0.0, |biome| biome.vegetation.trees);
+        let count = trees.floor() as u32 + if rng.gen::<f64>() < trees.fract() { 1 } else { 0 };
+
+        for _ in 0..count {
+            // Every tree's numbers are taken even if it can't grow, so the rest come out the same
+            let x = origin.x + rng.gen_range(0, CHUNK_SIZE as i32);
+            let z = origin.z + rng.gen_range(0, CHUNK_SIZE as i32);
+            let trunk = rng.gen_range(4, 7);
+            let corners: u32 = rng.gen();
+
+            let (ground, biome) = column(x, z);
+            let on_grass = biome.is_some_and(|biome| decoration.grows_on(biome));
+
+            if on_grass && ground >= self.sea_level {
+                self.place_tree(chunk, chunk_pos, Vector3 { x, y: ground + 1, z }, trunk, corners, decoration);
+            }
+        }
+    }
+
+    /// A trunk with two wide layers of leaves around its top and two narrow layers over it.
+    /// Bits of corners decide which of the outer leaf corners are left off, so trees aren't all the same.
+    fn place_tree(&self, chunk: &mut ChunkStorage, chunk_pos: Vector3<i32>, base: Vector3<i32>, trunk: i32, corners: u32, decoration: &DecorationBlocks) {
+        let top = base.y + trunk - 1;
+
+        for y in top - 2..=top + 1 {
+            let radius = if y < top { LEAF_RADIUS } else { 1 };
+
+            for x in -radius..=radius {
+                for z in -radius..=radius {
+                    let corner = x.abs() == radius && z.abs() == radius;
+                    let corner_bit = (y - top + 2) * 4 + if x > 0 { 1 } else { 0 } + if z > 0 { 2 } else { 0 };
+
+                    // The very top is a plus shape, other corners are random
+                    if corner && (y == top + 1 || corners & (1 << corner_bit) != 0) {
+                        continue;
+                    }
+
+                    set_if_air(chunk, chunk_pos, base + Vector3 { x, y: y - base.y, z }, decoration.leaves);
+                }
+            }
+        }
+
+        for y in 0..trunk {
+            set_block(chunk, chunk_pos, base + Vector3 { x: 0, y, z: 0 }, decoration.log, |state| state == BlockState::AIR || state == decoration.leaves);
+        }
+    }
+
+    /// Grass and flowers never leave the column they grow in, so only this chunk's own columns are needed.
+    /// Plants on ground at the top of the chunk below are skipped, as the ground can't be seen from this chunk.
+    fn grow_plants<'a, F: Fn(i32, i32) -> (i32, Option<&'a Biome>)>(&self, chunk: &mut ChunkStorage, chunk_pos: Vector3<i32>, heights: &Heights, column: &F, decoration: &DecorationBlocks) {
+        let mut rng: StdRng = position_rng(self.seed, Vector3 { x: chunk_pos.x, y: 0, z: chunk_pos.z }, PLANT_SALT);
+        let origin = chunk_pos * CHUNK_SIZE as i32;
+
+        for x in 0..CHUNK_SIZE as i32 {
+            for z in 0..CHUNK_SIZE as i32 {
+                let (roll, flower): (f64, usize) = (rng.gen(), rng.gen_range(0, decoration.flowers.len().max(1)));
+
+                let ground = heights[x as usize][z as usize];
+                let y = ground + 1 - origin.y;
+
+                // The ground has to be in this chunk so it can be checked, a cave might have carved it away
+                if y < 1 || y >= CHUNK_SIZE as i32 || ground < self.sea_level {
+                    continue;
+                }
+
+                let biome = match column(origin.x + x, origin.z + z).1 {
+                    Some(biome) => biome,
+                    None => continue
+                };
+
+                if !decoration.grows_on(biome) {
+                    continue;
+                }
+
+                let vegetation = &biome.vegetation;
+                let plant = if roll < vegetation.flowers {
+                    decoration.flowers.get(flower).cloned()
+                } else if roll < vegetation.flowers + vegetation.grass {
+                    decoration.tall_grass
+                } else {
+                    None
+                };
+
+                let on_grass = chunk.get(x as usize, y as usize - 1, z as usize) == decoration.grass_block;
+
+                if let (Some(plant), true) = (plant, on_grass) {
+                    set_if_air(chunk, chunk_pos, Vector3 { x: origin.x + x, y: ground + 1, z: origin.z + z }, plant);
+                }
+            }
+        }
+    }
+}
+
+impl<'b> DecorationBlocks<'b> {
+    /// None if the blocks trees are made of are missing
+    fn new(blocks: &'b BlockRegistry) -> Option<DecorationBlocks<'b>> {
+        Some(DecorationBlocks {
+            blocks,
+            grass_block: blocks.default_state("minecraft:grass_block")?,
+            log: blocks.default_state("minecraft:oak_log")?,
+            leaves: blocks.default_state("minecraft:oak_leaves")?,
+            tall_grass: blocks.default_state("minecraft:short_grass"),
+            flowers: FLOWERS.iter().filter_map(|name| blocks.default_state(name)).collect()
+        })
+    }
+
+    /// Plants only grow in biomes covered in grass
+    fn grows_on(&self, biome: &Biome) -> bool {
+        self.blocks.default_state(&biome.surface) == Some(self.grass_block)
+    }
+}
+
+fn set_if_air(chunk: &mut ChunkStorage, chunk_pos: Vector3<i32>, pos: Vector3<i32>, state: BlockState) {
+    set_block(chunk, chunk_pos, pos, state, |current| current == BlockState::AIR);
+}
+
+/// Sets a block given in world coordinates if it's inside the chunk and replace allows it
+fn set_block<F: Fn(BlockState) -> bool>(chunk: &mut ChunkStorage, chunk_pos: Vector3<i32>, pos: Vector3<i32>, state: BlockState, replace: F) {
+    let local = pos - chunk_pos * CHUNK_SIZE as i32;
+    let size = CHUNK_SIZE as i32;
+
+    if local.x < 0 || local.y < 0 || local.z < 0 || local.x >= size || local.y >= size || local.z >= size {
+        return;
+    }
+
+    let (x, y, z) = (local.x as usize, local.y as usize, local.z as usize);
+
+    if replace(chunk.get(x, y, z)) {
+        chunk.set(x, y, z, state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GROUND: i32 = 64;
+
+    fn biome(trees: f64, grass: f64) -> Biome {
+        serde_json::from_str(&format!(r#"{{
+            "id": 2, "name": "forest", "surface": "minecraft:grass_block", "filler": "minecraft:dirt",
+            "vegetation": {{ "trees": {}, "grass": {}, "flowers": 0.0 }}
+        }}"#, trees, grass)).unwrap()
+    }
+
+    /// A chunk on flat ground, with grass along its bottom layer if the ground is in it
+    fn decorate(decorator: &Decorator, chunk_pos: Vector3<i32>, biome: &Biome, blocks: &BlockRegistry) -> ChunkStorage {
+        let mut chunk = ChunkStorage::empty();
+        let grass = blocks.default_state("minecraft:grass_block").unwrap();
+
+        if chunk_pos.y * CHUNK_SIZE as i32 == GROUND {
+            for x in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    chunk.set(x, 0, z, grass);
+                }
+            }
+        }
+
+        decorator.decorate(&mut chunk, chunk_pos, &[[GROUND; CHUNK_SIZE]; CHUNK_SIZE], |_, _| (GROUND, Some(biome)), blocks);
+        chunk
+    }
+
+    #[test]
+    fn trees_across_a_border_match_in_either_order() {
+        let blocks = BlockRegistry::built_in();
+        let biome = biome(6.0, 0.0);
+        let log = blocks.default_state("minecraft:oak_log").unwrap();
+        let leaves = blocks.default_state("minecraft:oak_leaves").unwrap();
+        let (left_pos, right_pos) = (Vector3 { x: 0, y: 4, z: 0 }, Vector3 { x: 1, y: 4, z: 0 });
+
+        let decorator = Decorator::new(11, 62);
+        let left = decorate(&decorator, left_pos, &biome, &blocks);
+        let right = decorate(&decorator, right_pos, &biome, &blocks);
+
+        let decorator = Decorator::new(11, 62);
+        let right_first = decorate(&decorator, right_pos, &biome, &blocks);
+        let left_second = decorate(&decorator, left_pos, &biome, &blocks);
+
+        let edge = CHUNK_SIZE - 1;
+        let mut crossing = 0;
+
+        for y in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for (x, first, second) in [(edge, &left, &left_second), (edge - 1, &left, &left_second), (0, &right, &right_first), (1, &right, &right_first)].iter() {
+                    assert_eq!(first.get(*x, y, z), second.get(*x, y, z));
+                }
+
+                // Leaves at the edge of one chunk belong to a trunk in one of the two chunks
+                for (x, chunk) in [(edge, &left), (0, &right)].iter() {
+                    if chunk.get(*x, y, z) != leaves {
+                        continue;
+                    }
+
+                    crossing += 1;
+                    let has_trunk = (y.saturating_sub(3)..=y).any(|trunk_y| (z.saturating_sub(2)..(z + 3).min(CHUNK_SIZE)).any(|trunk_z| {
+                        (edge - 2..CHUNK_SIZE).any(|trunk_x| left.get(trunk_x, trunk_y, trunk_z) == log) ||
+                            (0..3).any(|trunk_x| right.get(trunk_x, trunk_y, trunk_z) == log)
+                    }));
+
+                    assert!(has_trunk, "leaves at {}, {}, {} have no trunk", x, y, z);
+                }
+            }
+        }
+
+        assert!(crossing > 0, "no tree reached the border, the test needs a different seed");
+    }
+
+    #[test]
+    fn plants_only_grow_on_grass_they_can_see() {
+        let blocks = BlockRegistry::built_in();
+        let biome = biome(0.0, 1.0);
+        let tall_grass = blocks.default_state("minecraft:short_grass").unwrap();
+        let decorator = Decorator::new(3, 62);
+
+        let ground = decorate(&decorator, Vector3 { x: 0, y: 4, z: 0 }, &biome, &blocks);
+        assert_eq!(ground.get(5, 1, 5), tall_grass);
+
+        // The ground is in the chunk below, so nothing is grown at the bottom of this one
+        let mut above = ChunkStorage::empty();
+        decorator.decorate(&mut above, Vector3 { x: 0, y: 4, z: 0 }, &[[GROUND - 1; CHUNK_SIZE]; CHUNK_SIZE], |_, _| (GROUND - 1, Some(&biome)), &blocks);
+        assert!(above.is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+//
+// Superflat worlds made of horizontal layers, mostly used for testing
+//
+
+use crate::block::registry::BlockRegistry;
+use crate::block::state::BlockState;
+use crate::services::chunk_service::storage::ChunkStorage;
+use crate::services::settings_service::CHUNK_SIZE;
+use crate::world::generator::WorldGenerator;
+use cgmath::Vector3;
+
+/// The layers used when a preset can't be read
+pub const DEFAULT_FLAT_PRESET: &str = "stone,2*dirt,grass";
+
+/// A layer of one block, some number of blocks thick
+#[derive(Clone, Debug)]
+pub struct FlatLayer {
+    pub block: String,
+    pub thickness: u32
+}
+
+pub struct FlatGenerator {
+    seed: u32,
+    /// The state and thickness of each layer from the bottom of the world (y = 0) upwards, unknown blocks are left as air
+    layers: Vec<(BlockState, u32)>
+}
+
+impl FlatGenerator {
+    pub fn new(seed: u32, layers: Vec<FlatLayer>, blocks: &BlockRegistry) -> FlatGenerator {
+        let layers = layers.into_iter().map(|layer| {
+            let state = blocks.default_state(&layer.block).unwrap_or_else(|| {
+                log_error!("Flat world layer uses unknown block {}", layer.block);
+                BlockState::AIR
+            });
+
+            (state, layer.thickness)
+        }).collect();
+
+        FlatGenerator {
+            seed,
+            layers
+        }
+    }
+
+    /// Reads layers written bottom to top like "stone,2*dirt,grass", each optionally prefixed with how thick it is
+    pub fn from_preset(seed: u32, preset: &str, blocks: &BlockRegistry) -> FlatGenerator {
+        match parse_preset(preset) {
+            Some(layers) => FlatGenerator::new(seed, layers, blocks),
+            None => {
+                log_error!(format!("Invalid flat world preset {}, using {}", preset, DEFAULT_FLAT_PRESET));
+                FlatGenerator::new(seed, parse_preset(DEFAULT_FLAT_PRESET).unwrap(), blocks)
+            }
+        }
+    }
+}
+
+impl WorldGenerator for FlatGenerator {
+    fn name(&self) -> &str {
+        "flat"
+    }
+
+    fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    fn generate_chunk(&self, chunk_pos: Vector3<i32>, _blocks: &BlockRegistry) -> ChunkStorage {
+        let mut chunk = ChunkStorage::empty();
+        let chunk_bottom = chunk_pos.y * CHUNK_SIZE as i32;
+        let mut layer_bottom = 0;
+
+        for (state, thickness) in self.layers.iter() {
+            let layer_top = layer_bottom + *thickness as i32;
+
+            for y in layer_bottom.max(chunk_bottom)..layer_top.min(chunk_bottom + CHUNK_SIZE as i32) {
+                for x in 0..CHUNK_SIZE {
+                    for z in 0..CHUNK_SIZE {
+                        chunk.set(x, (y - chunk_bottom) as usize, z, *state);
+                    }
+                }
+            }
+
+            layer_bottom = layer_top;
+        }
+
+        chunk.compact();
+
+        chunk
+    }
+
+    fn spawn_height(&self, _x: i32, _z: i32) -> Option<i32> {
+        Some(self.layers.iter().map(|(_, thickness)| *thickness as i32).sum())
+    }
+}
+
+fn parse_preset(preset: &str) -> Option<Vec<FlatLayer>> {
+    let mut layers = Vec::new();
+
+    for layer in preset.split(',').map(|layer| layer.trim()).filter(|layer| !layer.is_empty()) {
+        let mut parts = layer.splitn(2, '*');
+        let first = parts.next()?;
+
+        let (thickness, block) = match parts.next() {
+            Some(block) => (first.trim().parse().ok()?, block.trim()),
+            None => (1, first)
+        };
+
+        layers.push(FlatLayer {
+            block: block.to_string(),
+            thickness
+        });
+    }
+
+    if layers.is_empty() {
+        None
+    } else {
+        Some(layers)
+    }
+}
@@ -1,55 +1,89 @@
-use noise::{NoiseFn, Perlin, Seedable};
-use crate::block::Block;
-use crate::services::settings_service::{CHUNK_SIZE};
-use crate::services::chunk_service::chunk::{ChunkData};
-use cgmath::Vector3;
+//
+// World generators, each one decides what a chunk contains from its position and the world seed
+//
 
-pub struct World {
-}
+use crate::block::registry::BlockRegistry;
+use crate::services::chunk_service::storage::ChunkStorage;
+use crate::world::generator::noise::NoiseGenerator;
+use crate::world::generator::flat::FlatGenerator;
+use crate::world::generator::void::VoidGenerator;
+use crate::world::generator::debug::DebugGenerator;
+use crate::world::generator::presets::load_preset;
+use crate::world::metadata::WorldMetadata;
+use crate::services::settings_service::SettingsService;
+use crate::world::biome::{BiomeMap, BiomeRegistry, DEFAULT_BIOME};
+use crate::world::generator::structures::Structure;
+use crate::world::template::TemplateRegistry;
+use cgmath::{Vector2, Vector3};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::sync::Arc;
 
-impl World {
+pub mod noise;
+pub mod flat;
+pub mod void;
+pub mod debug;
+pub mod terrain;
+pub mod climate;
+pub mod caves;
+pub mod ores;
+pub mod decoration;
+pub mod structures;
+pub mod presets;
 
-    pub fn generate_chunk(chunk_pos: Vector3<i32>, blocks: &Vec<Block>) -> ChunkData {
-        let scale = 1.0 / CHUNK_SIZE as f64;
+/// Generates chunks for a world. The same seed and chunk position always give the same chunk,
+/// and generators are shared between threads so chunks can be generated anywhere.
+pub trait WorldGenerator: Send + Sync {
+    /// The name used to pick this generator in the settings
+    fn name(&self) -> &str;
 
-        let noise_map = Perlin::new();
-        noise_map.set_seed(0);
+    fn seed(&self) -> u32;
 
-        let mut chunk = [[[0 as u32; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
-        let blocks: Vec<Block> = (*blocks).to_vec();
+    fn generate_chunk(&self, chunk_pos: Vector3<i32>, blocks: &BlockRegistry) -> ChunkStorage;
 
-        for x in 0..chunk.len() {
-            for z in 0..chunk[0][0].len() {
-                for y_offset in 0..CHUNK_SIZE {
-                    let y = (chunk_pos.y * CHUNK_SIZE as i32) + y_offset as i32;
-                    let height_map = noise_map.get([(x as f64 * scale) + chunk_pos.x as f64, (z as f64 * scale) + chunk_pos.z as f64]);
-                    let height = (height_map * 20.0).round() as i32 + 50;
+    /// The biome of every position in a column, generators without biomes fill it with the default biome
+    fn generate_biomes(&self, _column: Vector2<i32>) -> BiomeMap {
+        BiomeMap::filled(DEFAULT_BIOME)
+    }
 
-                    // Stone
-                    if y < height {
-                        chunk[x][y_offset][z] = 1;
+    /// The lowest y a player can stand at the x and z of a world position, None if there's no ground
+    fn spawn_height(&self, _x: i32, _z: i32) -> Option<i32> {
+        None
+    }
 
-                    // Dirt
-                    } else if y <= (height + 1) {
-                        chunk[x][y_offset][z] = 2;
-                    } else if y == (height + 2) {
-                        chunk[x][y_offset][z] = 3;
-                    }
-                }
-            }
-        }
+    /// Every structure with a bounding box around a world position, whether or not its chunks have been generated
+    fn structures_at(&self, _pos: Vector3<i32>) -> Vec<Arc<Structure>> {
+        Vec::new()
+    }
+}
+
+/// Creates the generator a world was made with, falling back to noise terrain if it doesn't exist.
+/// Terrain presets are looked up in the settings' resources, as they aren't stored in the world, and structures are built from templates.
+pub fn create_generator(world: &WorldMetadata, settings: &SettingsService, blocks: &BlockRegistry, biomes: &BiomeRegistry, templates: &TemplateRegistry) -> Arc<dyn WorldGenerator> {
+    let seed = world.seed;
 
-        // Way to tell X/Z
-        // chunk[0][0][0] = 1;
-        // chunk[1][0][0] = 1;
-        // chunk[2][0][0] = 1;
-        // chunk[3][0][0] = 1;
-        //
-        // chunk[0][0][1] = 2;
-        // chunk[0][0][1] = 2;
-        // chunk[0][0][1] = 2;
-        // chunk[0][0][1] = 2;
-
-        (chunk, blocks)
+    log!(format!("Generating world with {} generator and seed {}", world.generator, seed));
+
+    match world.generator.as_str() {
+        "noise" => Arc::new(NoiseGenerator::new(seed, &load_preset(settings, &world.generator_settings.terrain_preset), biomes, templates)),
+        "flat" => Arc::new(FlatGenerator::from_preset(seed, &world.generator_settings.flat_preset, blocks)),
+        "void" => Arc::new(VoidGenerator::new(seed)),
+        "debug" => Arc::new(DebugGenerator::new(seed)),
+        name => {
+            log_error!("Unknown world generator {}, using noise", name);
+            Arc::new(NoiseGenerator::new(seed, &load_preset(settings, &world.generator_settings.terrain_preset), biomes, templates))
+        }
     }
-}
\ No newline at end of file
+}
+
+/// Random numbers that only depend on the seed and a position, so features come out the same whichever order chunks are generated in.
+/// The salt keeps different features at the same position from getting the same numbers.
+pub fn position_rng(seed: u32, position: Vector3<i32>, salt: u64) -> StdRng {
+    let hash = (seed as u64)
+        ^ (position.x as i64 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (position.y as i64 as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (position.z as i64 as u64).wrapping_mul(0x1656_67B1_9E37_79F9)
+        ^ salt.wrapping_mul(0x27D4_EB2F_1656_67C5);
+
+    StdRng::seed_from_u64(hash)
+}
@@ -0,0 +1,237 @@
+//
+// Natural looking terrain shaped by a terrain preset and covered by biomes, with oceans filled up to sea level,
+// then hollowed out by caves, given ores, decorated with plants and built on
+//
+
+use crate::block::registry::BlockRegistry;
+use crate::block::state::BlockState;
+use crate::services::chunk_service::storage::ChunkStorage;
+use crate::services::chunk_service::streaming::WORLD_HEIGHT;
+use crate::services::settings_service::CHUNK_SIZE;
+use crate::world::biome::{Biome, BiomeMap, BiomePlacement, BiomeRegistry, DEFAULT_BIOME};
+use crate::world::generator::WorldGenerator;
+use crate::world::generator::climate::ClimateNoise;
+use crate::world::generator::caves::{CaveCarver, Heights};
+use crate::world::generator::ores::OrePlacer;
+use crate::world::generator::decoration::Decorator;
+use crate::world::generator::structures::{Structure, StructureGenerator};
+use crate::world::generator::presets::TerrainPreset;
+use crate::world::generator::terrain::TerrainNoise;
+use crate::world::template::TemplateRegistry;
+use cgmath::{Vector2, Vector3};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Biome height offsets fade in over this many blocks above sea level, so coastlines stay where the terrain put them
+const COAST_FADE: f64 = 8.0;
+
+/// The blocks a biome is made of, looked up once per chunk
+struct BiomeBlocks {
+    surface: BlockState,
+    filler: BlockState,
+    filler_depth: i32
+}
+
+pub struct NoiseGenerator {
+    seed: u32,
+    sea_level: i32,
+    terrain: TerrainNoise,
+    climate: ClimateNoise,
+    caves: Option<CaveCarver>,
+    ores: OrePlacer,
+    decorator: Decorator,
+    structures: StructureGenerator,
+    /// Biomes picked by climate
+    land: Vec<Biome>,
+    ocean: Option<Biome>,
+    beach: Option<Biome>
+}
+
+impl NoiseGenerator {
+    pub fn new(seed: u32, preset: &TerrainPreset, biomes: &BiomeRegistry, templates: &TemplateRegistry) -> NoiseGenerator {
+        NoiseGenerator {
+            seed,
+            sea_level: preset.sea_level,
+            terrain: TerrainNoise::new(seed, preset),
+            climate: ClimateNoise::new(seed, preset),
+            caves: preset.caves.as_ref().map(|caves| CaveCarver::new(seed, preset.sea_level, caves)),
+            ores: OrePlacer::new(seed, &preset.ores),
+            decorator: Decorator::new(seed, preset.sea_level),
+            structures: StructureGenerator::new(seed, preset.sea_level, &preset.structures, templates),
+            land: biomes.iter().filter(|biome| biome.placement == BiomePlacement::Land).cloned().collect(),
+            ocean: biomes.with_placement(BiomePlacement::Ocean).cloned(),
+            beach: biomes.with_placement(BiomePlacement::Beach).cloned()
+        }
+    }
+
+    /// The y of the top solid block at a world x and z, kept inside the world, and the biome there
+    pub fn column(&self, x: i32, z: i32) -> (i32, Option<&Biome>) {
+        let (x_pos, z_pos) = (x as f64, z as f64);
+        let mut height = self.terrain.height(x_pos, z_pos);
+
+        let blend = self.climate.blend(&self.land, x_pos, z_pos);
+
+        // Biomes only change land, the sea floor is left to the terrain
+        let relief = height - self.sea_level as f64;
+        if let (Some(blend), true) = (blend.as_ref(), relief > 0.0) {
+            height = self.sea_level as f64 + relief * blend.height_scale + blend.height_offset * (relief / COAST_FADE).min(1.0);
+        }
+
+        let max = WORLD_HEIGHT * CHUNK_SIZE as i32 - 1;
+        let height = (height.round() as i32).max(1).min(max);
+
+        let land = blend.map(|blend| &self.land[blend.nearest]);
+        let biome = if height < self.sea_level {
+            self.ocean.as_ref().or(land)
+        } else if height <= self.sea_level + 1 {
+            self.beach.as_ref().or(land)
+        } else {
+            land
+        };
+
+        (height, biome)
+    }
+}
+
+impl WorldGenerator for NoiseGenerator {
+    fn name(&self) -> &str {
+        "noise"
+    }
+
+    fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    fn generate_chunk(&self, chunk_pos: Vector3<i32>, blocks: &BlockRegistry) -> ChunkStorage {
+        let stone = blocks.default_state("stone").unwrap_or(BlockState::AIR);
+        let water = blocks.default_state("water").unwrap_or(BlockState::AIR);
+
+        let biome_blocks: HashMap<u8, BiomeBlocks> = self.land.iter().chain(self.ocean.iter()).chain(self.beach.iter())
+            .map(|biome| (biome.id, BiomeBlocks {
+                surface: blocks.default_state(&biome.surface).unwrap_or(stone),
+                filler: blocks.default_state(&biome.filler).unwrap_or(stone),
+                filler_depth: biome.filler_depth
+            }))
+            .collect();
+
+        let mut chunk = ChunkStorage::empty();
+        let mut heights: Heights = [[0; CHUNK_SIZE]; CHUNK_SIZE];
+        let bottom = chunk_pos.y * CHUNK_SIZE as i32;
+
+        for (x, row) in heights.iter_mut().enumerate() {
+            for (z, column_height) in row.iter_mut().enumerate() {
+                let (height, biome) = self.column(chunk_pos.x * CHUNK_SIZE as i32 + x as i32, chunk_pos.z * CHUNK_SIZE as i32 + z as i32);
+                *column_height = height;
+
+                // Nothing to place in this chunk, the column is all air here
+                if bottom > height.max(self.sea_level - 1) {
+                    continue;
+                }
+
+                let (surface, filler, filler_depth) = match biome.and_then(|biome| biome_blocks.get(&biome.id)) {
+                    Some(biome) => (biome.surface, biome.filler, biome.filler_depth),
+                    None => (stone, stone, 0)
+                };
+
+                for y_offset in 0..CHUNK_SIZE {
+                    let y = bottom + y_offset as i32;
+
+                    let state = if y > height {
+                        if y < self.sea_level { water } else { continue }
+                    } else if y == height {
+                        surface
+                    } else if y >= height - filler_depth {
+                        filler
+                    } else {
+                        stone
+                    };
+
+                    chunk.set(x, y_offset, z, state);
+                }
+            }
+        }
+
+        if let Some(caves) = self.caves.as_ref() {
+            caves.carve(&mut chunk, chunk_pos, &heights, water);
+        }
+
+        self.ores.place(&mut chunk, chunk_pos, blocks);
+        self.decorator.decorate(&mut chunk, chunk_pos, &heights, |x, z| self.column(x, z), blocks);
+        self.structures.place(&mut chunk, chunk_pos, &heights, |x, z| self.column(x, z), blocks);
+
+        chunk.compact();
+
+        chunk
+    }
+
+    fn generate_biomes(&self, column: Vector2<i32>) -> BiomeMap {
+        let mut biomes = BiomeMap::filled(DEFAULT_BIOME);
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                if let (_, Some(biome)) = self.column(column.x * CHUNK_SIZE as i32 + x as i32, column.y * CHUNK_SIZE as i32 + z as i32) {
+                    biomes.set(x, z, biome.id);
+                }
+            }
+        }
+
+        biomes
+    }
+
+    fn spawn_height(&self, x: i32, z: i32) -> Option<i32> {
+        Some(self.column(x, z).0.max(self.sea_level - 1) + 1)
+    }
+
+    fn structures_at(&self, pos: Vector3<i32>) -> Vec<Arc<Structure>> {
+        self.structures.structures_at(pos, |x, z| self.column(x, z))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::generator::presets::{built_in_preset, DEFAULT_PRESET};
+
+    fn generator(seed: u32) -> NoiseGenerator {
+        NoiseGenerator::new(seed, &built_in_preset(DEFAULT_PRESET), &BiomeRegistry::built_in(), &TemplateRegistry::built_in())
+    }
+
+    #[test]
+    fn columns_only_depend_on_the_seed() {
+        let first = generator(2024);
+        let again = generator(2024);
+        let other = generator(7);
+
+        let mut differs = false;
+        for x in (-500..500).step_by(37) {
+            for z in (-500..500).step_by(41) {
+                let (height, biome) = first.column(x, z);
+                let (same_height, same_biome) = again.column(x, z);
+
+                assert_eq!(height, same_height);
+                assert_eq!(biome.map(|biome| biome.id), same_biome.map(|biome| biome.id));
+                assert!(height >= 1 && height < WORLD_HEIGHT * CHUNK_SIZE as i32);
+
+                differs |= other.column(x, z).0 != height;
+            }
+        }
+
+        assert!(differs);
+    }
+
+    #[test]
+    fn columns_below_sea_level_are_ocean() {
+        let generator = generator(2024);
+
+        for x in (-2000..2000).step_by(53) {
+            let (height, biome) = generator.column(x, x / 3);
+            let placement = biome.unwrap().placement;
+
+            if height < generator.sea_level {
+                assert_eq!(placement, BiomePlacement::Ocean);
+            } else if height > generator.sea_level + 1 {
+                assert_eq!(placement, BiomePlacement::Land);
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+//
+// Places veins of ore through the stone of generated terrain
+//
+
+use crate::block::registry::BlockRegistry;
+use crate::block::state::BlockState;
+use crate::services::chunk_service::storage::ChunkStorage;
+use crate::services::settings_service::CHUNK_SIZE;
+use crate::world::generator::position_rng;
+use crate::world::generator::presets::{OreFeature, HeightDistribution};
+use cgmath::Vector3;
+use rand::Rng;
+use rand::rngs::StdRng;
+use std::f64::consts::PI;
+
+/// Salt for the first feature's random numbers, each feature after it adds one
+const ORE_SALT: u64 = 100;
+
+pub struct OrePlacer {
+    seed: u32,
+    features: Vec<OreFeature>
+}
+
+/// A vein runs along a line, made of spheres of random sizes
+struct Vein {
+    from: [f64; 3],
+    to: [f64; 3],
+    radii: Vec<f64>
+}
+
+impl OrePlacer {
+    pub fn new(seed: u32, features: &[OreFeature]) -> OrePlacer {
+        OrePlacer {
+            seed,
+            features: features.to_vec()
+        }
+    }
+
+    /// Veins are tried from every column that could reach this chunk, with random numbers seeded from that column,
+    /// so veins crossing chunk borders are placed the same whichever order the chunks are generated in
+    pub fn place(&self, chunk: &mut ChunkStorage, chunk_pos: Vector3<i32>, blocks: &BlockRegistry) {
+        let origin = chunk_pos * CHUNK_SIZE as i32;
+
+        for (index, feature) in self.features.iter().enumerate() {
+            let ore = match blocks.default_state(&feature.block) {
+                Some(ore) => ore,
+                None => continue
+            };
+
+            let replace: Vec<BlockState> = feature.replace.iter().filter_map(|name| blocks.default_state(name)).collect();
+
+            let reach = vein_reach(feature.size);
+            if feature.max_y as f64 + reach < origin.y as f64 || feature.min_y as f64 - reach > (origin.y + CHUNK_SIZE as i32) as f64 {
+                continue;
+            }
+
+            let columns = (reach / CHUNK_SIZE as f64).ceil() as i32;
+
+            for source_x in chunk_pos.x - columns..=chunk_pos.x + columns {
+                for source_z in chunk_pos.z - columns..=chunk_pos.z + columns {
+                    let mut rng = position_rng(self.seed, Vector3 { x: source_x, y: 0, z: source_z }, ORE_SALT + index as u64);
+
+                    for _ in 0..feature.attempts_per_column {
+                        // Every vein's numbers are taken even if it misses this chunk, so the rest come out the same
+                        let vein = Vein::random(&mut rng, feature, source_x, source_z);
+                        vein.place(chunk, origin, ore, &replace);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The furthest a block of a vein can be from where it started
+fn vein_reach(size: u32) -> f64 {
+    size as f64 / 8.0 + size as f64 / 16.0 + 3.0
+}
+
+impl Vein {
+    fn random(rng: &mut StdRng, feature: &OreFeature, column_x: i32, column_z: i32) -> Vein {
+        let x = (column_x * CHUNK_SIZE as i32) as f64 + rng.gen_range(0.0, CHUNK_SIZE as f64);
+        let z = (column_z * CHUNK_SIZE as i32) as f64 + rng.gen_range(0.0, CHUNK_SIZE as f64);
+
+        let (min_y, max_y) = (feature.min_y, feature.max_y.max(feature.min_y));
+        let y = match feature.distribution {
+            HeightDistribution::Uniform => rng.gen_range(min_y, max_y + 1),
+            // The average of two uniform numbers is most often in the middle
+            HeightDistribution::Triangular => (rng.gen_range(min_y, max_y + 1) + rng.gen_range(min_y, max_y + 1)) / 2
+        } as f64;
+
+        let angle = rng.gen_range(0.0, PI);
+        let spread = feature.size as f64 / 8.0;
+
+        let from = [x + angle.cos() * spread, y + rng.gen_range(-2.0, 2.0), z + angle.sin() * spread];
+        let to = [x - angle.cos() * spread, y + rng.gen_range(-2.0, 2.0), z - angle.sin() * spread];
+
+        let radii = (0..feature.size).map(|i| {
+            let t = i as f64 / feature.size as f64;
+            ((t * PI).sin() + 1.0) * rng.gen::<f64>() * feature.size as f64 / 16.0 / 2.0 + 0.5
+        }).collect();
+
+        Vein {
+            from,
+            to,
+            radii
+        }
+    }
+
+    fn place(&self, chunk: &mut ChunkStorage, origin: Vector3<i32>, ore: BlockState, replace: &[BlockState]) {
+        let size = self.radii.len() as f64;
+
+        for (i, radius) in self.radii.iter().enumerate() {
+            let t = i as f64 / size;
+            let centre = [
+                self.from[0] + (self.to[0] - self.from[0]) * t - origin.x as f64,
+                self.from[1] + (self.to[1] - self.from[1]) * t - origin.y as f64,
+                self.from[2] + (self.to[2] - self.from[2]) * t - origin.z as f64
+            ];
+
+            // Local bounds of the sphere, clamped to the chunk
+            let bounds = |axis: usize| {
+                let min = (centre[axis] - radius).floor().max(0.0) as i32;
+                let max = (centre[axis] + radius).ceil().min(CHUNK_SIZE as f64 - 1.0) as i32;
+                (min, max)
+            };
+
+            let (x_range, y_range, z_range) = (bounds(0), bounds(1), bounds(2));
+            if x_range.0 > x_range.1 || y_range.0 > y_range.1 || z_range.0 > z_range.1 {
+                continue;
+            }
+
+            for x in x_range.0..=x_range.1 {
+                for y in y_range.0..=y_range.1 {
+                    for z in z_range.0..=z_range.1 {
+                        let offset = [x as f64 + 0.5 - centre[0], y as f64 + 0.5 - centre[1], z as f64 + 0.5 - centre[2]];
+
+                        if offset[0] * offset[0] + offset[1] * offset[1] + offset[2] * offset[2] > radius * radius {
+                            continue;
+                        }
+
+                        let (x, y, z) = (x as usize, y as usize, z as usize);
+                        if replace.contains(&chunk.get(x, y, z)) {
+                            chunk.set(x, y, z, ore);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::generator::presets::{built_in_preset, DEFAULT_PRESET};
+
+    fn placer(seed: u32) -> OrePlacer {
+        OrePlacer::new(seed, &built_in_preset(DEFAULT_PRESET).ores)
+    }
+
+    fn place(placer: &OrePlacer, chunk_pos: Vector3<i32>, blocks: &BlockRegistry) -> Vec<BlockState> {
+        let mut chunk = ChunkStorage::filled(blocks.default_state("minecraft:stone").unwrap());
+        placer.place(&mut chunk, chunk_pos, blocks);
+
+        let mut states = Vec::new();
+        for x in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for z in 0..CHUNK_SIZE {
+                    states.push(chunk.get(x, y, z));
+                }
+            }
+        }
+
+        states
+    }
+
+    #[test]
+    fn bundled_ores_are_known_blocks() {
+        let blocks = BlockRegistry::built_in();
+
+        for feature in &built_in_preset(DEFAULT_PRESET).ores {
+            assert!(blocks.default_state(&feature.block).is_some(), "{} isn't a block", feature.block);
+            assert!(feature.min_y <= feature.max_y && feature.attempts_per_column > 0 && feature.size > 0);
+        }
+    }
+
+    #[test]
+    fn the_same_seed_places_the_same_ores_in_any_order() {
+        let blocks = BlockRegistry::built_in();
+        let target = Vector3 { x: -7, y: 1, z: 12 };
+        let alone = place(&placer(31), target, &blocks);
+
+        let stone = blocks.default_state("minecraft:stone").unwrap();
+        assert!(alone.iter().any(|state| *state != stone), "no ore was placed, the test needs a different seed");
+
+        let placer = placer(31);
+        for x in -1..=1 {
+            for y in -1..=1 {
+                for z in -1..=1 {
+                    place(&placer, target + Vector3 { x, y, z }, &blocks);
+                }
+            }
+        }
+        assert_eq!(place(&placer, target, &blocks), alone);
+
+        assert_ne!(place(&OrePlacer::new(32, &built_in_preset(DEFAULT_PRESET).ores), target, &blocks), alone);
+    }
+
+    #[test]
+    fn attempts_are_per_column_of_chunks() {
+        let blocks = BlockRegistry::built_in();
+        let diamond = blocks.default_state("minecraft:diamond_ore").unwrap();
+        let stone = blocks.default_state("minecraft:stone").unwrap();
+        let feature: OreFeature = serde_json::from_str(r#"{
+            "block": "minecraft:diamond_ore", "size": 8, "attempts": 1, "min_y": 0, "max_y": 127
+        }"#).unwrap();
+        assert_eq!(feature.attempts_per_column, 1);
+
+        // One vein per column, however many chunks the column is split into
+        let placer = OrePlacer::new(5, &[feature]);
+        let columns_with_ore = (0..40).filter(|x| {
+            (0..8).any(|y| place(&placer, Vector3 { x: *x, y, z: 0 }, &blocks).contains(&diamond))
+        }).count();
+        let chunks_with_ore = (0..40).map(|x| {
+            (0..8).filter(|y| place(&placer, Vector3 { x, y: *y, z: 0 }, &blocks).contains(&diamond)).count()
+        }).sum::<usize>();
+
+        assert!(columns_with_ore > 0);
+        assert!(chunks_with_ore < 40 * 3, "too many chunks have ore for one vein per column");
+        assert!(place(&placer, Vector3 { x: 0, y: 9, z: 0 }, &blocks).iter().all(|state| *state == stone));
+    }
+}
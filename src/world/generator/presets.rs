@@ -0,0 +1,413 @@
+//
+// Named terrain presets read from json, so landscapes can be tuned without recompiling
+//
+
+use crate::services::settings_service::SettingsService;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+
+/// The presets the game ships with, extra ones are read from resources/worldgen/ and replace built in ones of the same name
+const DEFAULT_PRESETS: &str = include_str!("../../../assets/worldgen/terrain_presets.json");
+
+/// Ores placed by presets that don't list their own
+const DEFAULT_ORES: &str = include_str!("../../../assets/worldgen/ores.json");
+
+/// Structures built by presets that don't list their own
+const DEFAULT_STRUCTURES: &str = include_str!("../../../assets/worldgen/structures.json");
+
+/// Used when a world asks for a preset that doesn't exist
+pub const DEFAULT_PRESET: &str = "default";
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FractalKind {
+    Fbm,
+    Ridged
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct NoiseSettings {
+    pub kind: FractalKind,
+    pub octaves: u32,
+    /// Frequency of the first octave, in cycles per block
+    pub frequency: f64,
+    #[serde(default = "default_lacunarity")]
+    pub lacunarity: f64,
+    #[serde(default = "default_persistence")]
+    pub persistence: f64
+}
+
+/// A noise layer mapped through a curve of [noise value, blocks] points
+#[derive(Clone, Debug, Deserialize)]
+pub struct CurvedNoise {
+    pub noise: NoiseSettings,
+    pub curve: Vec<[f64; 2]>
+}
+
+/// A noise layer scaled by a fixed number of blocks
+#[derive(Clone, Debug, Deserialize)]
+pub struct ScaledNoise {
+    pub noise: NoiseSettings,
+    pub amplitude: f64
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TerrainPreset {
+    /// Water fills everything below this that isn't terrain
+    pub sea_level: i32,
+    /// Surface height before any of the layers are added
+    pub base_height: f64,
+    /// Added to the height, low values are ocean and high values inland
+    pub continentalness: CurvedNoise,
+    /// Multiplies the peaks, low erosion gives mountains and high erosion flat land
+    pub erosion: CurvedNoise,
+    /// Added to the height after being scaled by erosion, usually ridged
+    pub peaks: CurvedNoise,
+    /// Small bumps added on top of everything else
+    #[serde(default)]
+    pub detail: Option<ScaledNoise>,
+    /// Moves where every other layer is sampled, which bends coastlines and ridges
+    #[serde(default)]
+    pub warp: Option<ScaledNoise>,
+    /// Climate noise that picks land biomes
+    #[serde(default = "default_climate")]
+    pub temperature: NoiseSettings,
+    #[serde(default = "default_climate")]
+    pub humidity: NoiseSettings,
+    /// Left out for the default caves, or null for none at all
+    #[serde(default = "default_caves")]
+    pub caves: Option<CaveSettings>,
+    /// Left out for the default ores, or an empty list for none
+    #[serde(default = "default_ores")]
+    pub ores: Vec<OreFeature>,
+    /// Left out for the default structures, or an empty list for none
+    #[serde(default = "default_structures")]
+    pub structures: Vec<StructureFeature>
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct CaveSettings {
+    /// Caves are only carved between these heights
+    pub min_y: i32,
+    pub max_y: i32,
+    /// Blocks of roof kept above noise caves and under the sea floor
+    pub surface_margin: i32,
+    pub cheese: Option<CheeseCaves>,
+    pub spaghetti: Option<SpaghettiCaves>,
+    pub worms: Option<WormSettings>
+}
+
+/// Open caverns wherever the noise is above the threshold, a higher threshold gives fewer and smaller caverns
+#[derive(Clone, Debug, Deserialize)]
+pub struct CheeseCaves {
+    pub noise: NoiseSettings,
+    pub threshold: f64
+}
+
+/// Tunnels where two noise values are both within width of zero, a higher width gives wider tunnels
+#[derive(Clone, Debug, Deserialize)]
+pub struct SpaghettiCaves {
+    pub noise: NoiseSettings,
+    pub width: f64
+}
+
+/// Tunnels that wander from a random point, started from a column with the given chance
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WormSettings {
+    pub chance: f64,
+    /// Steps of one block each
+    pub length: u32,
+    pub min_radius: f64,
+    pub max_radius: f64
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HeightDistribution {
+    /// Any height in the range is as likely as any other
+    Uniform,
+    /// Most likely in the middle of the range, tapering to nothing at each end
+    Triangular
+}
+
+/// Veins of a block placed through stone
+#[derive(Clone, Debug, Deserialize)]
+pub struct OreFeature {
+    pub block: String,
+    /// Roughly how many blocks are in a vein
+    pub size: u32,
+    /// Veins tried in each column of chunks, spread between min_y and max_y rather than in every chunk of the column
+    #[serde(alias = "attempts")]
+    pub attempts_per_column: u32,
+    pub min_y: i32,
+    pub max_y: i32,
+    #[serde(default = "default_distribution")]
+    pub distribution: HeightDistribution,
+    /// Blocks the vein can replace
+    #[serde(default = "default_replace")]
+    pub replace: Vec<String>
+}
+
+/// A structure built from templates. The world is split into square cells of chunks and each cell has one try at starting it.
+#[derive(Clone, Debug, Deserialize)]
+pub struct StructureFeature {
+    pub name: String,
+    /// Width of the cells in chunks
+    pub spacing: i32,
+    /// Chunks at the far edge of each cell that never have a start, so structures in neighbouring cells aren't too close
+    #[serde(default)]
+    pub separation: i32,
+    /// Biomes the structure can start in, empty for any
+    #[serde(default)]
+    pub biomes: Vec<String>,
+    /// Template built at the start
+    pub start: String,
+    /// Templates built around the start
+    #[serde(default)]
+    pub pieces: Vec<WeightedTemplate>,
+    /// The least and most pieces tried around the start
+    #[serde(default)]
+    pub count: (u32, u32),
+    /// How far in blocks pieces can be from the start
+    #[serde(default)]
+    pub radius: i32,
+    /// Blocks each piece is sunk into the ground
+    #[serde(default)]
+    pub sink: i32
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct WeightedTemplate {
+    pub template: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32
+}
+
+fn default_weight() -> u32 {
+    1
+}
+
+fn default_distribution() -> HeightDistribution {
+    HeightDistribution::Uniform
+}
+
+fn default_replace() -> Vec<String> {
+    vec![String::from("minecraft:stone")]
+}
+
+impl Default for CaveSettings {
+    fn default() -> CaveSettings {
+        CaveSettings {
+            min_y: 4,
+            max_y: 128,
+            surface_margin: 6,
+            cheese: Some(CheeseCaves {
+                noise: NoiseSettings {
+                    kind: FractalKind::Fbm,
+                    octaves: 2,
+                    frequency: 0.02,
+                    lacunarity: default_lacunarity(),
+                    persistence: default_persistence()
+                },
+                threshold: 0.45
+            }),
+            spaghetti: Some(SpaghettiCaves {
+                noise: NoiseSettings {
+                    kind: FractalKind::Fbm,
+                    octaves: 1,
+                    frequency: 0.025,
+                    lacunarity: default_lacunarity(),
+                    persistence: default_persistence()
+                },
+                width: 0.05
+            }),
+            worms: Some(WormSettings::default())
+        }
+    }
+}
+
+impl Default for WormSettings {
+    fn default() -> WormSettings {
+        WormSettings {
+            chance: 0.08,
+            length: 80,
+            min_radius: 1.5,
+            max_radius: 3.0
+        }
+    }
+}
+
+fn default_lacunarity() -> f64 {
+    2.0
+}
+
+fn default_persistence() -> f64 {
+    0.5
+}
+
+/// Biomes a few hundred blocks across
+fn default_climate() -> NoiseSettings {
+    NoiseSettings {
+        kind: FractalKind::Fbm,
+        octaves: 3,
+        frequency: 0.0015,
+        lacunarity: default_lacunarity(),
+        persistence: default_persistence()
+    }
+}
+
+fn default_caves() -> Option<CaveSettings> {
+    Some(CaveSettings::default())
+}
+
+fn default_ores() -> Vec<OreFeature> {
+    match serde_json::from_str(DEFAULT_ORES) {
+        Ok(ores) => ores,
+        Err(e) => {
+            log_error!("Invalid default ore features: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+fn default_structures() -> Vec<StructureFeature> {
+    match serde_json::from_str(DEFAULT_STRUCTURES) {
+        Ok(structures) => structures,
+        Err(e) => {
+            log_error!("Invalid default structures: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+/// Finds a preset by name, falling back to the default one if it doesn't exist
+pub fn load_preset(settings: &SettingsService, name: &str) -> TerrainPreset {
+    let mut presets = load_presets(settings);
+
+    match presets.remove(name) {
+        Some(preset) => preset,
+        None => {
+            log_error!("Unknown terrain preset {}, using default", name);
+
+            presets.remove(DEFAULT_PRESET)
+                .or_else(|| read_presets("default presets", DEFAULT_PRESETS).remove(DEFAULT_PRESET))
+                .expect("Built in terrain presets are missing the default preset")
+        }
+    }
+}
+
+/// Every preset by name, built in ones first so files in resources can replace them
+pub fn load_presets(settings: &SettingsService) -> BTreeMap<String, TerrainPreset> {
+    let mut presets = read_presets("default presets", DEFAULT_PRESETS);
+
+    let path = format!("{}resources/worldgen/", settings.path);
+    if let Ok(files) = fs::read_dir(&path) {
+        let mut files: Vec<String> = files
+            .filter_map(|file| file.ok())
+            .map(|file| file.path().to_string_lossy().to_string())
+            .filter(|file| file.ends_with(".json"))
+            .collect();
+        files.sort();
+
+        for file in files {
+            match fs::read_to_string(&file) {
+                Ok(data) => presets.append(&mut read_presets(&file, &data)),
+                Err(e) => {
+                    log_error!(format!("Failed to read terrain presets {}: {}", file, e));
+                }
+            }
+        }
+    }
+
+    presets
+}
+
+fn read_presets(source: &str, data: &str) -> BTreeMap<String, TerrainPreset> {
+    match serde_json::from_str(data) {
+        Ok(presets) => presets,
+        Err(e) => {
+            log_error!(format!("Invalid terrain presets in {}: {}", source, e));
+            BTreeMap::new()
+        }
+    }
+}
+
+/// One of the presets that come with the game, for tests that need real terrain
+#[cfg(test)]
+pub fn built_in_preset(name: &str) -> TerrainPreset {
+    read_presets("default presets", DEFAULT_PRESETS).remove(name).expect("No built in preset with that name")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::chunk_service::streaming::WORLD_HEIGHT;
+    use crate::services::settings_service::CHUNK_SIZE;
+
+    fn assert_valid_noise(preset: &str, noise: &NoiseSettings) {
+        assert!(noise.octaves >= 1, "{} has a layer without octaves", preset);
+        assert!(noise.frequency > 0.0, "{} has a layer with no frequency", preset);
+        assert!(noise.persistence > 0.0 && noise.persistence < 1.0, "{} has a layer that gets louder each octave", preset);
+    }
+
+    fn assert_valid_curve(preset: &str, curve: &CurvedNoise) {
+        assert_valid_noise(preset, &curve.noise);
+        assert!(curve.curve.len() >= 2, "{} has a curve with less than two points", preset);
+    }
+
+    #[test]
+    fn bundled_presets_parse_and_validate() {
+        let presets: BTreeMap<String, TerrainPreset> = serde_json::from_str(DEFAULT_PRESETS).unwrap();
+        assert!(presets.contains_key(DEFAULT_PRESET));
+
+        let world_top = WORLD_HEIGHT * CHUNK_SIZE as i32;
+
+        for (name, preset) in &presets {
+            assert!(preset.sea_level > 0 && preset.sea_level < world_top, "{} has its sea outside the world", name);
+            assert!(preset.base_height > 0.0 && preset.base_height < world_top as f64, "{} starts outside the world", name);
+
+            assert_valid_curve(name, &preset.continentalness);
+            assert_valid_curve(name, &preset.erosion);
+            assert_valid_curve(name, &preset.peaks);
+            assert_valid_noise(name, &preset.temperature);
+            assert_valid_noise(name, &preset.humidity);
+
+            for layer in preset.detail.iter().chain(preset.warp.iter()) {
+                assert_valid_noise(name, &layer.noise);
+            }
+
+            if let Some(caves) = &preset.caves {
+                assert!(caves.min_y < caves.max_y, "{} has caves with no room", name);
+            }
+        }
+    }
+
+    #[test]
+    fn left_out_settings_use_the_defaults() {
+        let presets = read_presets("test presets", r#"{
+            "flat": {
+                "sea_level": 10,
+                "base_height": 20,
+                "continentalness": { "noise": { "kind": "fbm", "octaves": 1, "frequency": 0.01 }, "curve": [[0, 0]] },
+                "erosion": { "noise": { "kind": "fbm", "octaves": 1, "frequency": 0.01 }, "curve": [[0, 0]] },
+                "peaks": { "noise": { "kind": "ridged", "octaves": 1, "frequency": 0.01 }, "curve": [[0, 0]] },
+                "caves": null
+            }
+        }"#);
+        let preset = &presets["flat"];
+
+        assert_eq!(preset.erosion.noise.lacunarity, 2.0);
+        assert_eq!(preset.erosion.noise.persistence, 0.5);
+        assert!(preset.caves.is_none());
+        assert!(preset.detail.is_none());
+        assert_eq!(preset.temperature.octaves, default_climate().octaves);
+    }
+
+    #[test]
+    fn invalid_presets_are_skipped() {
+        assert!(read_presets("test presets", r#"{ "broken": { "sea_level": "high" } }"#).is_empty());
+    }
+}
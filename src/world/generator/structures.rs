@@ -0,0 +1,464 @@
+//
+// Builds structures like villages out of templates. Each one is worked out in full from its starting cell whenever a chunk
+// it could reach is generated, and only the blocks inside that chunk are placed, so it comes out the same in every chunk.
+//
+
+use crate::block::registry::BlockRegistry;
+use crate::block::state::BlockState;
+use crate::services::chunk_service::storage::ChunkStorage;
+use crate::services::settings_service::CHUNK_SIZE;
+use crate::world::biome::Biome;
+use crate::world::generator::position_rng;
+use crate::world::generator::caves::Heights;
+use crate::world::generator::presets::StructureFeature;
+use crate::world::template::{StructureTemplate, TemplateRegistry, Transform};
+use cgmath::{Vector2, Vector3};
+use rand::Rng;
+use rand::rngs::StdRng;
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::sync::{Arc, Mutex};
+
+/// Salt for the first feature's random numbers, each feature after it adds one
+const STRUCTURE_SALT: u64 = 300;
+
+/// Worked out structures are kept for the chunks around them, once there are this many the least recently used is forgotten
+const MAX_CACHED: usize = 256;
+
+/// Blocks kept clear between the pieces of a structure
+const PIECE_GAP: i32 = 2;
+
+/// Places tried for each piece before it's left out
+const PIECE_TRIES: u32 = 8;
+
+/// The furthest a piece's floor is held up above the ground
+const MAX_FOUNDATION: i32 = 8;
+
+/// A feature's index and one of its cells
+type CellKey = (usize, Vector2<i32>);
+
+/// Structures by feature and cell, None for cells without one, each with when it was last used
+struct StructureCache {
+    entries: HashMap<CellKey, (Option<Arc<Structure>>, u64)>,
+    /// Counts up every time the cache is used
+    clock: u64
+}
+
+/// A box of blocks, both corners inside it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BoundingBox {
+    pub min: Vector3<i32>,
+    pub max: Vector3<i32>
+}
+
+#[derive(Clone, Debug)]
+pub struct StructurePiece {
+    pub template: String,
+    pub transform: Transform,
+    pub bounds: BoundingBox
+}
+
+/// A structure that's been worked out, whether or not the chunks it's in have been generated
+#[derive(Clone, Debug)]
+pub struct Structure {
+    pub name: String,
+    /// Holds every piece
+    pub bounds: BoundingBox,
+    pub pieces: Vec<StructurePiece>
+}
+
+pub struct StructureGenerator {
+    seed: u32,
+    sea_level: i32,
+    features: Vec<StructureFeature>,
+    templates: HashMap<String, StructureTemplate>,
+    /// How many chunks each feature's structures can reach from the chunk they start in
+    reach: Vec<i32>,
+    cache: Mutex<StructureCache>
+}
+
+impl BoundingBox {
+    pub fn new(min: Vector3<i32>, size: Vector3<i32>) -> BoundingBox {
+        BoundingBox {
+            min,
+            max: min + size - Vector3 { x: 1, y: 1, z: 1 }
+        }
+    }
+
+    pub fn chunk(chunk_pos: Vector3<i32>) -> BoundingBox {
+        let size = CHUNK_SIZE as i32;
+        BoundingBox::new(chunk_pos * size, Vector3 { x: size, y: size, z: size })
+    }
+
+    pub fn contains(&self, pos: Vector3<i32>) -> bool {
+        pos.x >= self.min.x && pos.y >= self.min.y && pos.z >= self.min.z && pos.x <= self.max.x && pos.y <= self.max.y && pos.z <= self.max.z
+    }
+
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        self.min.x <= other.max.x && self.max.x >= other.min.x
+            && self.min.y <= other.max.y && self.max.y >= other.min.y
+            && self.min.z <= other.max.z && self.max.z >= other.min.z
+    }
+
+    /// The smallest box holding both
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        BoundingBox {
+            min: Vector3 { x: self.min.x.min(other.min.x), y: self.min.y.min(other.min.y), z: self.min.z.min(other.min.z) },
+            max: Vector3 { x: self.max.x.max(other.max.x), y: self.max.y.max(other.max.y), z: self.max.z.max(other.max.z) }
+        }
+    }
+
+    /// Grown by distance on every side
+    pub fn grow(&self, distance: i32) -> BoundingBox {
+        let distance = Vector3 { x: distance, y: distance, z: distance };
+
+        BoundingBox {
+            min: self.min - distance,
+            max: self.max + distance
+        }
+    }
+}
+
+impl StructureCache {
+    fn new() -> StructureCache {
+        StructureCache {
+            entries: HashMap::new(),
+            clock: 0
+        }
+    }
+
+    /// Outer None if the cell hasn't been worked out yet
+    fn get(&mut self, key: &CellKey) -> Option<Option<Arc<Structure>>> {
+        self.clock += 1;
+        let clock = self.clock;
+
+        self.entries.get_mut(key).map(|(structure, last_used)| {
+            *last_used = clock;
+            structure.clone()
+        })
+    }
+
+    /// Forgets the least recently used structure first if the cache is full, chunks being generated near
+    /// the player keep asking for the same few cells, so those stay cached
+    fn insert(&mut self, key: CellKey, structure: Option<Arc<Structure>>) {
+        self.clock += 1;
+
+        if self.entries.len() >= MAX_CACHED && !self.entries.contains_key(&key) {
+            let oldest = self.entries.iter().min_by_key(|(_, (_, last_used))| *last_used).map(|(key, _)| *key);
+
+            if let Some(oldest) = oldest {
+                self.entries.remove(&oldest);
+            }
+        }
+
+        self.entries.insert(key, (structure, self.clock));
+    }
+}
+
+impl Structure {
+    /// The piece a world position is in, if any
+    pub fn piece_at(&self, pos: Vector3<i32>) -> Option<&StructurePiece> {
+        self.pieces.iter().find(|piece| piece.bounds.contains(pos))
+    }
+}
+
+impl StructureGenerator {
+    pub fn new(seed: u32, sea_level: i32, features: &[StructureFeature], templates: &TemplateRegistry) -> StructureGenerator {
+        let mut used = HashMap::new();
+        let mut reach = Vec::new();
+
+        for feature in features {
+            let names = Some(&feature.start).into_iter().chain(feature.pieces.iter().map(|piece| &piece.template));
+            let mut widest = 0;
+
+            for name in names {
+                match templates.get(name) {
+                    Some(template) => {
+                        widest = widest.max(template.size.x).max(template.size.z);
+                        used.insert(name.clone(), template.clone());
+                    }
+                    None => {
+                        log_error!(format!("Structure {} uses missing template {}", feature.name, name));
+                    }
+                }
+            }
+
+            reach.push(((feature.radius + widest) as f64 / CHUNK_SIZE as f64).ceil() as i32 + 1);
+        }
+
+        StructureGenerator {
+            seed,
+            sea_level,
+            features: features.to_vec(),
+            templates: used,
+            reach,
+            cache: Mutex::new(StructureCache::new())
+        }
+    }
+
+    /// Builds the pieces of every structure that reaches into a chunk. heights are the surface heights of the chunk's columns,
+    /// and column gives the surface height and biome at any world x and z.
+    pub fn place<'a, F: Fn(i32, i32) -> (i32, Option<&'a Biome>)>(&self, chunk: &mut ChunkStorage, chunk_pos: Vector3<i32>, heights: &Heights, column: F, blocks: &BlockRegistry) {
+        // Pieces a little above the chunk can still have their foundations in it
+        let chunk_bounds = BoundingBox::chunk(chunk_pos);
+        let area = BoundingBox { min: chunk_bounds.min, max: chunk_bounds.max + Vector3 { x: 0, y: MAX_FOUNDATION, z: 0 } };
+
+        for structure in self.structures_in(&area, &column) {
+            for piece in structure.pieces.iter().filter(|piece| piece.bounds.intersects(&area)) {
+                self.place_piece(chunk, chunk_pos, piece, heights, blocks);
+            }
+        }
+    }
+
+    /// Every structure with a bounding box around a world position
+    pub fn structures_at<'a, F: Fn(i32, i32) -> (i32, Option<&'a Biome>)>(&self, pos: Vector3<i32>, column: F) -> Vec<Arc<Structure>> {
+        self.structures_in(&BoundingBox { min: pos, max: pos }, &column)
+    }
+
+    fn structures_in<'a, F: Fn(i32, i32) -> (i32, Option<&'a Biome>)>(&self, area: &BoundingBox, column: &F) -> Vec<Arc<Structure>> {
+        let size = CHUNK_SIZE as i32;
+        let mut structures = Vec::new();
+
+        for (index, feature) in self.features.iter().enumerate() {
+            let spacing = feature.spacing.max(1);
+            let reach = self.reach[index];
+
+            // Cells holding any chunk a structure could start in and still reach the area
+            let min_cell = Vector2 { x: (area.min.x.div_euclid(size) - reach).div_euclid(spacing), y: (area.min.z.div_euclid(size) - reach).div_euclid(spacing) };
+            let max_cell = Vector2 { x: (area.max.x.div_euclid(size) + reach).div_euclid(spacing), y: (area.max.z.div_euclid(size) + reach).div_euclid(spacing) };
+
+            for cell_x in min_cell.x..=max_cell.x {
+                for cell_z in min_cell.y..=max_cell.y {
+                    if let Some(structure) = self.structure(index, Vector2 { x: cell_x, y: cell_z }, column) {
+                        if structure.bounds.intersects(area) {
+                            structures.push(structure);
+                        }
+                    }
+                }
+            }
+        }
+
+        structures
+    }
+
+    /// The structure a feature has in a cell, worked out the first time it's asked for
+    fn structure<'a, F: Fn(i32, i32) -> (i32, Option<&'a Biome>)>(&self, index: usize, cell: Vector2<i32>, column: &F) -> Option<Arc<Structure>> {
+        if let Some(structure) = self.cache.lock().unwrap().get(&(index, cell)) {
+            return structure;
+        }
+
+        // Other threads might be doing the same one, but they'll get the same structure
+        let structure = self.assemble(index, cell, column).map(Arc::new);
+        self.cache.lock().unwrap().insert((index, cell), structure.clone());
+
+        structure
+    }
+
+    /// Picks where in a cell a structure starts, then places pieces around the start piece wherever they don't overlap
+    fn assemble<'a, F: Fn(i32, i32) -> (i32, Option<&'a Biome>)>(&self, index: usize, cell: Vector2<i32>, column: &F) -> Option<Structure> {
+        let feature = &self.features[index];
+        let mut rng = position_rng(self.seed, Vector3 { x: cell.x, y: 0, z: cell.y }, STRUCTURE_SALT + index as u64);
+
+        let spacing = feature.spacing.max(1);
+        let spread = (spacing - feature.separation).max(1);
+        let start_x = (cell.x * spacing + rng.gen_range(0, spread)) * CHUNK_SIZE as i32 + CHUNK_SIZE as i32 / 2;
+        let start_z = (cell.y * spacing + rng.gen_range(0, spread)) * CHUNK_SIZE as i32 + CHUNK_SIZE as i32 / 2;
+
+        let biome = column(start_x, start_z).1;
+        if !feature.biomes.is_empty() && !biome.is_some_and(|biome| feature.biomes.contains(&biome.name)) {
+            return None;
+        }
+
+        let start = self.piece(&feature.start, start_x, start_z, feature.sink, &mut rng, column)?;
+        let mut bounds = start.bounds;
+        let mut pieces = vec![start];
+
+        let total_weight: u32 = feature.pieces.iter().map(|piece| piece.weight).sum();
+        let (min_count, max_count) = feature.count;
+        let count = if total_weight > 0 { rng.gen_range(min_count, max_count.max(min_count) + 1) } else { 0 };
+
+        for _ in 0..count {
+            for _ in 0..PIECE_TRIES {
+                let mut roll = rng.gen_range(0, total_weight);
+                let template = feature.pieces.iter().find(|piece| {
+                    let picked = roll < piece.weight;
+                    roll = roll.saturating_sub(piece.weight);
+                    picked
+                }).map(|piece| &piece.template);
+
+                let angle = rng.gen_range(0.0, PI * 2.0);
+                let distance = rng.gen_range(0.0, feature.radius.max(1) as f64);
+                let x = start_x + (angle.cos() * distance).round() as i32;
+                let z = start_z + (angle.sin() * distance).round() as i32;
+
+                let piece = match template.and_then(|template| self.piece(template, x, z, feature.sink, &mut rng, column)) {
+                    Some(piece) => piece,
+                    None => continue
+                };
+
+                if pieces.iter().all(|other| !other.bounds.grow(PIECE_GAP).intersects(&piece.bounds)) {
+                    bounds = bounds.union(&piece.bounds);
+                    pieces.push(piece);
+                    break;
+                }
+            }
+        }
+
+        Some(Structure {
+            name: feature.name.clone(),
+            bounds,
+            pieces
+        })
+    }
+
+    /// A piece turned at random and centred on x, z, standing on the ground there. None if it would be under water.
+    fn piece<'a, F: Fn(i32, i32) -> (i32, Option<&'a Biome>)>(&self, template_name: &str, x: i32, z: i32, sink: i32, rng: &mut StdRng, column: &F) -> Option<StructurePiece> {
+        let template = self.templates.get(template_name)?;
+        let transform = Transform {
+            rotation: rng.gen_range(0, 4),
+            mirror: rng.gen()
+        };
+
+        let ground = column(x, z).0;
+        if ground < self.sea_level {
+            return None;
+        }
+
+        let size = transform.size(template.size);
+        let min = Vector3 { x: x - size.x / 2, y: ground + 1 - sink, z: z - size.z / 2 };
+
+        Some(StructurePiece {
+            template: template_name.to_string(),
+            transform,
+            bounds: BoundingBox::new(min, size)
+        })
+    }
+
+    /// Sets the blocks of a piece that are inside the chunk, then fills in any gap between the piece's floor and the ground
+    fn place_piece(&self, chunk: &mut ChunkStorage, chunk_pos: Vector3<i32>, piece: &StructurePiece, heights: &Heights, blocks: &BlockRegistry) {
+        let template = match self.templates.get(&piece.template) {
+            Some(template) => template,
+            None => return
+        };
+
+        let palette: Vec<Option<BlockState>> = template.palette_states(blocks).into_iter()
+            .map(|state| state.map(|state| piece.transform.apply_state(state, blocks)))
+            .collect();
+
+        let origin = chunk_pos * CHUNK_SIZE as i32;
+        let size = CHUNK_SIZE as i32;
+
+        for block in &template.blocks {
+            let state = match palette[block.state] {
+                Some(state) => state,
+                None => continue
+            };
+
+            let local = piece.bounds.min + piece.transform.apply(block.pos, template.size) - origin;
+            if local.x < 0 || local.z < 0 || local.x >= size || local.z >= size {
+                continue;
+            }
+
+            if local.y >= 0 && local.y < size {
+                chunk.set(local.x as usize, local.y as usize, local.z as usize, state);
+            }
+
+            // Solid floor blocks are held up by more of the same block
+            if block.pos.y == 0 && !blocks.is_transparent(state) {
+                let ground = heights[local.x as usize][local.z as usize] - origin.y;
+                let bottom = (ground + 1).max(local.y - MAX_FOUNDATION).max(0);
+
+                for y in bottom..local.y.min(size) {
+                    chunk.set(local.x as usize, y as usize, local.z as usize, state);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::biome::BiomeRegistry;
+    use crate::world::generator::presets::{built_in_preset, DEFAULT_PRESET};
+
+    fn area(min: (i32, i32, i32), max: (i32, i32, i32)) -> BoundingBox {
+        BoundingBox { min: Vector3 { x: min.0, y: min.1, z: min.2 }, max: Vector3 { x: max.0, y: max.1, z: max.2 } }
+    }
+
+    #[test]
+    fn boxes_touching_at_an_edge_intersect() {
+        let a = area((0, 0, 0), (3, 3, 3));
+
+        assert!(a.intersects(&area((3, 3, 3), (5, 5, 5))));
+        assert!(a.intersects(&area((-2, 1, 1), (0, 2, 2))));
+        assert!(a.intersects(&area((1, 1, 1), (2, 2, 2))));
+        assert!(!a.intersects(&area((4, 0, 0), (6, 3, 3))));
+        assert!(!a.intersects(&area((0, -3, 0), (3, -1, 3))));
+        assert!(!a.intersects(&area((0, 0, 4), (3, 3, 4))));
+    }
+
+    #[test]
+    fn union_holds_both_boxes() {
+        let a = area((0, 5, -2), (3, 6, 1));
+        let b = area((-4, 2, 0), (1, 3, 7));
+        let union = a.union(&b);
+
+        assert_eq!(union, area((-4, 2, -2), (3, 6, 7)));
+        assert_eq!(b.union(&a), union);
+        assert!(union.contains(a.min) && union.contains(a.max) && union.contains(b.min) && union.contains(b.max));
+        assert_eq!(a.union(&a), a);
+    }
+
+    #[test]
+    fn chunk_boxes_grow_on_every_side() {
+        let size = CHUNK_SIZE as i32;
+        let chunk = BoundingBox::chunk(Vector3 { x: -1, y: 2, z: 0 });
+
+        assert_eq!(chunk, area((-size, 2 * size, 0), (-1, 3 * size - 1, size - 1)));
+        assert_eq!(chunk.grow(2), area((-size - 2, 2 * size - 2, -2), (1, 3 * size + 1, size + 1)));
+        assert!(!chunk.contains(Vector3 { x: 0, y: 2 * size, z: 0 }));
+    }
+
+    #[test]
+    fn chunks_share_the_structures_crossing_them() {
+        let biomes = BiomeRegistry::built_in();
+        let plains = biomes.get_by_name("plains").unwrap();
+        let column = |_, _| (70, Some(plains));
+        let generator = StructureGenerator::new(1234, 62, &built_in_preset(DEFAULT_PRESET).structures, &TemplateRegistry::built_in());
+        let size = CHUNK_SIZE as i32;
+
+        // Any structure spreading over more than one chunk will do
+        let structure = (-8..8).flat_map(|x| (-8..8).map(move |z| Vector2 { x, y: z }))
+            .flat_map(|cell| (0..generator.features.len()).map(move |index| (index, cell)))
+            .filter_map(|(index, cell)| generator.structure(index, cell, &column))
+            .find(|structure| structure.bounds.min.x.div_euclid(size) != structure.bounds.max.x.div_euclid(size))
+            .expect("no structure crosses a chunk border");
+
+        let first = BoundingBox::chunk(structure.bounds.min.map(|value| value.div_euclid(size)));
+        let second = BoundingBox::chunk(structure.bounds.max.map(|value| value.div_euclid(size)));
+
+        let find = |chunk: &BoundingBox| generator.structures_in(chunk, &column).into_iter()
+            .find(|other| Arc::ptr_eq(other, &structure));
+        assert!(find(&first).is_some() && find(&second).is_some(), "{} isn't found from both chunks", structure.name);
+    }
+
+    #[test]
+    fn cache_forgets_the_least_recently_used() {
+        let mut cache = StructureCache::new();
+        let key = |x| (0, Vector2 { x, y: 0 });
+
+        for x in 0..MAX_CACHED as i32 {
+            cache.insert(key(x), None);
+        }
+
+        // The first one is used again, so the second is now the oldest
+        assert!(cache.get(&key(0)).is_some());
+        cache.insert(key(-1), None);
+
+        assert_eq!(cache.entries.len(), MAX_CACHED);
+        assert!(cache.get(&key(0)).is_some());
+        assert!(cache.get(&key(1)).is_none());
+        assert!(cache.get(&key(-1)).is_some());
+    }
+}
@@ -0,0 +1,210 @@
+//
+// The noise behind the shape of the terrain. Continentalness decides between ocean and land, erosion how
+// rough the land is, and ridged peaks add mountains where erosion allows, all sampled through a warped domain.
+//
+
+use ::noise::{NoiseFn, Perlin, Seedable};
+use crate::world::generator::presets::{TerrainPreset, NoiseSettings, FractalKind};
+
+/// Several octaves of perlin noise added together, each at a higher frequency and lower amplitude than the last
+pub struct Fractal {
+    kind: FractalKind,
+    octaves: Vec<Perlin>,
+    frequency: f64,
+    lacunarity: f64,
+    persistence: f64,
+    /// Sum of the octave amplitudes, so the result stays in range however many octaves there are
+    total_amplitude: f64
+}
+
+impl Fractal {
+    /// Each octave gets its own seed, starting from seed + salt, so layers made from the same world seed differ
+    pub fn new(seed: u32, salt: u32, settings: &NoiseSettings) -> Fractal {
+        let octaves = settings.octaves.max(1);
+        let base_seed = seed.wrapping_add(salt.wrapping_mul(0x9E37_79B9));
+
+        Fractal {
+            kind: settings.kind,
+            octaves: (0..octaves).map(|octave| Perlin::new().set_seed(base_seed.wrapping_add(octave))).collect(),
+            frequency: settings.frequency,
+            lacunarity: settings.lacunarity,
+            persistence: settings.persistence,
+            total_amplitude: (0..octaves).map(|octave| settings.persistence.powi(octave as i32)).sum()
+        }
+    }
+
+    /// Fbm is roughly -1 to 1, ridged is 0 to 1 with sharp crests at 1
+    pub fn get(&self, x: f64, z: f64) -> f64 {
+        self.sum(|octave, frequency| octave.get([x * frequency, z * frequency]))
+    }
+
+    /// The same as get, in three dimensions for carving caves
+    pub fn get3(&self, x: f64, y: f64, z: f64) -> f64 {
+        self.sum(|octave, frequency| octave.get([x * frequency, y * frequency, z * frequency]))
+    }
+
+    fn sum<F: Fn(&Perlin, f64) -> f64>(&self, sample: F) -> f64 {
+        let mut frequency = self.frequency;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+
+        for octave in &self.octaves {
+            let value = sample(octave, frequency);
+
+            total += amplitude * match self.kind {
+                FractalKind::Fbm => value,
+                FractalKind::Ridged => {
+                    let ridge = 1.0 - value.abs();
+                    ridge * ridge
+                }
+            };
+
+            frequency *= self.lacunarity;
+            amplitude *= self.persistence;
+        }
+
+        total / self.total_amplitude
+    }
+}
+
+/// A piecewise linear curve through sorted points, flat past either end
+pub struct Curve {
+    points: Vec<[f64; 2]>
+}
+
+impl Curve {
+    pub fn new(mut points: Vec<[f64; 2]>) -> Curve {
+        points.sort_by(|a, b| a[0].partial_cmp(&b[0]).unwrap_or(std::cmp::Ordering::Equal));
+        Curve {
+            points
+        }
+    }
+
+    pub fn get(&self, x: f64) -> f64 {
+        let points = &self.points;
+
+        let next = match points.iter().position(|point| point[0] > x) {
+            Some(0) => return points[0][1],
+            Some(next) => next,
+            None => return points.last().map_or(0.0, |point| point[1])
+        };
+
+        let (from, to) = (points[next - 1], points[next]);
+        let t = (x - from[0]) / (to[0] - from[0]);
+
+        from[1] + (to[1] - from[1]) * t
+    }
+}
+
+/// All the layers of a preset, ready to sample
+pub struct TerrainNoise {
+    base_height: f64,
+    continentalness: (Fractal, Curve),
+    erosion: (Fractal, Curve),
+    peaks: (Fractal, Curve),
+    detail: Option<(Fractal, f64)>,
+    /// One fractal for each axis, and how many blocks they can move a sample by
+    warp: Option<(Fractal, Fractal, f64)>
+}
+
+impl TerrainNoise {
+    pub fn new(seed: u32, preset: &TerrainPreset) -> TerrainNoise {
+        TerrainNoise {
+            base_height: preset.base_height,
+            continentalness: (Fractal::new(seed, 1, &preset.continentalness.noise), Curve::new(preset.continentalness.curve.clone())),
+            erosion: (Fractal::new(seed, 2, &preset.erosion.noise), Curve::new(preset.erosion.curve.clone())),
+            peaks: (Fractal::new(seed, 3, &preset.peaks.noise), Curve::new(preset.peaks.curve.clone())),
+            detail: preset.detail.as_ref().map(|detail| (Fractal::new(seed, 4, &detail.noise), detail.amplitude)),
+            warp: preset.warp.as_ref().map(|warp| (Fractal::new(seed, 5, &warp.noise), Fractal::new(seed, 6, &warp.noise), warp.amplitude))
+        }
+    }
+
+    /// Height of the surface at a world x and z, in blocks
+    pub fn height(&self, x: f64, z: f64) -> f64 {
+        let (x, z) = match &self.warp {
+            Some((warp_x, warp_z, amplitude)) => (x + warp_x.get(x, z) * amplitude, z + warp_z.get(x, z) * amplitude),
+            None => (x, z)
+        };
+
+        let continentalness = self.continentalness.1.get(self.continentalness.0.get(x, z));
+        // Erosion scales the peaks, so smooth areas stay smooth even where the peak noise is high
+        let erosion = self.erosion.1.get(self.erosion.0.get(x, z));
+        let peaks = self.peaks.1.get(self.peaks.0.get(x, z));
+        let detail = self.detail.as_ref().map_or(0.0, |(noise, amplitude)| noise.get(x, z) * amplitude);
+
+        self.base_height + continentalness + erosion * peaks + detail
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::generator::presets::built_in_preset;
+
+    fn settings(kind: FractalKind) -> NoiseSettings {
+        NoiseSettings {
+            kind,
+            octaves: 4,
+            frequency: 0.05,
+            lacunarity: 2.0,
+            persistence: 0.5
+        }
+    }
+
+    #[test]
+    fn curves_interpolate_between_points_and_are_flat_past_the_ends() {
+        let curve = Curve::new(vec![[1.0, 10.0], [-1.0, -10.0], [0.0, 0.0]]);
+
+        assert_eq!(curve.get(-5.0), -10.0);
+        assert_eq!(curve.get(-0.5), -5.0);
+        assert_eq!(curve.get(0.0), 0.0);
+        assert_eq!(curve.get(0.25), 2.5);
+        assert_eq!(curve.get(5.0), 10.0);
+        assert_eq!(Curve::new(Vec::new()).get(1.0), 0.0);
+    }
+
+    #[test]
+    fn fractals_stay_in_range() {
+        let fbm = Fractal::new(42, 1, &settings(FractalKind::Fbm));
+        let ridged = Fractal::new(42, 1, &settings(FractalKind::Ridged));
+
+        for x in -20..20 {
+            for z in -20..20 {
+                let (x, z) = (x as f64 * 3.7, z as f64 * 2.9);
+
+                assert!(fbm.get(x, z).abs() <= 1.0);
+                assert!((0.0..=1.0).contains(&ridged.get(x, z)));
+                assert!(fbm.get3(x, z * 0.5, z).abs() <= 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn salts_give_different_layers_from_one_seed() {
+        let first = Fractal::new(42, 1, &settings(FractalKind::Fbm));
+        let second = Fractal::new(42, 2, &settings(FractalKind::Fbm));
+
+        assert!((0..50).any(|i| first.get(i as f64 * 1.3, 0.7) != second.get(i as f64 * 1.3, 0.7)));
+    }
+
+    #[test]
+    fn heights_only_depend_on_the_seed() {
+        for name in ["default", "amplified", "islands"].iter() {
+            let preset = built_in_preset(name);
+            let first = TerrainNoise::new(1234, &preset);
+            let again = TerrainNoise::new(1234, &preset);
+            let other = TerrainNoise::new(4321, &preset);
+
+            let mut differs = false;
+            for x in (-200..200).step_by(17) {
+                for z in (-200..200).step_by(23) {
+                    let (x, z) = (x as f64, z as f64);
+                    assert_eq!(first.height(x, z), again.height(x, z));
+                    differs |= first.height(x, z) != other.height(x, z);
+                }
+            }
+
+            assert!(differs, "{} ignores the seed", name);
+        }
+    }
+}
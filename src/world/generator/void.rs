@@ -0,0 +1,34 @@
+//
+// An empty world, nothing but air
+//
+
+use crate::block::registry::BlockRegistry;
+use crate::services::chunk_service::storage::ChunkStorage;
+use crate::world::generator::WorldGenerator;
+use cgmath::Vector3;
+
+pub struct VoidGenerator {
+    seed: u32
+}
+
+impl VoidGenerator {
+    pub fn new(seed: u32) -> VoidGenerator {
+        VoidGenerator {
+            seed
+        }
+    }
+}
+
+impl WorldGenerator for VoidGenerator {
+    fn name(&self) -> &str {
+        "void"
+    }
+
+    fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    fn generate_chunk(&self, _chunk_pos: Vector3<i32>, _blocks: &BlockRegistry) -> ChunkStorage {
+        ChunkStorage::empty()
+    }
+}
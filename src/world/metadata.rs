@@ -0,0 +1,149 @@
+//
+// Everything about a world that isn't in its chunks, kept in level.json next to the region folder
+//
+
+use crate::services::settings_service::SettingsService;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever the layout of the save folder or the metadata changes
+pub const FORMAT_VERSION: u32 = 1;
+
+const METADATA_FILE: &str = "level.json";
+
+/// Where the player starts in a new world, moved onto the ground if the generator knows where that is
+const DEFAULT_SPAWN: [f32; 3] = [10.0, 50.0, 10.0];
+
+/// World time advances this many ticks a second, like Minecraft
+pub const TICKS_PER_SECOND: f64 = 20.0;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct WorldMetadata {
+    pub format_version: u32,
+    pub seed: u32,
+    /// Name of the generator new chunks are made with
+    pub generator: String,
+    #[serde(default)]
+    pub generator_settings: GeneratorSettings,
+    pub spawn: [f32; 3],
+    /// Ticks since the world was created
+    #[serde(default)]
+    pub time: u64,
+    #[serde(default)]
+    pub game_rules: GameRules,
+    /// The world folder, set when the metadata is loaded or created
+    #[serde(skip)]
+    directory: PathBuf
+}
+
+/// Options for the generator, kept with the world so changing the settings doesn't change existing worlds
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GeneratorSettings {
+    pub flat_preset: String,
+    /// Name of the terrain preset used by the noise generator
+    pub terrain_preset: String
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GameRules {
+    /// Whether world time moves on while playing
+    pub daylight_cycle: bool
+}
+
+impl Default for GeneratorSettings {
+    fn default() -> GeneratorSettings {
+        GeneratorSettings {
+            flat_preset: String::from(crate::world::generator::flat::DEFAULT_FLAT_PRESET),
+            terrain_preset: String::from(crate::world::generator::presets::DEFAULT_PRESET)
+        }
+    }
+}
+
+impl Default for GameRules {
+    fn default() -> GameRules {
+        GameRules {
+            daylight_cycle: true
+        }
+    }
+}
+
+impl WorldMetadata {
+    /// Opens the metadata of the world in directory, creating it from the settings if the world is new.
+    /// A world whose metadata can't be read falls back to new metadata, but the broken file is left alone.
+    pub fn load_or_create(directory: &Path, settings: &SettingsService) -> WorldMetadata {
+        let path = directory.join(METADATA_FILE);
+
+        if path.exists() {
+            match WorldMetadata::load(&path) {
+                Ok(mut metadata) => {
+                    metadata.directory = directory.to_path_buf();
+                    return metadata;
+                }
+                Err(e) => {
+                    log_error!(format!("Failed to read world metadata {}, the world may generate differently: {}", path.display(), e));
+
+                    let mut metadata = WorldMetadata::new(directory, settings);
+                    // Don't overwrite it, it might be fixable by hand
+                    metadata.directory = PathBuf::new();
+                    return metadata;
+                }
+            }
+        }
+
+        let metadata = WorldMetadata::new(directory, settings);
+        metadata.save();
+        metadata
+    }
+
+    /// Metadata for a brand new world, picking a random seed if the settings don't have one
+    pub fn new(directory: &Path, settings: &SettingsService) -> WorldMetadata {
+        WorldMetadata {
+            format_version: FORMAT_VERSION,
+            seed: settings.world_seed.unwrap_or_else(rand::random),
+            generator: settings.world_generator.clone(),
+            generator_settings: GeneratorSettings {
+                flat_preset: settings.flat_preset.clone(),
+                terrain_preset: settings.terrain_preset.clone()
+            },
+            spawn: DEFAULT_SPAWN,
+            time: 0,
+            game_rules: GameRules::default(),
+            directory: directory.to_path_buf()
+        }
+    }
+
+    fn load(path: &Path) -> io::Result<WorldMetadata> {
+        let metadata: WorldMetadata = serde_json::from_slice(&fs::read(path)?)?;
+
+        if metadata.format_version > FORMAT_VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("world was saved by a newer version (format {})", metadata.format_version)));
+        }
+
+        Ok(metadata)
+    }
+
+    /// Writes the metadata back to the world folder. The old file is only replaced once the new one is written.
+    pub fn save(&self) {
+        if self.directory.as_os_str().is_empty() {
+            return;
+        }
+
+        if let Err(e) = self.write() {
+            log_error!(format!("Failed to save world metadata in {}: {}", self.directory.display(), e));
+        }
+    }
+
+    fn write(&self) -> io::Result<()> {
+        fs::create_dir_all(&self.directory)?;
+
+        let path = self.directory.join(METADATA_FILE);
+        let temp_path = self.directory.join(format!("{}.tmp", METADATA_FILE));
+
+        fs::write(&temp_path, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(temp_path, path)
+    }
+}
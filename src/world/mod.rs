@@ -1 +1,7 @@
 pub mod generator;
+pub mod region;
+pub mod save;
+pub mod anvil;
+pub mod metadata;
+pub mod biome;
+pub mod template;
@@ -0,0 +1,394 @@
+//
+// Region files, each holding a 32x32 area of chunk columns. The file starts with a table of where each column is stored
+// and when it was saved, followed by the columns themselves, each compressed on its own and padded out to whole sectors.
+//
+
+use flate2::Compression;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::ZlibEncoder;
+use cgmath::Vector2;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Width of a region in chunk columns
+pub const REGION_SIZE: i32 = 32;
+
+const SECTOR_SIZE: usize = 4096;
+const COLUMNS: usize = (REGION_SIZE * REGION_SIZE) as usize;
+
+/// The location table and the timestamp table take a sector each
+const HEADER_SECTORS: usize = 2;
+
+/// A column can't take up more sectors than fit in the location table's length byte
+const MAX_COLUMN_SECTORS: usize = 255;
+
+/// Columns are always written with zlib, gzip is only read as some Minecraft worlds use it
+const COMPRESSION_GZIP: u8 = 1;
+const COMPRESSION_ZLIB: u8 = 2;
+
+pub struct RegionFile {
+    file: File,
+    /// Sector offset in the top 24 bits and length in sectors in the bottom 8, 0 if the column hasn't been saved
+    locations: Vec<u32>,
+    /// Seconds since the unix epoch when each column was last saved
+    timestamps: Vec<u32>,
+    /// Which sectors of the file are taken
+    used_sectors: Vec<bool>
+}
+
+impl RegionFile {
+    /// Opens a region file, creating an empty one if it doesn't exist yet
+    pub fn open(path: &Path) -> io::Result<RegionFile> {
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+
+        // New file, an empty header is all zeroes
+        if file.metadata()?.len() < (HEADER_SECTORS * SECTOR_SIZE) as u64 {
+            file.set_len((HEADER_SECTORS * SECTOR_SIZE) as u64)?;
+        }
+
+        RegionFile::from_file(file, path)
+    }
+
+    /// Opens an existing region file only for reading, such as a Minecraft .mca file, which uses the same layout
+    pub fn open_read_only(path: &Path) -> io::Result<RegionFile> {
+        let file = File::open(path)?;
+
+        if file.metadata()?.len() < (HEADER_SECTORS * SECTOR_SIZE) as u64 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Region file is too short for its header"));
+        }
+
+        RegionFile::from_file(file, path)
+    }
+
+    fn from_file(mut file: File, path: &Path) -> io::Result<RegionFile> {
+        let mut locations = vec![0; COLUMNS];
+        let mut timestamps = vec![0; COLUMNS];
+
+        let mut header = vec![0; HEADER_SECTORS * SECTOR_SIZE];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut header)?;
+
+        for i in 0..COLUMNS {
+            locations[i] = read_u32(&header[i * 4..]);
+            timestamps[i] = read_u32(&header[SECTOR_SIZE + i * 4..]);
+        }
+
+        let length = file.metadata()?.len() as usize;
+        let mut used_sectors = vec![false; length.div_ceil(SECTOR_SIZE)];
+
+        for used in used_sectors.iter_mut().take(HEADER_SECTORS) {
+            *used = true;
+        }
+
+        for (i, location) in locations.iter_mut().enumerate() {
+            if *location == 0 {
+                continue;
+            }
+
+            let (offset, count) = split_location(*location);
+
+            // A column pointing outside the file or into the header can't be read, forget about it
+            if offset < HEADER_SECTORS || offset + count > used_sectors.len() {
+                log_error!(format!("Column {} in region file {} is out of bounds, it will be regenerated", i, path.display()));
+                *location = 0;
+                continue;
+            }
+
+            for used in &mut used_sectors[offset..offset + count] {
+                *used = true;
+            }
+        }
+
+        Ok(RegionFile {
+            file,
+            locations,
+            timestamps,
+            used_sectors
+        })
+    }
+
+    /// Reads and decompresses a column, None if it hasn't been saved
+    pub fn read_column(&mut self, column: Vector2<i32>) -> io::Result<Option<Vec<u8>>> {
+        let location = self.locations[column_index(column)];
+
+        if location == 0 {
+            return Ok(None);
+        }
+
+        let (offset, count) = split_location(location);
+
+        let mut header = [0; 5];
+        self.file.seek(SeekFrom::Start((offset * SECTOR_SIZE) as u64))?;
+        self.file.read_exact(&mut header)?;
+
+        // Length counts the compression byte as well as the data
+        let length = read_u32(&header) as usize;
+        if length == 0 || length + 4 > count * SECTOR_SIZE {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Column length doesn't fit its sectors"));
+        }
+
+        let mut compressed = vec![0; length - 1];
+        self.file.read_exact(&mut compressed)?;
+
+        let mut data = Vec::new();
+        match header[4] {
+            COMPRESSION_GZIP => GzDecoder::new(compressed.as_slice()).read_to_end(&mut data)?,
+            COMPRESSION_ZLIB => ZlibDecoder::new(compressed.as_slice()).read_to_end(&mut data)?,
+            compression => return Err(io::Error::new(io::ErrorKind::InvalidData, format!("Unknown compression type {}", compression)))
+        };
+
+        Ok(Some(data))
+    }
+
+    /// Compresses and writes a column, reusing its old sectors if it still fits in them
+    pub fn write_column(&mut self, column: Vector2<i32>, data: &[u8]) -> io::Result<()> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+
+        let sectors = (compressed.len() + 5).div_ceil(SECTOR_SIZE);
+        if sectors > MAX_COLUMN_SECTORS {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Column is too large to save"));
+        }
+
+        let index = column_index(column);
+
+        // Free the old sectors first so they can be written over
+        if self.locations[index] != 0 {
+            let (offset, count) = split_location(self.locations[index]);
+
+            for sector in offset..offset + count {
+                self.used_sectors[sector] = false;
+            }
+        }
+
+        let offset = self.allocate(sectors);
+
+        let mut payload = Vec::with_capacity(sectors * SECTOR_SIZE);
+        payload.extend_from_slice(&((compressed.len() + 1) as u32).to_be_bytes());
+        payload.push(COMPRESSION_ZLIB);
+        payload.extend_from_slice(&compressed);
+        payload.resize(sectors * SECTOR_SIZE, 0);
+
+        self.file.seek(SeekFrom::Start((offset * SECTOR_SIZE) as u64))?;
+        self.file.write_all(&payload)?;
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs() as u32);
+
+        self.locations[index] = ((offset as u32) << 8) | sectors as u32;
+        self.timestamps[index] = timestamp;
+
+        self.file.seek(SeekFrom::Start((index * 4) as u64))?;
+        self.file.write_all(&self.locations[index].to_be_bytes())?;
+        self.file.seek(SeekFrom::Start((SECTOR_SIZE + index * 4) as u64))?;
+        self.file.write_all(&timestamp.to_be_bytes())?;
+
+        Ok(())
+    }
+
+    /// Finds the first run of free sectors long enough, or grows the file if there isn't one
+    fn allocate(&mut self, sectors: usize) -> usize {
+        let mut run_start = 0;
+        let mut run = 0;
+
+        for (i, used) in self.used_sectors.iter().enumerate() {
+            if *used {
+                run_start = i + 1;
+                run = 0;
+            } else {
+                run += 1;
+
+                if run == sectors {
+                    break;
+                }
+            }
+        }
+
+        // Either a big enough gap, or the free sectors at the end of the file which are grown to fit
+        if run_start + sectors > self.used_sectors.len() {
+            self.used_sectors.resize(run_start + sectors, false);
+        }
+
+        for sector in run_start..run_start + sectors {
+            self.used_sectors[sector] = true;
+        }
+
+        run_start
+    }
+}
+
+/// The region a column of chunks is stored in
+pub fn region_position(column: Vector2<i32>) -> Vector2<i32> {
+    Vector2 {
+        x: column.x.div_euclid(REGION_SIZE),
+        y: column.y.div_euclid(REGION_SIZE)
+    }
+}
+
+fn column_index(column: Vector2<i32>) -> usize {
+    (column.x.rem_euclid(REGION_SIZE) + column.y.rem_euclid(REGION_SIZE) * REGION_SIZE) as usize
+}
+
+fn split_location(location: u32) -> (usize, usize) {
+    ((location >> 8) as usize, (location & 0xFF) as usize)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    (bytes[0] as u32) << 24 | (bytes[1] as u32) << 16 | (bytes[2] as u32) << 8 | bytes[3] as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// A Minecraft region with a zlib column at 0, 0 and a gzip column at 2, 1, written by tests/fixtures/make_anvil_fixture.py
+    const ANVIL_FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/r.0.0.mca");
+
+    /// A region file in the temp directory that's deleted when the test ends
+    struct TempRegion(PathBuf);
+
+    impl TempRegion {
+        fn new(name: &str) -> TempRegion {
+            let path = std::env::temp_dir().join(format!("region_{}_{}.mca", name, std::process::id()));
+            let _ = fs::remove_file(&path);
+
+            TempRegion(path)
+        }
+    }
+
+    impl Drop for TempRegion {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.0);
+        }
+    }
+
+    /// Bytes that zlib can't shrink, so the compressed size is known
+    fn noise(length: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+
+        (0..length).map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (state >> 16) as u8
+        }).collect()
+    }
+
+    fn location(region: &RegionFile, column: Vector2<i32>) -> (usize, usize) {
+        split_location(region.locations[column_index(column)])
+    }
+
+    fn file_sectors(path: &Path) -> u64 {
+        fs::metadata(path).unwrap().len() / SECTOR_SIZE as u64
+    }
+
+    #[test]
+    fn location_table_points_at_each_column() {
+        let region = RegionFile::open_read_only(Path::new(ANVIL_FIXTURE)).unwrap();
+
+        assert_eq!(split_location(region.locations[column_index(Vector2 { x: 0, y: 0 })]), (2, 1));
+        assert_eq!(split_location(region.locations[column_index(Vector2 { x: 2, y: 1 })]), (3, 1));
+        assert_eq!(region.timestamps[column_index(Vector2 { x: 2, y: 1 })], 1_600_000_000);
+        assert_eq!(region.locations.iter().filter(|location| **location != 0).count(), 2);
+        assert_eq!(region.used_sectors, vec![true; 4]);
+    }
+
+    #[test]
+    fn column_index_wraps_into_the_region() {
+        assert_eq!(column_index(Vector2 { x: 2, y: 1 }), 34);
+        assert_eq!(column_index(Vector2 { x: -30, y: 33 }), 34);
+        assert_eq!(region_position(Vector2 { x: -30, y: 33 }), Vector2 { x: -1, y: 1 });
+    }
+
+    #[test]
+    fn reads_zlib_and_gzip_columns() {
+        let mut region = RegionFile::open_read_only(Path::new(ANVIL_FIXTURE)).unwrap();
+
+        for column in [Vector2 { x: 0, y: 0 }, Vector2 { x: 2, y: 1 }].iter() {
+            let data = region.read_column(*column).unwrap().unwrap();
+
+            // Every column is an unnamed root compound
+            assert_eq!(&data[..3], &[10, 0, 0]);
+        }
+
+        assert!(region.read_column(Vector2 { x: 5, y: 5 }).unwrap().is_none());
+    }
+
+    #[test]
+    fn columns_are_read_back_after_reopening() {
+        let temp = TempRegion::new("reopen");
+        let first = Vector2 { x: 0, y: 0 };
+        let second = Vector2 { x: 37, y: -5 };
+
+        {
+            let mut region = RegionFile::open(&temp.0).unwrap();
+            assert_eq!(file_sectors(&temp.0), HEADER_SECTORS as u64);
+
+            region.write_column(first, b"first column").unwrap();
+            region.write_column(second, &noise(6000, 1)).unwrap();
+        }
+
+        let mut region = RegionFile::open(&temp.0).unwrap();
+        assert_eq!(region.read_column(first).unwrap().unwrap(), b"first column");
+        assert_eq!(region.read_column(second).unwrap().unwrap(), noise(6000, 1));
+        assert_eq!(region.read_column(Vector2 { x: 1, y: 0 }).unwrap(), None);
+        assert!(region.timestamps[column_index(first)] > 0);
+
+        let mut read_only = RegionFile::open_read_only(&temp.0).unwrap();
+        assert_eq!(read_only.read_column(first).unwrap().unwrap(), b"first column");
+    }
+
+    #[test]
+    fn freed_sectors_are_reused_before_the_file_grows() {
+        let temp = TempRegion::new("reuse");
+        let a = Vector2 { x: 0, y: 0 };
+        let b = Vector2 { x: 1, y: 0 };
+        let c = Vector2 { x: 2, y: 0 };
+
+        let mut region = RegionFile::open(&temp.0).unwrap();
+        region.write_column(a, b"a").unwrap();
+        region.write_column(b, b"b").unwrap();
+        assert_eq!(location(&region, a), (2, 1));
+        assert_eq!(location(&region, b), (3, 1));
+        assert_eq!(file_sectors(&temp.0), 4);
+
+        // Too big for its old sector, so it moves to the end of the file and leaves a gap
+        let large = noise(3 * SECTOR_SIZE, 2);
+        region.write_column(a, &large).unwrap();
+        assert_eq!(location(&region, a), (4, 4));
+        assert_eq!(file_sectors(&temp.0), 8);
+
+        region.write_column(c, b"c").unwrap();
+        assert_eq!(location(&region, c), (2, 1));
+        assert_eq!(file_sectors(&temp.0), 8);
+
+        // Shrinking keeps the column where it was and frees the rest
+        region.write_column(a, b"small again").unwrap();
+        assert_eq!(location(&region, a), (4, 1));
+        // b's old sector is left free behind it
+        region.write_column(b, &noise(2 * SECTOR_SIZE, 3)).unwrap();
+        assert_eq!(location(&region, b), (5, 3));
+        assert_eq!(file_sectors(&temp.0), 8);
+
+        drop(region);
+        let mut region = RegionFile::open(&temp.0).unwrap();
+        assert_eq!(region.read_column(a).unwrap().unwrap(), b"small again");
+        assert_eq!(region.read_column(b).unwrap().unwrap(), noise(2 * SECTOR_SIZE, 3));
+        assert_eq!(region.read_column(c).unwrap().unwrap(), b"c");
+        assert_eq!(region.used_sectors, vec![true, true, true, false, true, true, true, true]);
+    }
+
+    #[test]
+    fn allocate_takes_the_first_gap_that_fits() {
+        let temp = TempRegion::new("allocate");
+        let mut region = RegionFile::open(&temp.0).unwrap();
+
+        region.used_sectors = vec![true, true, false, true, false, false, true];
+        assert_eq!(region.allocate(2), 4);
+        assert_eq!(region.allocate(1), 2);
+        assert_eq!(region.allocate(3), 7);
+        assert_eq!(region.used_sectors, vec![true; 10]);
+    }
+}
@@ -0,0 +1,351 @@
+//
+// Saves columns of chunks to region files and loads them back, on a thread of its own so the game never waits on the disk
+//
+
+use crate::world::region::{RegionFile, region_position};
+use crate::services::chunk_service::storage::ChunkStorage;
+use crate::block::registry::BlockRegistry;
+use crate::block::state::BlockState;
+use cgmath::Vector2;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::mpsc::{channel, Sender, Receiver, TryIter};
+use std::thread;
+
+/// Bumped whenever the layout of a saved column changes
+const COLUMN_VERSION: u32 = 1;
+
+/// Region files are closed once this many are open, there's no point keeping ones far behind the player open
+const MAX_OPEN_REGIONS: usize = 16;
+
+/// The chunks of a column, each with its chunk y position
+pub type ColumnChunks = Vec<(i32, ChunkStorage)>;
+
+enum SaveRequest {
+    Load(Vector2<i32>),
+    Save(Vector2<i32>, ColumnChunks),
+    /// Replies once every request before it has been dealt with
+    Flush(Sender<()>)
+}
+
+pub struct LoadedColumn {
+    pub column: Vector2<i32>,
+    /// None if the column has never been saved, or couldn't be read
+    pub chunks: Option<ColumnChunks>
+}
+
+#[derive(Serialize, Deserialize)]
+struct ColumnData {
+    version: u32,
+    chunks: Vec<ChunkData>
+}
+
+#[derive(Serialize, Deserialize)]
+struct ChunkData {
+    y: i32,
+    palette: Vec<PaletteEntry>,
+    data: Vec<u64>
+}
+
+/// Blocks are saved by identifier and property values rather than by id, so ids can change without breaking saves
+#[derive(Serialize, Deserialize)]
+struct PaletteEntry {
+    name: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    properties: BTreeMap<String, String>
+}
+
+pub struct WorldSave {
+    requests: Sender<SaveRequest>,
+    loaded: Receiver<LoadedColumn>
+}
+
+impl WorldSave {
+    /// Starts the save thread for the world in directory, region files go in its region folder
+    pub fn new(directory: PathBuf, blocks: Arc<BlockRegistry>) -> WorldSave {
+        let (request_sender, request_receiver) = channel();
+        let (loaded_sender, loaded_receiver) = channel();
+
+        let thread = thread::Builder::new().name(String::from("World save")).spawn(move || {
+            let mut regions = RegionCache::new(directory.join("region"));
+
+            for request in request_receiver {
+                match request {
+                    SaveRequest::Load(column) => {
+                        let chunks = regions.read_column(column, &blocks);
+
+                        if loaded_sender.send(LoadedColumn { column, chunks }).is_err() {
+                            return;
+                        }
+                    }
+                    SaveRequest::Save(column, chunks) => regions.write_column(column, &chunks, &blocks),
+                    SaveRequest::Flush(done) => {
+                        let _ = done.send(());
+                    }
+                }
+            }
+        });
+
+        if let Err(e) = thread {
+            log_error!("Failed to start world save thread: {}", e);
+        }
+
+        WorldSave {
+            requests: request_sender,
+            loaded: loaded_receiver
+        }
+    }
+
+    /// Asks for a column to be loaded, it comes back through loaded()
+    pub fn load_column(&self, column: Vector2<i32>) {
+        self.send(SaveRequest::Load(column));
+    }
+
+    pub fn save_column(&self, column: Vector2<i32>, chunks: ColumnChunks) {
+        self.send(SaveRequest::Save(column, chunks));
+    }
+
+    /// Waits until everything asked for so far has been written
+    pub fn flush(&self) {
+        let (done, wait) = channel();
+        self.send(SaveRequest::Flush(done));
+        let _ = wait.recv();
+    }
+
+    /// Columns that have finished loading since the last call
+    pub fn loaded(&self) -> TryIter<'_, LoadedColumn> {
+        self.loaded.try_iter()
+    }
+
+    fn send(&self, request: SaveRequest) {
+        if self.requests.send(request).is_err() {
+            log_error!("World save thread has stopped, request dropped");
+        }
+    }
+}
+
+/// Region files that have been opened, by region position
+struct RegionCache {
+    directory: PathBuf,
+    regions: HashMap<Vector2<i32>, RegionFile>
+}
+
+impl RegionCache {
+    fn new(directory: PathBuf) -> RegionCache {
+        RegionCache {
+            directory,
+            regions: HashMap::new()
+        }
+    }
+
+    fn read_column(&mut self, column: Vector2<i32>, blocks: &BlockRegistry) -> Option<ColumnChunks> {
+        let region = self.region(region_position(column), false)?;
+
+        let data = match region.read_column(column) {
+            Ok(data) => data?,
+            Err(e) => {
+                log_error!(format!("Failed to read column {}, {}: {}", column.x, column.y, e));
+                return None;
+            }
+        };
+
+        match decode_column(&data, blocks) {
+            Ok(chunks) => Some(chunks),
+            Err(e) => {
+                log_error!(format!("Saved column {}, {} is corrupt, it will be regenerated: {}", column.x, column.y, e));
+                None
+            }
+        }
+    }
+
+    fn write_column(&mut self, column: Vector2<i32>, chunks: &ColumnChunks, blocks: &BlockRegistry) {
+        let data = match encode_column(chunks, blocks) {
+            Ok(data) => data,
+            Err(e) => {
+                log_error!(format!("Failed to encode column {}, {}: {}", column.x, column.y, e));
+                return;
+            }
+        };
+
+        let region = match self.region(region_position(column), true) {
+            Some(region) => region,
+            None => return
+        };
+
+        if let Err(e) = region.write_column(column, &data) {
+            log_error!(format!("Failed to save column {}, {}: {}", column.x, column.y, e));
+        }
+    }
+
+    /// Opens a region file, only creating it if create is set
+    fn region(&mut self, position: Vector2<i32>, create: bool) -> Option<&mut RegionFile> {
+        if !self.regions.contains_key(&position) {
+            let path = self.directory.join(format!("r.{}.{}.rcr", position.x, position.y));
+
+            if !create && !path.exists() {
+                return None;
+            }
+
+            if let Err(e) = fs::create_dir_all(&self.directory) {
+                log_error!(format!("Failed to create save directory {}: {}", self.directory.display(), e));
+                return None;
+            }
+
+            let region = match RegionFile::open(&path) {
+                Ok(region) => region,
+                Err(e) => {
+                    log_error!(format!("Failed to open region file {}: {}", path.display(), e));
+                    return None;
+                }
+            };
+
+            if self.regions.len() >= MAX_OPEN_REGIONS {
+                self.regions.clear();
+            }
+
+            self.regions.insert(position, region);
+        }
+
+        self.regions.get_mut(&position)
+    }
+}
+
+fn encode_column(chunks: &ColumnChunks, blocks: &BlockRegistry) -> serde_json::Result<Vec<u8>> {
+    let column = ColumnData {
+        version: COLUMN_VERSION,
+        chunks: chunks.iter().map(|(y, storage)| ChunkData {
+            y: *y,
+            palette: storage.palette().iter().map(|state| palette_entry(*state, blocks)).collect(),
+            data: storage.packed_data().clone()
+        }).collect()
+    };
+
+    serde_json::to_vec(&column)
+}
+
+fn decode_column(data: &[u8], blocks: &BlockRegistry) -> Result<ColumnChunks, String> {
+    let column: ColumnData = serde_json::from_slice(data).map_err(|e| e.to_string())?;
+
+    if column.version != COLUMN_VERSION {
+        return Err(format!("unknown version {}", column.version));
+    }
+
+    column.chunks.into_iter().map(|chunk| {
+        let palette = chunk.palette.iter().map(|entry| palette_state(entry, blocks)).collect();
+        let y = chunk.y;
+
+        ChunkStorage::from_packed(palette, chunk.data)
+            .map(|storage| (y, storage))
+            .ok_or_else(|| format!("chunk {} doesn't match its palette", y))
+    }).collect()
+}
+
+fn palette_entry(state: BlockState, blocks: &BlockRegistry) -> PaletteEntry {
+    match blocks.get_by_state(state) {
+        Some(block) => PaletteEntry {
+            name: block.identifier.clone(),
+            properties: block.properties.iter()
+                .filter_map(|property| block.property_value(state, &property.name).map(|value| (property.name.clone(), value.to_string())))
+                .collect()
+        },
+        None => PaletteEntry {
+            name: String::from("minecraft:air"),
+            properties: BTreeMap::new()
+        }
+    }
+}
+
+/// Blocks that no longer exist are loaded as air
+fn palette_state(entry: &PaletteEntry, blocks: &BlockRegistry) -> BlockState {
+    match blocks.get_by_name(&entry.name) {
+        Some(block) => entry.properties.iter()
+            .fold(block.default_state(), |state, (name, value)| block.with_property(state, name, value)),
+        None => {
+            if entry.name != "minecraft:air" {
+                log_error!("Unknown block {} in save, replaced with air", entry.name);
+            }
+
+            BlockState::AIR
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(blocks: &BlockRegistry) -> ColumnChunks {
+        let stone = blocks.default_state("minecraft:stone").unwrap();
+        let dirt = blocks.default_state("minecraft:dirt").unwrap();
+        let log = blocks.get_by_name("minecraft:oak_log").unwrap();
+        let sideways_log = log.with_property(log.default_state(), "axis", "x");
+        assert_ne!(sideways_log, log.default_state());
+
+        let mut mixed = ChunkStorage::empty();
+        for i in 0..16 {
+            mixed.set(i, 0, 0, stone);
+            mixed.set(0, i, 3, dirt);
+            mixed.set(5, 5, i, sideways_log);
+        }
+
+        vec![(-1, ChunkStorage::filled(stone)), (0, mixed), (4, ChunkStorage::empty())]
+    }
+
+    fn assert_same_column(a: &ColumnChunks, b: &ColumnChunks) {
+        assert_eq!(a.len(), b.len());
+
+        for ((a_y, a), (b_y, b)) in a.iter().zip(b.iter()) {
+            assert_eq!(a_y, b_y);
+
+            for x in 0..16 {
+                for y in 0..16 {
+                    for z in 0..16 {
+                        assert_eq!(a.get(x, y, z), b.get(x, y, z));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn columns_decode_to_the_blocks_they_were_encoded_from() {
+        let blocks = BlockRegistry::built_in();
+        let chunks = column(&blocks);
+
+        let data = encode_column(&chunks, &blocks).unwrap();
+        assert_same_column(&decode_column(&data, &blocks).unwrap(), &chunks);
+    }
+
+    #[test]
+    fn columns_survive_a_region_file() {
+        let blocks = BlockRegistry::built_in();
+        let chunks = column(&blocks);
+        let path = std::env::temp_dir().join(format!("save_column_{}.mca", std::process::id()));
+        let position = Vector2 { x: -3, y: 40 };
+
+        {
+            let mut region = RegionFile::open(&path).unwrap();
+            region.write_column(position, &encode_column(&chunks, &blocks).unwrap()).unwrap();
+        }
+
+        let data = RegionFile::open(&path).unwrap().read_column(position).unwrap().unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_same_column(&decode_column(&data, &blocks).unwrap(), &chunks);
+    }
+
+    #[test]
+    fn unknown_versions_and_blocks_are_handled() {
+        let blocks = BlockRegistry::built_in();
+
+        let newer = br#"{ "version": 99, "chunks": [] }"#;
+        assert!(decode_column(newer, &blocks).is_err());
+
+        let removed = br#"{ "version": 1, "chunks": [{ "y": 0, "palette": [{ "name": "mod:removed" }], "data": [] }] }"#;
+        let decoded = decode_column(removed, &blocks).unwrap();
+        assert_eq!(decoded[0].1.get(1, 2, 3), BlockState::AIR);
+    }
+}
@@ -0,0 +1,520 @@
+//
+// Structure templates are boxes of blocks that get built into the world, like village houses. They're stored as gzipped NBT
+// in the same layout as Minecraft's structure files, so templates can be saved from a world and shared between the two.
+//
+
+use crate::block::registry::BlockRegistry;
+use crate::block::state::BlockState;
+use crate::nbt::{Tag, from_tag, to_tag};
+use crate::nbt::compression::{read_compressed, write_gzip};
+use crate::services::settings_service::SettingsService;
+use cgmath::Vector3;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read};
+use std::path::Path;
+
+/// The templates the game ships with, extra ones are read from resources/structures/
+const DEFAULT_TEMPLATES: [(&str, &[u8]); 4] = [
+    ("village_well", include_bytes!("../../assets/structures/village_well.nbt")),
+    ("village_house", include_bytes!("../../assets/structures/village_house.nbt")),
+    ("village_farm", include_bytes!("../../assets/structures/village_farm.nbt")),
+    ("ruin", include_bytes!("../../assets/structures/ruin.nbt"))
+];
+
+/// Written into saved templates so Minecraft knows which version of its blocks they use
+const DATA_VERSION: i32 = 3700;
+
+const AIR_BLOCKS: [&str; 3] = ["minecraft:air", "minecraft:cave_air", "minecraft:void_air"];
+
+/// Leaves whatever is already in the world where it is
+const STRUCTURE_VOID: &str = "minecraft:structure_void";
+
+/// Facing values in clockwise order
+const FACINGS: [&str; 4] = ["north", "east", "south", "west"];
+
+#[derive(Clone, Debug)]
+pub struct StructureTemplate {
+    pub size: Vector3<i32>,
+    pub palette: Vec<PaletteEntry>,
+    pub blocks: Vec<TemplateBlock>
+}
+
+/// Blocks are stored by identifier and property values, so templates don't depend on block ids
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PaletteEntry {
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Properties", default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, String>
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct TemplateBlock {
+    /// Position inside the template, from 0 to its size
+    pub pos: Vector3<i32>,
+    /// Index into the palette
+    pub state: usize
+}
+
+/// How a template is turned when it's placed
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Transform {
+    /// Clockwise quarter turns, looking down
+    pub rotation: u8,
+    /// Flips the template along x before it's turned
+    pub mirror: bool
+}
+
+/// Minecraft's structure file layout, anything we don't use such as entities is skipped
+#[derive(Serialize, Deserialize)]
+struct TemplateData {
+    #[serde(rename = "DataVersion", default)]
+    data_version: i32,
+    size: Vec<i32>,
+    #[serde(default)]
+    palette: Vec<PaletteEntry>,
+    /// Templates with random variations have several palettes instead of one, we always use the first
+    #[serde(default, skip_serializing)]
+    palettes: Vec<Vec<PaletteEntry>>,
+    blocks: Vec<BlockData>,
+    #[serde(default, skip_deserializing)]
+    entities: Vec<Tag>
+}
+
+#[derive(Serialize, Deserialize)]
+struct BlockData {
+    pos: Vec<i32>,
+    state: i32
+}
+
+impl StructureTemplate {
+    /// Reads a template from NBT that's usually gzipped
+    pub fn read<R: Read>(reader: R) -> io::Result<StructureTemplate> {
+        let (_, tag) = read_compressed(reader)?;
+        let mut data: TemplateData = from_tag(tag).map_err(|e| invalid(&e.to_string()))?;
+
+        let size = match data.size.as_slice() {
+            [x, y, z] if *x > 0 && *y > 0 && *z > 0 => Vector3 { x: *x, y: *y, z: *z },
+            _ => return Err(invalid("template size must be three positive numbers"))
+        };
+
+        let palette = if data.palette.is_empty() && !data.palettes.is_empty() {
+            data.palettes.swap_remove(0)
+        } else {
+            data.palette
+        };
+
+        let mut blocks = Vec::with_capacity(data.blocks.len());
+        for block in data.blocks {
+            let pos = match block.pos.as_slice() {
+                [x, y, z] => Vector3 { x: *x, y: *y, z: *z },
+                _ => return Err(invalid("block position must be three numbers"))
+            };
+
+            if pos.x < 0 || pos.y < 0 || pos.z < 0 || pos.x >= size.x || pos.y >= size.y || pos.z >= size.z {
+                return Err(invalid("block is outside the template"));
+            }
+
+            if block.state < 0 || block.state as usize >= palette.len() {
+                return Err(invalid("block state is outside the palette"));
+            }
+
+            blocks.push(TemplateBlock { pos, state: block.state as usize });
+        }
+
+        Ok(StructureTemplate {
+            size,
+            palette,
+            blocks
+        })
+    }
+
+    pub fn write(&self, path: &Path) -> io::Result<()> {
+        let data = TemplateData {
+            data_version: DATA_VERSION,
+            size: vec![self.size.x, self.size.y, self.size.z],
+            palette: self.palette.clone(),
+            palettes: Vec::new(),
+            blocks: self.blocks.iter().map(|block| BlockData {
+                pos: vec![block.pos.x, block.pos.y, block.pos.z],
+                state: block.state as i32
+            }).collect(),
+            entities: Vec::new()
+        };
+
+        let tag = to_tag(&data).map_err(|e| invalid(&e.to_string()))?;
+
+        write_gzip(BufWriter::new(File::create(path)?), "", &tag)
+    }
+
+    /// Makes a template from the blocks between two corners, air included so placing it clears out whatever is there.
+    /// get_block gives the block at a world position, None if it isn't loaded, in which case there's no template.
+    pub fn capture<F: Fn(Vector3<i32>) -> Option<BlockState>>(from: Vector3<i32>, to: Vector3<i32>, get_block: F, blocks: &BlockRegistry) -> Option<StructureTemplate> {
+        let min = Vector3 { x: from.x.min(to.x), y: from.y.min(to.y), z: from.z.min(to.z) };
+        let max = Vector3 { x: from.x.max(to.x), y: from.y.max(to.y), z: from.z.max(to.z) };
+
+        let mut states: HashMap<BlockState, usize> = HashMap::new();
+        let mut palette = Vec::new();
+        let mut template_blocks = Vec::new();
+
+        // Same order Minecraft saves them in
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                for x in min.x..=max.x {
+                    let pos = Vector3 { x, y, z };
+                    let state = get_block(pos)?;
+
+                    let index = *states.entry(state).or_insert_with(|| {
+                        palette.push(palette_entry(state, blocks));
+                        palette.len() - 1
+                    });
+
+                    template_blocks.push(TemplateBlock { pos: pos - min, state: index });
+                }
+            }
+        }
+
+        Some(StructureTemplate {
+            size: max - min + Vector3 { x: 1, y: 1, z: 1 },
+            palette,
+            blocks: template_blocks
+        })
+    }
+
+    /// The block state of every palette entry, None for blocks that shouldn't be placed such as structure voids
+    pub fn palette_states(&self, blocks: &BlockRegistry) -> Vec<Option<BlockState>> {
+        self.palette.iter().map(|entry| palette_state(entry, blocks)).collect()
+    }
+
+    /// Names in the palette that aren't known blocks
+    pub fn unknown_blocks(&self, blocks: &BlockRegistry) -> Vec<&str> {
+        self.palette.iter()
+            .map(|entry| entry.name.as_str())
+            .filter(|name| *name != STRUCTURE_VOID && !AIR_BLOCKS.contains(name) && blocks.get_by_name(name).is_none())
+            .collect()
+    }
+}
+
+impl Transform {
+    /// The size of a template once it's been turned
+    pub fn size(&self, size: Vector3<i32>) -> Vector3<i32> {
+        if self.rotation % 2 == 1 {
+            Vector3 { x: size.z, y: size.y, z: size.x }
+        } else {
+            size
+        }
+    }
+
+    /// Moves a position inside a template of size to where it ends up, still measured from the corner of the turned template
+    pub fn apply(&self, pos: Vector3<i32>, size: Vector3<i32>) -> Vector3<i32> {
+        let x = if self.mirror { size.x - 1 - pos.x } else { pos.x };
+        let z = pos.z;
+
+        let (x, z) = match self.rotation % 4 {
+            0 => (x, z),
+            1 => (size.z - 1 - z, x),
+            2 => (size.x - 1 - x, size.z - 1 - z),
+            _ => (z, size.x - 1 - x)
+        };
+
+        Vector3 { x, y: pos.y, z }
+    }
+
+    /// Turns the properties of a block state that point somewhere, so stairs and furnaces face the same way relative to the template
+    pub fn apply_state(&self, state: BlockState, blocks: &BlockRegistry) -> BlockState {
+        let block = match blocks.get_by_state(state) {
+            Some(block) => block,
+            None => return state
+        };
+
+        let mut state = state;
+
+        if let Some(index) = block.property_value(state, "facing").and_then(|facing| FACINGS.iter().position(|value| *value == facing)) {
+            // Mirroring along x swaps east and west
+            let index = if self.mirror && index % 2 == 1 { (index + 2) % 4 } else { index };
+            state = block.with_property(state, "facing", FACINGS[(index + self.rotation as usize) % 4]);
+        }
+
+        if self.rotation % 2 == 1 {
+            match block.property_value(state, "axis") {
+                Some("x") => state = block.with_property(state, "axis", "z"),
+                Some("z") => state = block.with_property(state, "axis", "x"),
+                _ => {}
+            }
+        }
+
+        state
+    }
+}
+
+fn palette_entry(state: BlockState, blocks: &BlockRegistry) -> PaletteEntry {
+    match blocks.get_by_state(state).filter(|_| !state.is_air()) {
+        Some(block) => PaletteEntry {
+            name: block.identifier.clone(),
+            properties: block.properties.iter()
+                .filter_map(|property| block.property_value(state, &property.name).map(|value| (property.name.clone(), value.to_string())))
+                .collect()
+        },
+        None => PaletteEntry {
+            name: String::from("minecraft:air"),
+            properties: BTreeMap::new()
+        }
+    }
+}
+
+/// Properties we don't have are dropped, and unknown blocks are skipped like structure voids
+fn palette_state(entry: &PaletteEntry, blocks: &BlockRegistry) -> Option<BlockState> {
+    if AIR_BLOCKS.contains(&entry.name.as_str()) {
+        return Some(BlockState::AIR);
+    }
+
+    let block = blocks.get_by_name(&entry.name)?;
+
+    Some(entry.properties.iter().fold(block.default_state(), |state, (name, value)| block.with_property(state, name, value)))
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+pub struct TemplateRegistry {
+    templates: HashMap<String, StructureTemplate>
+}
+
+impl TemplateRegistry {
+    /// Templates are named after their file, so a file in resources replaces the built in template of the same name
+    pub fn load(settings: &SettingsService, blocks: &BlockRegistry) -> TemplateRegistry {
+        let mut templates = HashMap::new();
+
+        for (name, data) in DEFAULT_TEMPLATES.iter() {
+            match StructureTemplate::read(*data) {
+                Ok(template) => {
+                    templates.insert(name.to_string(), template);
+                }
+                Err(e) => {
+                    log_error!(format!("Invalid built in structure template {}: {}", name, e));
+                }
+            }
+        }
+
+        if let Ok(files) = fs::read_dir(template_directory(settings)) {
+            for file in files.filter_map(|file| file.ok()).map(|file| file.path()) {
+                let name = match (file.extension(), file.file_stem()) {
+                    (Some(extension), Some(name)) if extension == "nbt" => name.to_string_lossy().to_string(),
+                    _ => continue
+                };
+
+                match File::open(&file).and_then(|file| StructureTemplate::read(BufReader::new(file))) {
+                    Ok(template) => {
+                        templates.insert(name, template);
+                    }
+                    Err(e) => {
+                        log_error!(format!("Failed to read structure template {}: {}", file.to_string_lossy(), e));
+                    }
+                }
+            }
+        }
+
+        for (name, template) in &templates {
+            for block in template.unknown_blocks(blocks) {
+                log_error!(format!("Structure template {} uses unknown block {}, it won't be placed", name, block));
+            }
+        }
+
+        log!("Loaded {} structure templates", templates.len());
+
+        TemplateRegistry {
+            templates
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&StructureTemplate> {
+        self.templates.get(name)
+    }
+}
+
+#[cfg(test)]
+impl TemplateRegistry {
+    /// Only the templates that come with the game, for tests that build structures
+    pub fn built_in() -> TemplateRegistry {
+        TemplateRegistry {
+            templates: DEFAULT_TEMPLATES.iter()
+                .map(|(name, data)| (name.to_string(), StructureTemplate::read(*data).unwrap()))
+                .collect()
+        }
+    }
+}
+
+/// Templates are saved as name.nbt, so names that could reach outside the template directory aren't allowed
+pub fn valid_template_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['/', '\\']) && !name.contains("..")
+}
+
+/// Where templates saved in game go, and extra templates are read from
+pub fn template_directory(settings: &SettingsService) -> String {
+    format!("{}resources/structures/", settings.path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every rotation with and without mirroring
+    fn transforms() -> Vec<Transform> {
+        (0..4).flat_map(|rotation| vec![Transform { rotation, mirror: false }, Transform { rotation, mirror: true }]).collect()
+    }
+
+    fn direction(facing: &str) -> Vector3<i32> {
+        match facing {
+            "north" => Vector3 { x: 0, y: 0, z: -1 },
+            "east" => Vector3 { x: 1, y: 0, z: 0 },
+            "south" => Vector3 { x: 0, y: 0, z: 1 },
+            _ => Vector3 { x: -1, y: 0, z: 0 }
+        }
+    }
+
+    #[test]
+    fn turned_sizes_swap_x_and_z_on_quarter_turns() {
+        let size = Vector3 { x: 5, y: 3, z: 2 };
+
+        for transform in transforms() {
+            let expected = if transform.rotation % 2 == 1 { Vector3 { x: 2, y: 3, z: 5 } } else { size };
+            assert_eq!(transform.size(size), expected);
+        }
+    }
+
+    #[test]
+    fn apply_moves_every_block_inside_the_turned_template_once() {
+        let size = Vector3 { x: 5, y: 2, z: 3 };
+
+        for transform in transforms() {
+            let turned = transform.size(size);
+            let mut seen = std::collections::HashSet::new();
+
+            for x in 0..size.x {
+                for y in 0..size.y {
+                    for z in 0..size.z {
+                        let pos = transform.apply(Vector3 { x, y, z }, size);
+
+                        assert!(pos.x >= 0 && pos.z >= 0 && pos.x < turned.x && pos.z < turned.z, "{:?} moved {:?} outside", transform, pos);
+                        assert_eq!(pos.y, y);
+                        assert!(seen.insert((pos.x, pos.y, pos.z)), "{:?} moved two blocks to {:?}", transform, pos);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn apply_turns_clockwise_looking_down() {
+        let size = Vector3 { x: 5, y: 1, z: 3 };
+        let north_west = Vector3 { x: 0, y: 0, z: 0 };
+
+        let corner = |rotation, mirror| Transform { rotation, mirror }.apply(north_west, size);
+        assert_eq!(corner(0, false), Vector3 { x: 0, y: 0, z: 0 });
+        assert_eq!(corner(1, false), Vector3 { x: 2, y: 0, z: 0 });
+        assert_eq!(corner(2, false), Vector3 { x: 4, y: 0, z: 2 });
+        assert_eq!(corner(3, false), Vector3 { x: 0, y: 0, z: 4 });
+        assert_eq!(corner(0, true), Vector3 { x: 4, y: 0, z: 0 });
+        assert_eq!(corner(1, true), Vector3 { x: 2, y: 0, z: 4 });
+    }
+
+    #[test]
+    fn facing_turns_with_the_blocks() {
+        let blocks = BlockRegistry::built_in();
+        let size = Vector3 { x: 4, y: 1, z: 4 };
+        let middle = Vector3 { x: 1, y: 0, z: 1 };
+
+        for name in ["minecraft:furnace", "minecraft:oak_stairs"].iter() {
+            let block = blocks.get_by_name(name).unwrap();
+
+            for facing in FACINGS.iter() {
+                let state = block.with_property(block.default_state(), "facing", facing);
+
+                for transform in transforms() {
+                    // The block next to this one in the direction it faces must still be the one it faces once turned
+                    let step = transform.apply(middle + direction(facing), size) - transform.apply(middle, size);
+                    let turned = block.property_value(transform.apply_state(state, &blocks), "facing").unwrap();
+
+                    assert_eq!(step, direction(turned), "{} facing {} with {:?}", name, facing, transform);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn axis_swaps_on_quarter_turns() {
+        let blocks = BlockRegistry::built_in();
+        let log = blocks.get_by_name("minecraft:oak_log").unwrap();
+
+        for transform in transforms() {
+            for (axis, turned) in [("x", "z"), ("z", "x"), ("y", "y")].iter() {
+                let state = transform.apply_state(log.with_property(log.default_state(), "axis", axis), &blocks);
+                let expected = if transform.rotation % 2 == 1 { *turned } else { *axis };
+
+                assert_eq!(log.property_value(state, "axis"), Some(expected));
+            }
+        }
+
+        let stone = blocks.default_state("minecraft:stone").unwrap();
+        assert_eq!(Transform { rotation: 1, mirror: true }.apply_state(stone, &blocks), stone);
+    }
+
+    #[test]
+    fn templates_read_back_what_was_written() {
+        let blocks = BlockRegistry::built_in();
+        let log = blocks.get_by_name("minecraft:oak_log").unwrap();
+        let sideways = log.with_property(log.default_state(), "axis", "z");
+        let stone = blocks.default_state("minecraft:stone").unwrap();
+
+        let from = Vector3 { x: 10, y: 60, z: -4 };
+        let to = Vector3 { x: 7, y: 62, z: -3 };
+        let world = |pos: Vector3<i32>| Some(match (pos.x + pos.y + pos.z).rem_euclid(3) {
+            0 => stone,
+            1 => sideways,
+            _ => BlockState::AIR
+        });
+
+        let template = StructureTemplate::capture(from, to, world, &blocks).unwrap();
+        assert_eq!(template.size, Vector3 { x: 4, y: 3, z: 2 });
+        assert_eq!(template.blocks.len(), 24);
+
+        let path = std::env::temp_dir().join(format!("template_round_trip_{}.nbt", std::process::id()));
+        template.write(&path).unwrap();
+        let read = StructureTemplate::read(File::open(&path).unwrap());
+        fs::remove_file(&path).unwrap();
+        let read = read.unwrap();
+
+        assert_eq!(read.size, template.size);
+        assert_eq!(read.palette_states(&blocks), template.palette_states(&blocks));
+        assert_eq!(read.blocks.len(), template.blocks.len());
+
+        let min = Vector3 { x: 7, y: 60, z: -4 };
+        for block in &read.blocks {
+            assert_eq!(read.palette_states(&blocks)[block.state], world(min + block.pos));
+        }
+    }
+
+    #[test]
+    fn built_in_templates_only_use_known_blocks() {
+        let blocks = BlockRegistry::built_in();
+        let templates = TemplateRegistry::built_in();
+
+        for (name, _) in DEFAULT_TEMPLATES.iter() {
+            assert!(templates.get(name).unwrap().unknown_blocks(&blocks).is_empty(), "{} uses unknown blocks", name);
+        }
+    }
+
+    #[test]
+    fn template_names_stay_inside_the_template_directory() {
+        assert!(valid_template_name("village_house"));
+        assert!(valid_template_name("template_1700000000"));
+        assert!(!valid_template_name(""));
+        assert!(!valid_template_name("../level"));
+        assert!(!valid_template_name(".."));
+        assert!(!valid_template_name("houses/small"));
+        assert!(!valid_template_name("C:\\houses"));
+    }
+}
@@ -0,0 +1,162 @@
+#!/usr/bin/env python3
+#
+# Writes r.0.0.mca, a small Anvil region used by the importer tests. The block at x, y, z of every section
+# uses palette entry (x + 3 * y + 7 * z) % len(palette), the tests work out the same pattern.
+#
+
+import gzip
+import struct
+import zlib
+
+TAG_BYTE, TAG_INT, TAG_STRING, TAG_LIST, TAG_COMPOUND, TAG_LONG_ARRAY = 1, 3, 8, 9, 10, 12
+
+PALETTE = [
+    ("minecraft:air", {}),
+    ("minecraft:stone", {}),
+    ("minecraft:dirt", {}),
+    ("minecraft:grass_block", {}),
+    ("minecraft:sand", {}),
+    ("minecraft:sandstone", {}),
+    ("minecraft:oak_log", {"axis": "x"}),
+    ("minecraft:oak_planks", {}),
+    ("minecraft:smooth_stone_slab", {"type": "top", "waterlogged": "false"}),
+    ("minecraft:glass", {}),
+    ("minecraft:cave_air", {}),
+    ("minecraft:gravel", {}),
+    ("minecraft:coal_ore", {}),
+    ("minecraft:iron_ore", {}),
+    ("minecraft:gold_ore", {}),
+    ("minecraft:not_a_real_block", {}),
+    ("minecraft:diamond_ore", {}),
+]
+
+
+def string(value):
+    data = value.encode()
+    return struct.pack(">H", len(data)) + data
+
+
+def named(tag_id, name, payload):
+    return bytes([tag_id]) + string(name) + payload
+
+
+def compound(children):
+    return b"".join(children) + b"\0"
+
+
+def palette_entry(name, properties):
+    children = [named(TAG_STRING, "Name", string(name))]
+    if properties:
+        children.append(named(TAG_COMPOUND, "Properties", compound(
+            [named(TAG_STRING, key, string(value)) for key, value in properties.items()])))
+    return compound(children)
+
+
+def palette_list(palette):
+    return bytes([TAG_COMPOUND]) + struct.pack(">i", len(palette)) + b"".join(palette_entry(*entry) for entry in palette)
+
+
+def long_array(values):
+    return struct.pack(">i", len(values)) + b"".join(struct.pack(">q", value - (1 << 64) if value >= 1 << 63 else value) for value in values)
+
+
+def indices(palette_len):
+    return [(x + 3 * y + 7 * z) % palette_len for y in range(16) for z in range(16) for x in range(16)]
+
+
+def bits_for(palette_len):
+    bits = 4
+    while (1 << bits) < palette_len:
+        bits += 1
+    return bits
+
+
+def pack_spanning(values, bits):
+    total = 0
+    for i, value in enumerate(values):
+        total |= value << (i * bits)
+    longs = (len(values) * bits + 63) // 64
+    return [(total >> (64 * i)) & ((1 << 64) - 1) for i in range(longs)]
+
+
+def pack_padded(values, bits):
+    per_long = 64 // bits
+    longs = []
+    for start in range(0, len(values), per_long):
+        long = 0
+        for i, value in enumerate(values[start:start + per_long]):
+            long |= value << (i * bits)
+        longs.append(long)
+    return longs
+
+
+def old_section(y, palette):
+    bits = bits_for(len(palette))
+    return compound([
+        named(TAG_BYTE, "Y", struct.pack(">b", y)),
+        named(TAG_LIST, "Palette", palette_list(palette)),
+        named(TAG_LONG_ARRAY, "BlockStates", long_array(pack_spanning(indices(len(palette)), bits))),
+    ])
+
+
+def new_section(y, palette):
+    children = [named(TAG_LIST, "palette", palette_list(palette))]
+    if len(palette) > 1:
+        children.append(named(TAG_LONG_ARRAY, "data", long_array(pack_padded(indices(len(palette)), bits_for(len(palette))))))
+    return compound([
+        named(TAG_BYTE, "Y", struct.pack(">b", y)),
+        named(TAG_COMPOUND, "block_states", compound(children)),
+    ])
+
+
+def sections(section_list):
+    return bytes([TAG_COMPOUND]) + struct.pack(">i", len(section_list)) + b"".join(section_list)
+
+
+# 1.13 layout at column 0, 0: 17 entries need 5 bits which spill over into the next long, 2 entries use 4 bits which don't
+old_column = named(TAG_COMPOUND, "", compound([
+    named(TAG_INT, "DataVersion", struct.pack(">i", 1343)),
+    named(TAG_COMPOUND, "Level", compound([
+        named(TAG_INT, "xPos", struct.pack(">i", 0)),
+        named(TAG_INT, "zPos", struct.pack(">i", 0)),
+        named(TAG_LIST, "Sections", sections([
+            old_section(-1, PALETTE[:2]),
+            old_section(0, PALETTE),
+            old_section(1, PALETTE[:2]),
+        ])),
+    ])),
+]))
+
+# 1.18 layout at column 2, 1: 5 bit entries padded to 12 per long, and a section of only stone without any data
+new_column = named(TAG_COMPOUND, "", compound([
+    named(TAG_INT, "DataVersion", struct.pack(">i", 2860)),
+    named(TAG_INT, "xPos", struct.pack(">i", 2)),
+    named(TAG_INT, "zPos", struct.pack(">i", 1)),
+    named(TAG_LIST, "sections", sections([
+        new_section(0, PALETTE),
+        new_section(2, PALETTE[1:2]),
+    ])),
+]))
+
+SECTOR = 4096
+columns = [((0, 0), 2, zlib.compress(old_column)), ((2, 1), 1, gzip.compress(new_column, mtime=0))]
+
+locations = bytearray(SECTOR)
+timestamps = bytearray(SECTOR)
+body = bytearray()
+sector = 2
+
+for (x, z), compression, data in columns:
+    payload = struct.pack(">I", len(data) + 1) + bytes([compression]) + data
+    count = (len(payload) + SECTOR - 1) // SECTOR
+    payload += bytes(count * SECTOR - len(payload))
+
+    index = x + z * 32
+    struct.pack_into(">I", locations, index * 4, (sector << 8) | count)
+    struct.pack_into(">I", timestamps, index * 4, 1600000000)
+
+    body += payload
+    sector += count
+
+with open("r.0.0.mca", "wb") as file:
+    file.write(locations + timestamps + body)